@@ -7,7 +7,7 @@ use gltf::Glb;
 
 use voxel_tiler_core::build_voxelizer::{BuildSimpleVoxelizerDefault, BuildVoxelizer};
 use voxel_tiler_core::collection::PointCloud;
-use voxel_tiler_core::glb::{ColorMode, GlbGen};
+use voxel_tiler_core::glb::{ColorMode, ColorOutput, GlbGen};
 use voxel_tiler_core::mesh::{Mesher, ValidSide};
 use voxel_tiler_core::ply::PlyStructs;
 use voxel_tiler_core::voxelizer::Resolution;
@@ -16,7 +16,10 @@ fn main()
 {
     let file = BufReader::new(File::open("examples/data-source/colored_stanford_bunny.ply").unwrap());
 
-    let point_cloud = PointCloud::from_ply(file);
+    let mut point_cloud = PointCloud::from_ply(file);
+
+    // ボクセル化の前に、周囲から大きく離れた点を取り除きます
+    point_cloud.remove_statistical_outliers(8, 2.);
 
     let resolution = Resolution::Mater(0.03);
 
@@ -27,7 +30,7 @@ fn main()
     create_dir_all("examples/exports").expect("I/O error");
 
     {
-        let glb = Glb::from_voxel_mesh(mesh.clone(), ColorMode::Srgb).unwrap();
+        let glb = Glb::from_voxel_mesh(mesh.clone(), ColorMode::Srgb, ColorOutput::Material).unwrap();
 
         let writer = File::create("examples/exports/bunny.glb").expect("I/O error");
         glb.to_writer(writer).expect("glTF binary output error");
@@ -28,6 +28,26 @@ where
         voxelizer.add(pc);
         voxelizer.finish()
     }
+
+    /// 複数に分割された点群を順にボクセライザーに追加し、ボクセル化を行います。
+    /// 点群全体を一度にメモリ上に保持する必要がないため、巨大な点群を扱う場合に使用してください。
+    ///
+    /// # Errors
+    ///
+    /// + `batches`がエラーを返した場合、その時点で処理を中断してエラーを返します。
+    fn voxelize_batches<T, I, E>(batches: I, resolution: Resolution) -> Result<Option::OutVC, E>
+    where
+        T: VoxelCollection<Option::InPoint, Option::Weight, Option::Color>,
+        I: IntoIterator<Item=Result<T, E>>,
+    {
+        let mut voxelizer = Self::build_voxelizer(resolution);
+
+        for pc in batches {
+            voxelizer.add(pc?);
+        }
+
+        Ok(voxelizer.finish())
+    }
 }
 
 ///　標準で用意されたオプションでボクセライザーを構築するための構造体です。
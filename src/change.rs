@@ -0,0 +1,166 @@
+use fxhash::FxBuildHasher;
+use num::cast::AsPrimitive;
+
+use crate::collection::{HMap3DVoxelCollection, VoxelCollection, VoxelMergeRule};
+use crate::element::{Color, Int, Point3D, UInt, Voxel};
+use crate::mesh::{Mesher, ValidSide, VoxelMesh};
+
+/// 同じ格子でボクセル化された2時期のボクセルデータを比較した結果です。
+/// 座標値はオフセットを適用した値で比較し、保持します。
+#[derive(Clone, Debug)]
+pub struct VoxelChange<P, W, C>
+where
+    P: Int,
+    W: UInt,
+    C: UInt,
+{
+    added: Vec<(Point3D<P>, Voxel<C, W>)>,
+    removed: Vec<(Point3D<P>, Voxel<C, W>)>,
+    unchanged: Vec<(Point3D<P>, Voxel<C, W>)>,
+    resolution: f64,
+}
+
+impl<P, W, C> VoxelChange<P, W, C>
+where
+    P: Int,
+    W: UInt + AsPrimitive<C>,
+    C: UInt + AsPrimitive<W>,
+{
+    /// 変化前の`before`と変化後の`after`を比較します。
+    /// `after`にのみ存在するボクセルを追加、`before`にのみ存在するボクセルを削除、両方に存在するボクセルを変化なしとして分類します。
+    /// 変化なしのボクセルは`after`の値を保持します。
+    ///
+    /// # Errors
+    ///
+    /// + 2つの`VoxelCollection`の分解能が異なる場合、エラーを返します。
+    pub fn detect<BVC, AVC>(before: &BVC, after: &AVC) -> Result<Self, anyhow::Error>
+    where
+        BVC: VoxelCollection<P, W, C>,
+        AVC: VoxelCollection<P, W, C>,
+    {
+        let added = after.difference(before)?.into_vec();
+        let removed = before.difference(after)?.into_vec();
+        let unchanged = after.intersection(before, VoxelMergeRule::Left)?.into_vec();
+
+        Ok(Self {
+            added,
+            removed,
+            unchanged,
+            resolution: after.get_resolution(),
+        })
+    }
+
+    /// 追加されたボクセルを返します。
+    pub fn added(&self) -> &[(Point3D<P>, Voxel<C, W>)] {
+        &self.added
+    }
+
+    /// 削除されたボクセルを返します。
+    pub fn removed(&self) -> &[(Point3D<P>, Voxel<C, W>)] {
+        &self.removed
+    }
+
+    /// 変化のなかったボクセルを返します。
+    pub fn unchanged(&self) -> &[(Point3D<P>, Voxel<C, W>)] {
+        &self.unchanged
+    }
+
+    /// 比較したボクセルの分解能を返します。
+    pub fn get_resolution(&self) -> f64 {
+        self.resolution
+    }
+
+    // ボクセル1つあたりの体積(m³)
+    fn voxel_volume(&self) -> f64 {
+        self.resolution.powi(3)
+    }
+
+    /// 追加されたボクセルの体積の合計(m³)を返します。
+    pub fn added_volume(&self) -> f64 {
+        self.added.len() as f64 * self.voxel_volume()
+    }
+
+    /// 削除されたボクセルの体積の合計(m³)を返します。
+    pub fn removed_volume(&self) -> f64 {
+        self.removed.len() as f64 * self.voxel_volume()
+    }
+
+    /// 変化のなかったボクセルの体積の合計(m³)を返します。
+    pub fn unchanged_volume(&self) -> f64 {
+        self.unchanged.len() as f64 * self.voxel_volume()
+    }
+
+    /// 追加されたボクセルの体積から削除されたボクセルの体積を引いた値(m³)を返します。
+    pub fn net_volume(&self) -> f64 {
+        self.added_volume() - self.removed_volume()
+    }
+
+    /// 追加されたボクセルを緑、削除されたボクセルを赤で塗り分けたボクセルメッシュを生成します。
+    /// 変化のなかったボクセルは含まれません。
+    pub fn to_mesh(&self, valid_side: ValidSide) -> VoxelMesh<P, C>
+    where
+        P: AsPrimitive<i32>,
+        i32: AsPrimitive<P>,
+    {
+        let max = C::max_value();
+        let green = Color::new([C::zero(), max, C::zero()]);
+        let red = Color::new([max, C::zero(), C::zero()]);
+
+        let voxels = self.added.iter().map(|(point, _)| (*point, Voxel::new(green)))
+            .chain(self.removed.iter().map(|(point, _)| (*point, Voxel::new(red))))
+            .collect();
+
+        let vc = HMap3DVoxelCollection::<P, W, C, FxBuildHasher>::builder()
+            .voxels(voxels)
+            .resolution(self.resolution)
+            .build();
+
+        Mesher::meshing(vc, valid_side)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fxhash::FxBuildHasher;
+
+    use crate::change::VoxelChange;
+    use crate::collection::{HMap3DVoxelCollection, Vec3VoxelCollection, VoxelCollection};
+    use crate::element::{Color, Point3D};
+    use crate::mesh::ValidSide;
+
+    #[test]
+    fn test_detect() {
+        let before = Vec3VoxelCollection::<i32, u8, u8>::builder()
+            .points([0, 1, 2].iter().map(|&x| (Point3D::new([x, 0, 0]), Color::new([100, 100, 100]))).collect())
+            .resolution(0.5)
+            .build();
+
+        let after = HMap3DVoxelCollection::<i32, u8, u8, FxBuildHasher>::builder()
+            .points([1, 2, 3, 4].iter().map(|&x| (Point3D::new([x, 0, 0]), Color::new([50, 50, 50]))).collect())
+            .resolution(0.5)
+            .build();
+
+        let change = VoxelChange::detect(&before, &after).unwrap();
+
+        let mut added = change.added().iter().map(|(point, _)| point[0]).collect::<Vec<_>>();
+        added.sort();
+        assert_eq!(added, vec![3, 4]);
+        assert_eq!(change.removed().iter().map(|(point, _)| point[0]).collect::<Vec<_>>(), vec![0]);
+        assert_eq!(change.unchanged().len(), 2);
+        assert!(change.unchanged().iter().all(|(_, voxel)| voxel.color == Color::new([50, 50, 50])));
+
+        assert_eq!(change.added_volume(), 0.25);
+        assert_eq!(change.removed_volume(), 0.125);
+        assert_eq!(change.unchanged_volume(), 0.25);
+        assert_eq!(change.net_volume(), 0.125);
+
+        let mesh = change.to_mesh(ValidSide::all());
+        assert_eq!(mesh.resolution, 0.5);
+        assert!(mesh.faces.contains_key(&Color::new([0, 255, 0])));
+        assert!(mesh.faces.contains_key(&Color::new([255, 0, 0])));
+        assert_eq!(mesh.faces.len(), 2);
+
+        let coarse = HMap3DVoxelCollection::<i32, u8, u8, FxBuildHasher>::builder().resolution(1.).build();
+        assert!(VoxelChange::detect(&before, &coarse).is_err());
+    }
+}
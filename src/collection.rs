@@ -1,10 +1,14 @@
+use std::collections::HashMap;
 use std::hash::BuildHasher;
 use std::marker::PhantomData;
-use std::vec;
+use std::{iter, mem, vec};
 
 use anyhow::anyhow;
 use dashmap::DashMap;
+use fxhash::FxHashMap;
 use num::traits::AsPrimitive;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 
 use crate::collection::private::PrivateVoxelCollectionMethod;
 use crate::element::{Color, Int, Number, Point2D, Point3D, UInt, Voxel};
@@ -12,6 +16,7 @@ use crate::element::{Color, Int, Number, Point2D, Point3D, UInt, Voxel};
 mod private {
     use num::cast::AsPrimitive;
 
+    use crate::collection::VoxelMergeRule;
     use crate::element::{Color, Number, Point3D, UInt, Voxel};
 
     pub trait PrivateVoxelCollectionMethod<P: Number, W: UInt, C: UInt>
@@ -48,6 +53,7 @@ mod private {
         }
 
         // 重みを考慮して色を加算する
+        // `voxel.color`は`voxel.weight`個の頂点の色の総和として扱う
         fn add_color_with_weight_check(current_voxel: &mut Voxel<C, W>, voxel: Voxel<C, W>)
         where
             C: AsPrimitive<W>,
@@ -58,12 +64,31 @@ mod private {
             }
 
             if current_voxel.weight.checked_add(&voxel.weight).is_none() {
+                // 重みの上限を超える分は、平均色を用いて按分する
                 let weight = W::max_value() - current_voxel.weight;
                 current_voxel.weight += weight;
-                current_voxel.color += voxel.color * Color::from(weight).as_::<C>();
+                current_voxel.color += voxel.color / Color::from(voxel.weight).as_::<C>() * Color::from(weight).as_::<C>();
             } else {
                 current_voxel.weight += voxel.weight;
-                current_voxel.color += voxel.color * Color::from(voxel.weight).as_::<C>();
+                current_voxel.color += voxel.color;
+            }
+        }
+
+        // 2つの集合の両方に存在するボクセルを、規則に従って1つにまとめる
+        fn merge_voxel(rule: VoxelMergeRule, left: Voxel<C, W>, right: Voxel<C, W>) -> Voxel<C, W>
+        where
+            C: AsPrimitive<W>,
+            W: AsPrimitive<C>,
+        {
+            match rule {
+                VoxelMergeRule::Sum => {
+                    let mut voxel = left;
+                    Self::add_color_with_weight_check(&mut voxel, right);
+                    voxel
+                }
+                VoxelMergeRule::Left => left,
+                VoxelMergeRule::Right => right,
+                VoxelMergeRule::MaxWeight => if right.weight > left.weight { right } else { left },
             }
         }
     }
@@ -141,6 +166,254 @@ where
     }
 }
 
+/// 集合演算において、両方の`VoxelCollection`に存在する座標のボクセルをまとめる規則です。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VoxelMergeRule {
+    /// 色と重みを加算します。`insert`や`merge`と同じ規則です。
+    #[default]
+    Sum,
+    /// 演算の左辺(`self`)のボクセルを使用します。
+    Left,
+    /// 演算の右辺のボクセルを使用します。
+    Right,
+    /// 重みが大きい方のボクセルを使用します。重みが等しい場合は左辺のボクセルを使用します。
+    MaxWeight,
+}
+
+/// ボクセルの隣接関係を表します。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Connectivity {
+    /// 面を共有する6近傍です。
+    #[default]
+    Six,
+    /// 面または辺を共有する18近傍です。
+    Eighteen,
+    /// 面、辺または頂点を共有する26近傍です。
+    TwentySix,
+}
+
+impl Connectivity {
+    /// 近傍に含まれるボクセルの数を返します。
+    pub fn size(self) -> usize {
+        match self {
+            Connectivity::Six => 6,
+            Connectivity::Eighteen => 18,
+            Connectivity::TwentySix => 26,
+        }
+    }
+
+    /// 近傍に含まれるボクセルの相対座標を返します。
+    pub fn offsets(self) -> impl Iterator<Item=[i8; 3]> {
+        // 0以外の成分の数が、6近傍では1つ、18近傍では2つ、26近傍では3つまでになる
+        let max_nonzero = match self {
+            Connectivity::Six => 1,
+            Connectivity::Eighteen => 2,
+            Connectivity::TwentySix => 3,
+        };
+
+        (0..27).map(|i| [i / 9 - 1, i / 3 % 3 - 1, i % 3 - 1]).filter(move |offset| {
+            let nonzero = offset.iter().filter(|&&a| a != 0).count();
+            0 < nonzero && nonzero <= max_nonzero
+        })
+    }
+
+    /// 指定した座標値の近傍の座標値を返します。
+    /// 座標値の型で表現できない近傍は含まれません。
+    pub fn neighbors<P: Int>(self, point: Point3D<P>) -> impl Iterator<Item=Point3D<P>> {
+        self.offsets().filter_map(move |offset| {
+            let mut neighbor = point;
+
+            for axis in 0..3 {
+                neighbor[axis] = match offset[axis] {
+                    -1 => point[axis].checked_sub(&P::one())?,
+                    1 => point[axis].checked_add(&P::one())?,
+                    _ => point[axis],
+                };
+            }
+
+            Some(neighbor)
+        })
+    }
+}
+
+/// 連結成分の1つを表します。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VoxelComponent<P: Number> {
+    /// 連結成分に含まれるボクセルの数です。
+    pub count: usize,
+    /// 連結成分に含まれるボクセルの座標値の最小値と最大値です。
+    pub bounds: (Point3D<P>, Point3D<P>),
+}
+
+/// 連結成分のラベリングの結果です。
+/// ラベルは連結成分の通し番号で、[`ComponentLabels::components`]の添字と対応します。
+#[derive(Clone, Debug)]
+pub struct ComponentLabels<P: Number> {
+    labels: FxHashMap<Point3D<P>, usize>,
+    components: Vec<VoxelComponent<P>>,
+}
+
+impl<P: Number> ComponentLabels<P> {
+    /// 指定した座標値のボクセルが属する連結成分のラベルを返します。
+    /// ボクセルが存在しない場合は`None`を返します。
+    pub fn label(&self, point: &Point3D<P>) -> Option<usize> {
+        self.labels.get(point).copied()
+    }
+
+    /// すべての連結成分を返します。
+    pub fn components(&self) -> &[VoxelComponent<P>] {
+        &self.components
+    }
+
+    /// 連結成分の数を返します。
+    pub fn len(&self) -> usize {
+        self.components.len()
+    }
+
+    /// 連結成分が1つもない場合に`true`を返します。
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty()
+    }
+}
+
+// 座標値ごとにボクセルをまとめたハッシュマップ
+type VoxelMap<P, C, W> = FxHashMap<Point3D<P>, Voxel<C, W>>;
+
+// 座標値ごとにボクセルをまとめたハッシュマップを返す
+// `PointCloud`のように同じ座標値が複数含まれる場合は、色と重みを加算する
+fn voxel_map<P, W, C, VC>(vc: &VC) -> VoxelMap<P, C, W>
+where
+    P: Number,
+    W: UInt + AsPrimitive<C>,
+    C: UInt + AsPrimitive<W>,
+    VC: VoxelCollection<P, W, C>,
+{
+    let mut map = FxHashMap::default();
+
+    vc.iter().for_each(|(point, voxel)| {
+        map.entry(point).and_modify(|current_voxel| {
+            VC::add_color_with_weight_check(current_voxel, voxel);
+        }).or_insert(voxel);
+    });
+
+    map
+}
+
+// `f64`で集計した色の和と重みの和から、色と重みの型で表現できるボクセルを返す
+// 平均色を保ったまま、重みと色の和がそれぞれの型の最大値を超えないように重みを飽和させる
+fn voxel_from_sum<C, W>(color: [f64; 3], weight: f64) -> Voxel<C, W>
+where
+    C: UInt + AsPrimitive<f64>,
+    W: UInt + AsPrimitive<f64>,
+    f64: AsPrimitive<C> + AsPrimitive<W>,
+{
+    if weight <= 0. {
+        return Voxel { color: Color::default(), weight: W::zero() };
+    }
+
+    let max_color = color.into_iter().fold(0_f64, f64::max);
+    let c_max = AsPrimitive::<f64>::as_(C::max_value());
+
+    let mut saturated = weight.min(AsPrimitive::<f64>::as_(W::max_value()));
+    if max_color > c_max {
+        saturated = saturated.min((c_max * weight / max_color).floor()).max(1.);
+    }
+
+    Voxel {
+        color: Color::new(color.map(|a| (a / weight * saturated).round().min(c_max).as_())),
+        weight: saturated.as_(),
+    }
+}
+
+/// 内部を埋める際に走査する、外接直方体に含まれるボクセルの数の上限です。
+const MAX_FILL_CELLS: usize = 1 << 28;
+
+// `bounds`の範囲で、範囲の外側から6近傍で到達できない空のボクセルを返す
+// 色は、z軸方向に走査したときに直前にあるボクセルの平均色とする
+fn enclosed_voxels<P, W, C>(occupied: &VoxelMap<P, C, W>, bounds: (Point3D<P>, Point3D<P>)) -> Result<VoxelMap<P, C, W>, anyhow::Error>
+where
+    P: Int + AsPrimitive<usize>,
+    W: UInt + AsPrimitive<C>,
+    C: UInt + AsPrimitive<W>,
+    usize: AsPrimitive<P>,
+{
+    let (min, max) = bounds;
+
+    let size = [0, 1, 2].map(|axis| {
+        max[axis].checked_sub(&min[axis]).and_then(|a| AsPrimitive::<usize>::as_(a).checked_add(1))
+    });
+    let [Some(size_x), Some(size_y), Some(size_z)] = size else {
+        return Err(anyhow!("The bounding box is too large to fill"));
+    };
+    let size = [size_x, size_y, size_z];
+
+    // 各軸に3ボクセル以上なければ、内部の空間は存在しない
+    if size.iter().any(|&a| a < 3) {
+        return Ok(VoxelMap::default());
+    }
+
+    let count = size[0].checked_mul(size[1]).and_then(|a| a.checked_mul(size[2]))
+        .filter(|&count| count <= MAX_FILL_CELLS)
+        .ok_or_else(|| anyhow!("The bounding box is too large to fill"))?;
+
+    let index = |[x, y, z]: [usize; 3]| (z * size[1] + y) * size[0] + x;
+    let point = |cell: [usize; 3]| min + Point3D::new(cell.map(|a| a.as_()));
+
+    // 境界の面上にある空のボクセルを起点として、外部を探索する
+    let mut outside = vec![false; count];
+    let mut stack = Vec::new();
+
+    for z in 0..size[2] {
+        for y in 0..size[1] {
+            for x in 0..size[0] {
+                let cell = [x, y, z];
+                let on_face = (0..3).any(|axis| cell[axis] == 0 || cell[axis] == size[axis] - 1);
+
+                if on_face && !occupied.contains_key(&point(cell)) {
+                    outside[index(cell)] = true;
+                    stack.push(cell);
+                }
+            }
+        }
+    }
+
+    while let Some(cell) = stack.pop() {
+        for axis in 0..3 {
+            for next in [cell[axis].checked_sub(1), Some(cell[axis] + 1)] {
+                let Some(next) = next.filter(|&a| a < size[axis]) else { continue };
+
+                let mut neighbor = cell;
+                neighbor[axis] = next;
+
+                if !outside[index(neighbor)] && !occupied.contains_key(&point(neighbor)) {
+                    outside[index(neighbor)] = true;
+                    stack.push(neighbor);
+                }
+            }
+        }
+    }
+
+    let mut interior = VoxelMap::default();
+
+    for y in 0..size[1] {
+        for x in 0..size[0] {
+            let mut color = Color::default();
+
+            for z in 0..size[2] {
+                let cell = [x, y, z];
+
+                if let Some(voxel) = occupied.get(&point(cell)) {
+                    color = Color::new(voxel.color.data.map(|a| a / voxel.weight.as_()));
+                } else if !outside[index(cell)] {
+                    interior.insert(point(cell), Voxel::new(color));
+                }
+            }
+        }
+    }
+
+    Ok(interior)
+}
+
 ///　ボクセルの集合や点群を操作するためのトレイトです。
 pub trait VoxelCollection<P, W, C>: PrivateVoxelCollectionMethod<P, W, C> + Default + Clone
 where
@@ -197,36 +470,90 @@ where
         self.set_offset(min);
     }
 
+    /// 登録されているすべての座標とボクセルのタプルを順に返すイテレータを返します。
+    /// `to_vec`と異なり、すべての要素を格納する配列を確保しません。
+    /// オフセットを適用した結果を得たい場合、`iter_with_offset`メソッドを使用してください。
+    fn iter(&self) -> impl Iterator<Item=(Point3D<P>, Voxel<C, W>)>;
+
+    /// インスタンスを消費して、登録されているすべての座標とボクセルのタプルを順に返すイテレータを返します。
+    /// `into_vec`と異なり、すべての要素を格納する配列を確保しません。
+    fn into_iter(self) -> impl Iterator<Item=(Point3D<P>, Voxel<C, W>)>;
+
+    /// 登録されているすべての座標とボクセルのタプルを、複数のスレッドで並列に処理するイテレータを返します。
+    /// 要素の順序は保証されません。
+    #[cfg(feature = "rayon")]
+    fn par_iter(&self) -> impl ParallelIterator<Item=(Point3D<P>, Voxel<C, W>)>;
+
+    /// 登録されているすべての座標とボクセルのタプルを順に返すイテレータを返します。
+    /// 座標値には設定されているオフセットが加算されます。
+    fn iter_with_offset(&self) -> impl Iterator<Item=(Point3D<P>, Voxel<C, W>)> {
+        let offset = self.get_offset();
+        self.iter().map(move |(point, voxel)| {
+            (point - offset, voxel)
+        })
+    }
+
+    /// インスタンスを消費して、登録されているすべての座標とボクセルのタプルを順に返すイテレータを返します。
+    /// 座標値には設定されているオフセットが加算されます。
+    fn into_iter_with_offset(self) -> impl Iterator<Item=(Point3D<P>, Voxel<C, W>)> {
+        let offset = self.get_offset();
+        self.into_iter().map(move |(point, voxel)| {
+            (point - offset, voxel)
+        })
+    }
+
+    /// 登録されているすべてのボクセルの座標と、その色を順に返すイテレータを返します。
+    fn iter_points(&self) -> impl Iterator<Item=(Point3D<P>, Color<C>)>
+    where
+        C: AsPrimitive<W>,
+        W: AsPrimitive<C>,
+    {
+        self.iter().map(|(point, voxel)| {
+            let average_color = voxel.color / Color::from(voxel.weight).as_::<C>();
+            (point, average_color)
+        })
+    }
+
+    /// インスタンスを消費して、登録されているすべてのボクセルの座標と、その色を順に返すイテレータを返します。
+    fn into_iter_points(self) -> impl Iterator<Item=(Point3D<P>, Color<C>)>
+    where
+        C: AsPrimitive<W>,
+        W: AsPrimitive<C>,
+    {
+        self.into_iter().map(|(point, voxel)| {
+            let average_color = voxel.color / Color::from(voxel.weight).as_::<C>();
+            (point, average_color)
+        })
+    }
+
     /// 登録されているすべての座標とボクセルのタプルを返します。
     /// オフセットを適用した結果を得たい場合、`to_vec_with_offset`メソッドを使用してください。
     /// `Voxel`の場合、`Voxel.color / Voxel.weight`で平均色を計算できます。
     /// すでに平均が計算された色を取得したい場合は、`to_points`メソッドを使用してください。
-    fn to_vec(&self) -> Vec<(Point3D<P>, Voxel<C, W>)>;
+    fn to_vec(&self) -> Vec<(Point3D<P>, Voxel<C, W>)> {
+        self.iter().collect()
+    }
 
     /// 登録されているすべての座標とボクセルのタプルを返します。
     /// オフセットを適用した結果を得たい場合、`into_vec_with_offset`メソッドを使用してください。
     /// `Voxel`の場合、`Voxel.color / Voxel.weight`で平均色を計算できます。
     /// すでに平均が計算された色を取得したい場合は、`into_points`メソッドを使用してください。
-    fn into_vec(self) -> Vec<(Point3D<P>, Voxel<C, W>)>;
+    fn into_vec(self) -> Vec<(Point3D<P>, Voxel<C, W>)> {
+        self.into_iter().collect()
+    }
 
     /// 登録されているすべての座標とボクセルのタプルを返します。
     /// 座標値には設定されているオフセットが加算されます。
     /// すでに平均が計算された色を取得したい場合は、`to_points_with_offset`メソッドを使用してください。
     fn to_vec_with_offset(&self) -> Vec<(Point3D<P>, Voxel<C, W>)> {
-        let offset = self.get_offset();
-        self.to_vec().into_iter().map(|(point, voxel)| {
-            (point - offset, voxel)
-        }).collect()
+        self.iter_with_offset().collect()
     }
 
     /// 登録されているすべての座標とボクセルのタプルを返します。
     /// 座標値には設定されているオフセットが加算されます。
     /// すでに平均が計算された色を取得したい場合は、`into_points_with_offset`メソッドを使用してください。
     fn into_vec_with_offset(self) -> Vec<(Point3D<P>, Voxel<C, W>)> {
-        let offset = self.get_offset();
-        self.into_vec().into_iter().map(|(point, voxel)| {
-            (point - offset, voxel)
-        }).collect()
+        self.into_iter_with_offset().collect()
     }
 
     /// 登録されているすべてのボクセルの座標と、その色を返します。
@@ -235,10 +562,7 @@ where
         C: AsPrimitive<W>,
         W: AsPrimitive<C>,
     {
-        self.to_vec().into_iter().map(|(point, voxel)| {
-            let average_color = voxel.color / Color::from(voxel.weight).as_::<C>();
-            (point, average_color)
-        }).collect()
+        self.iter_points().collect()
     }
 
     /// 登録されているすべてのボクセルの座標と、その色を返します。
@@ -247,10 +571,7 @@ where
         C: AsPrimitive<W>,
         W: AsPrimitive<C>,
     {
-        self.into_vec().into_iter().map(|(point, voxel)| {
-            let average_color = voxel.color / Color::from(voxel.weight).as_::<C>();
-            (point, average_color)
-        }).collect()
+        self.into_iter_points().collect()
     }
 
     /// 登録されているすべてのボクセルの座標にオフセットを加算したものと、その色のタプルを返します。
@@ -259,7 +580,7 @@ where
         C: AsPrimitive<W>,
         W: AsPrimitive<C>,
     {
-        self.to_vec_with_offset().into_iter().map(|(point, voxel)| {
+        self.iter_with_offset().map(|(point, voxel)| {
             let average_color = voxel.color / Color::from(voxel.weight).as_::<C>();
             (point, average_color)
         }).collect()
@@ -271,7 +592,7 @@ where
         C: AsPrimitive<W>,
         W: AsPrimitive<C>,
     {
-        self.into_vec_with_offset().into_iter().map(|(point, voxel)| {
+        self.into_iter_with_offset().map(|(point, voxel)| {
             let average_color = voxel.color / Color::from(voxel.weight).as_::<C>();
             (point, average_color)
         }).collect()
@@ -286,10 +607,11 @@ where
     /// 以下の構造体は`insert`と`merge`の結果が同じであるため、より高速な`insert`メソッドを使用することを推奨します。
     ///
     /// + `PointCloud`
+    /// + `Vec3VoxelCollection`
     /// + `HMap3DVoxelCollection`
     /// + `HMap2DVoxelCollection`
     fn insert<T: VoxelCollection<P, W, C>>(&mut self, pc: T) {
-        pc.into_vec_with_offset().into_iter().for_each(|(point, voxel)| {
+        pc.into_iter_with_offset().for_each(|(point, voxel)| {
             self.insert_one(point, voxel);
         });
     }
@@ -304,7 +626,6 @@ where
     ///
     /// 以下の構造体において、境界外の値を挿入する場合はこのメソッドを使用する必要があります。
     ///
-    /// + `Vec3DVoxelCollection`
     /// + `Vec2DVoxelCollection`
     ///
     /// # Errors
@@ -330,91 +651,503 @@ where
         Ok(Self::new(voxels, new_bounds, Point3D::default(), resolution))
     }
 
-    /// 指定された座標値が登録されているかどうかを返します。
-    fn has(&self, point: &Point3D<P>) -> bool;
+    /// 2つの`VoxelCollection`の和集合を返します。
+    /// 両方に存在する座標のボクセルは`rule`に従ってまとめられます。
+    ///
+    /// 集合演算では、オフセットを適用した座標値で2つの`VoxelCollection`を比較します。
+    /// `PointCloud`のように同じ座標値が複数含まれる場合は、比較の前にそれらを1つのボクセルにまとめます。
+    /// 結果のオフセットは`merge`と同様に`Point3D::default()`になります。
+    ///
+    /// # Errors
+    ///
+    /// + 2つの`VoxelCollection`の分解能が異なる場合、エラーを返します。
+    fn union<T: VoxelCollection<P, W, C>>(&self, other: &T, rule: VoxelMergeRule) -> Result<Self, anyhow::Error>
+    where
+        C: AsPrimitive<W>,
+        W: AsPrimitive<C>,
+    {
+        if self.get_resolution() != other.get_resolution() {
+            return Err(anyhow!("Resolution is different"));
+        }
 
-    /// 登録されているすべてのボクセルに対して、指定された関数を適用します。
-    fn batch(&mut self, f: fn(&mut Voxel<C, W>));
-}
+        let (self_offset, other_offset) = (self.get_offset(), other.get_offset());
+        let (self_map, mut other_map) = (voxel_map(self), voxel_map(other));
 
-/// ボクセルや点群の集合を表現するための構造体です。
-/// 内部的に配列を使用しているため、インスタンスの生成を高速で行える一方で、隣接する座標値の検索にはすべての要素を走査する必要があります。
-/// ボクセル化されていない点群を扱う場合などに使用することを推奨します。
-#[derive(Clone)]
-pub struct PointCloud<P: Number, W: UInt, C: UInt> {
-    _phantom: PhantomData<W>,
-    pub field: Vec<(Point3D<P>, Voxel<C, W>)>,
-    pub bounds: Option<(Point3D<P>, Point3D<P>)>,
-    pub offset: Point3D<P>,
-    pub resolution: f64,
-}
+        let voxels = self_map.into_iter().map(|(point, voxel)| {
+            match other_map.remove(&(point - self_offset + other_offset)) {
+                Some(other_voxel) => (point - self_offset, Self::merge_voxel(rule, voxel, other_voxel)),
+                None => (point - self_offset, voxel),
+            }
+        }).collect::<Vec<_>>();
 
-impl<P: Number, W: UInt, C: UInt> Default for PointCloud<P, W, C> {
-    /// 分解能を`1.`として、空のインスタンスを生成します。
-    fn default() -> Self {
-        PointCloud {
-            _phantom: PhantomData,
-            field: Vec::default(),
-            bounds: None,
-            offset: Point3D::default(),
-            resolution: 1.,
-        }
-    }
-}
+        let voxels = voxels.into_iter()
+            .chain(other_map.into_iter().map(|(point, voxel)| (point - other_offset, voxel)))
+            .collect();
 
-impl<P: Number, W: UInt, C: UInt> PrivateVoxelCollectionMethod<P, W, C> for PointCloud<P, W, C> {
-    fn get_inner_bounds(&self) -> Option<(Point3D<P>, Point3D<P>)> {
-        self.bounds
+        Ok(Self::new(voxels, None, Point3D::default(), self.get_resolution()))
     }
 
-    fn set_inner_bounds(&mut self, bounds: (Point3D<P>, Point3D<P>)) {
-        self.bounds = Some(bounds)
+    /// 2つの`VoxelCollection`の積集合を返します。
+    /// ボクセルは`rule`に従ってまとめられます。
+    ///
+    /// # Errors
+    ///
+    /// + 2つの`VoxelCollection`の分解能が異なる場合、エラーを返します。
+    fn intersection<T: VoxelCollection<P, W, C>>(&self, other: &T, rule: VoxelMergeRule) -> Result<Self, anyhow::Error>
+    where
+        C: AsPrimitive<W>,
+        W: AsPrimitive<C>,
+    {
+        if self.get_resolution() != other.get_resolution() {
+            return Err(anyhow!("Resolution is different"));
+        }
+
+        let (self_offset, other_offset) = (self.get_offset(), other.get_offset());
+        let other_map = voxel_map(other);
+
+        let voxels = voxel_map(self).into_iter().filter_map(|(point, voxel)| {
+            let point = point - self_offset;
+            let other_voxel = other_map.get(&(point + other_offset))?;
+            Some((point, Self::merge_voxel(rule, voxel, *other_voxel)))
+        }).collect();
+
+        Ok(Self::new(voxels, None, Point3D::default(), self.get_resolution()))
     }
-}
 
-impl<P, W, C> VoxelCollection<P, W, C> for PointCloud<P, W, C>
-where
-    P: Number,
-    C: UInt + AsPrimitive<W>,
-    W: UInt + AsPrimitive<C>,
-{
-    fn new(voxels: Vec<(Point3D<P>, Voxel<C, W>)>, bounds: Option<(Point3D<P>, Point3D<P>)>, offset: Point3D<P>, resolution: f64) -> Self {
-        Self {
-            _phantom: PhantomData,
-            field: voxels,
-            bounds,
-            offset,
-            resolution,
+    /// `self`から`other`に存在する座標を取り除いた差集合を返します。
+    /// ボクセルの値は`self`のものがそのまま使用されます。
+    ///
+    /// # Errors
+    ///
+    /// + 2つの`VoxelCollection`の分解能が異なる場合、エラーを返します。
+    fn difference<T: VoxelCollection<P, W, C>>(&self, other: &T) -> Result<Self, anyhow::Error>
+    where
+        C: AsPrimitive<W>,
+        W: AsPrimitive<C>,
+    {
+        if self.get_resolution() != other.get_resolution() {
+            return Err(anyhow!("Resolution is different"));
         }
-    }
 
-    fn has_bounds(&self) -> bool {
-        self.bounds.is_some()
-    }
+        let (self_offset, other_offset) = (self.get_offset(), other.get_offset());
+        let other_map = voxel_map(other);
 
-    fn get_resolution(&self) -> f64 {
-        self.resolution
-    }
+        let voxels = voxel_map(self).into_iter()
+            .map(|(point, voxel)| (point - self_offset, voxel))
+            .filter(|(point, _)| !other_map.contains_key(&(*point + other_offset)))
+            .collect();
 
-    fn get_offset(&self) -> Point3D<P> {
-        self.offset
+        Ok(Self::new(voxels, None, Point3D::default(), self.get_resolution()))
     }
 
-    fn set_offset(&mut self, offset: Point3D<P>) {
-        self.offset = offset;
-    }
+    /// どちらか一方にのみ存在する座標からなる対称差を返します。
+    ///
+    /// # Errors
+    ///
+    /// + 2つの`VoxelCollection`の分解能が異なる場合、エラーを返します。
+    fn symmetric_difference<T: VoxelCollection<P, W, C>>(&self, other: &T) -> Result<Self, anyhow::Error>
+    where
+        C: AsPrimitive<W>,
+        W: AsPrimitive<C>,
+    {
+        if self.get_resolution() != other.get_resolution() {
+            return Err(anyhow!("Resolution is different"));
+        }
 
-    fn to_vec(&self) -> Vec<(Point3D<P>, Voxel<C, W>)> {
-        self.field.clone()
-    }
+        let (self_offset, other_offset) = (self.get_offset(), other.get_offset());
+        let (self_map, mut other_map) = (voxel_map(self), voxel_map(other));
 
-    fn into_vec(self) -> Vec<(Point3D<P>, Voxel<C, W>)> {
-        self.field
+        let voxels = self_map.into_iter()
+            .map(|(point, voxel)| (point - self_offset, voxel))
+            .filter(|(point, _)| other_map.remove(&(*point + other_offset)).is_none())
+            .collect::<Vec<_>>();
+
+        let voxels = voxels.into_iter()
+            .chain(other_map.into_iter().map(|(point, voxel)| (point - other_offset, voxel)))
+            .collect();
+
+        Ok(Self::new(voxels, None, Point3D::default(), self.get_resolution()))
+    }
+
+    /// 構造要素を`connectivity`の近傍とした膨張を行った結果を返します。
+    /// 新たに追加されるボクセルの色は、隣接する既存のボクセルの平均色の平均になり、重みは1になります。
+    /// 既存のボクセルの値は変更されません。
+    fn dilate(&self, connectivity: Connectivity) -> Self
+    where
+        P: Int,
+        C: AsPrimitive<W> + AsPrimitive<f64>,
+        W: AsPrimitive<C>,
+        f64: AsPrimitive<C>,
+    {
+        let occupied = voxel_map(self);
+
+        // 追加するボクセルごとの、隣接するボクセルの平均色の和と個数
+        let mut added = FxHashMap::<Point3D<P>, ([f64; 3], usize)>::default();
+
+        occupied.iter().for_each(|(point, voxel)| {
+            let weight = AsPrimitive::<f64>::as_(AsPrimitive::<C>::as_(voxel.weight));
+            let color = voxel.color.data.map(|a| AsPrimitive::<f64>::as_(a) / weight);
+
+            connectivity.neighbors(*point).filter(|neighbor| !occupied.contains_key(neighbor)).for_each(|neighbor| {
+                let (sum, count) = added.entry(neighbor).or_default();
+
+                (0..3).for_each(|i| sum[i] += color[i]);
+                *count += 1;
+            });
+        });
+
+        let voxels = occupied.into_iter().chain(added.into_iter().map(|(point, (sum, count))| {
+            let color = Color::new(sum.map(|a| (a / count as f64).round().as_()));
+            (point, Voxel::new(color))
+        })).collect();
+
+        Self::new(voxels, None, self.get_offset(), self.get_resolution())
+    }
+
+    /// 構造要素を`connectivity`の近傍とした収縮を行った結果を返します。
+    /// 近傍のすべてのボクセルが存在するボクセルのみが残り、その値は変更されません。
+    fn erode(&self, connectivity: Connectivity) -> Self
+    where
+        P: Int,
+        C: AsPrimitive<W>,
+        W: AsPrimitive<C>,
+    {
+        let occupied = voxel_map(self);
+
+        let voxels = occupied.iter().filter(|(point, _)| {
+            connectivity.neighbors(**point).filter(|neighbor| occupied.contains_key(neighbor)).count() == connectivity.size()
+        }).map(|(point, voxel)| (*point, *voxel)).collect();
+
+        Self::new(voxels, None, self.get_offset(), self.get_resolution())
+    }
+
+    /// 収縮の後に膨張を行うオープニングの結果を返します。
+    /// 孤立したボクセルや細い突起などのノイズを取り除けます。
+    /// 残ったボクセルの値は変更されません。
+    fn open(&self, connectivity: Connectivity) -> Self
+    where
+        P: Int,
+        C: AsPrimitive<W> + AsPrimitive<f64>,
+        W: AsPrimitive<C>,
+        f64: AsPrimitive<C>,
+    {
+        let opened = self.erode(connectivity).dilate(connectivity);
+
+        // 膨張で再び追加されたボクセルの色は補間されているため、元の値を使用する
+        let voxels = voxel_map(self).into_iter().filter(|(point, _)| opened.has(point)).collect();
+
+        Self::new(voxels, None, self.get_offset(), self.get_resolution())
+    }
+
+    /// 膨張の後に収縮を行うクロージングの結果を返します。
+    /// 壁や屋根に空いた小さな穴を埋められます。
+    /// 新たに追加されるボクセルの色は`dilate`と同様に補間されます。
+    fn close(&self, connectivity: Connectivity) -> Self
+    where
+        P: Int,
+        C: AsPrimitive<W> + AsPrimitive<f64>,
+        W: AsPrimitive<C>,
+        f64: AsPrimitive<C>,
+    {
+        self.dilate(connectivity).erode(connectivity)
+    }
+
+    /// 境界の外側から6近傍で到達できない空間を内部とみなして埋めた、中身の詰まったボクセルを返します。
+    /// 表面のみが取得される点群から生成した、中空の建物などを体積計算や3Dプリント用に変換できます。
+    /// 内部のボクセルの色は、z軸方向に走査したときに直前にあるボクセルの平均色になり、重みは1になります。
+    /// 既存のボクセルの値は変更されません。
+    ///
+    /// 内部の空間は、それを囲む26近傍の連結成分の外接直方体に含まれるため、連結成分ごとに探索します。
+    ///
+    /// # Errors
+    ///
+    /// + 1つの連結成分の外接直方体に含まれるボクセルの数が多すぎる場合、エラーを返します。
+    fn fill_solid(&self) -> Result<Self, anyhow::Error>
+    where
+        P: Int + AsPrimitive<usize>,
+        C: AsPrimitive<W>,
+        W: AsPrimitive<C>,
+        usize: AsPrimitive<P>,
+    {
+        let mut occupied = voxel_map(self);
+        let labels = self.label_components(Connectivity::TwentySix);
+
+        let interior = labels.components().iter()
+            .map(|component| enclosed_voxels(&occupied, component.bounds))
+            .collect::<Result<Vec<_>, anyhow::Error>>()?;
+
+        occupied.extend(interior.into_iter().flatten());
+
+        Ok(Self::new(occupied.into_iter().collect(), None, self.get_offset(), self.get_resolution()))
+    }
+
+    /// 中身の詰まったボクセルから、表面のボクセルのみを残した中空のボクセルを返します。
+    /// `connectivity`の近傍に空のボクセルが1つ以上あるボクセルを表面とみなし、その値は変更されません。
+    /// 6近傍では最も薄い表面になり、[`VoxelCollection::fill_solid`]で元の形状に戻せます。
+    fn hollow(&self, connectivity: Connectivity) -> Self
+    where
+        P: Int,
+        C: AsPrimitive<W>,
+        W: AsPrimitive<C>,
+    {
+        let occupied = voxel_map(self);
+
+        let voxels = occupied.iter().filter(|(point, _)| {
+            connectivity.neighbors(**point).filter(|neighbor| occupied.contains_key(neighbor)).count() < connectivity.size()
+        }).map(|(point, voxel)| (*point, *voxel)).collect();
+
+        Self::new(voxels, None, self.get_offset(), self.get_resolution())
+    }
+
+    /// ボクセルの大きさを`factor`倍した分解能で再サンプリングした結果を返します。
+    /// `factor`が1より大きい場合は、元のボクセルの中心を含むボクセルに色と重みを加算して集約するため、色は重みによる加重平均になります。
+    /// 集約した色の和や重みの和が色や重みの型で表現できない場合は、平均色を保ったまま重みを飽和させます。
+    /// `factor`が1より小さい場合は、中心が元のボクセルに含まれるボクセルに分割し、元のボクセルの値を複製します。
+    /// オフセットは`factor`で割った値に更新されます。
+    ///
+    /// # Errors
+    ///
+    /// + `factor`が正の有限な値でない場合、エラーを返します。
+    fn resample(&self, factor: f64) -> Result<Self, anyhow::Error>
+    where
+        P: Int + AsPrimitive<f64>,
+        C: AsPrimitive<W> + AsPrimitive<f64>,
+        W: AsPrimitive<C> + AsPrimitive<f64>,
+        i64: AsPrimitive<P>,
+        f64: AsPrimitive<C> + AsPrimitive<W>,
+    {
+        if !(factor.is_finite() && factor > 0.) {
+            return Err(anyhow!("Factor must be a positive finite number"));
+        }
+
+        let offset = Point3D::new(self.get_offset().data.map(|a| {
+            ((AsPrimitive::<f64>::as_(a) / factor).floor() as i64).as_()
+        }));
+
+        // 新しいボクセルごとの色の和と重みの和
+        // 多数のボクセルを集約すると色や重みの型の最大値を超えるため、`f64`で集計する
+        let mut sums = FxHashMap::<Point3D<P>, ([f64; 3], f64)>::default();
+
+        self.iter_with_offset().for_each(|(point, voxel)| {
+            let color = voxel.color.data.map(AsPrimitive::<f64>::as_);
+            let weight = AsPrimitive::<f64>::as_(voxel.weight);
+
+            // 新しいボクセルの座標値の範囲
+            let ranges = point.data.map(|a| {
+                let a = AsPrimitive::<f64>::as_(a);
+
+                if factor >= 1. {
+                    let i = ((a + 0.5) / factor).floor() as i64;
+                    i..i + 1
+                } else {
+                    (a / factor - 0.5).ceil() as i64..((a + 1.) / factor - 0.5).ceil() as i64
+                }
+            });
+
+            for x in ranges[0].clone() {
+                for y in ranges[1].clone() {
+                    for z in ranges[2].clone() {
+                        let point = Point3D::new([x, y, z].map(|a| a.as_())) + offset;
+
+                        let (sum, sum_weight) = sums.entry(point).or_default();
+
+                        (0..3).for_each(|i| sum[i] += color[i]);
+                        *sum_weight += weight;
+                    }
+                }
+            }
+        });
+
+        let voxels = sums.into_iter().map(|(point, (color, weight))| (point, voxel_from_sum(color, weight))).collect();
+
+        Ok(Self::new(voxels, None, offset, self.get_resolution() * factor))
+    }
+
+    /// 指定した分解能で再サンプリングした結果を返します。
+    /// 詳細は[`VoxelCollection::resample`]を参照してください。
+    ///
+    /// # Errors
+    ///
+    /// + `resolution`が正の有限な値でない場合、エラーを返します。
+    fn resample_to(&self, resolution: f64) -> Result<Self, anyhow::Error>
+    where
+        P: Int + AsPrimitive<f64>,
+        C: AsPrimitive<W> + AsPrimitive<f64>,
+        W: AsPrimitive<C> + AsPrimitive<f64>,
+        i64: AsPrimitive<P>,
+        f64: AsPrimitive<C> + AsPrimitive<W>,
+    {
+        self.resample(resolution / self.get_resolution())
+    }
+
+    /// `connectivity`で隣接するボクセルを同じ連結成分とみなして、ラベリングを行います。
+    fn label_components(&self, connectivity: Connectivity) -> ComponentLabels<P>
+    where
+        P: Int,
+        C: AsPrimitive<W>,
+        W: AsPrimitive<C>,
+    {
+        let occupied = voxel_map(self);
+
+        let mut labels = FxHashMap::<Point3D<P>, usize>::default();
+        let mut components = Vec::new();
+
+        occupied.keys().for_each(|&start| {
+            if labels.contains_key(&start) {
+                return;
+            }
+
+            let label = components.len();
+            let mut component = VoxelComponent { count: 0, bounds: (start, start) };
+
+            labels.insert(start, label);
+            let mut stack = vec![start];
+
+            while let Some(point) = stack.pop() {
+                component.count += 1;
+                component.bounds = Self::calc_bounds_from_2(component.bounds, (point, point));
+
+                connectivity.neighbors(point).for_each(|neighbor| {
+                    if occupied.contains_key(&neighbor) && !labels.contains_key(&neighbor) {
+                        labels.insert(neighbor, label);
+                        stack.push(neighbor);
+                    }
+                });
+            }
+
+            components.push(component);
+        });
+
+        ComponentLabels { labels, components }
+    }
+
+    /// `connectivity`で求めた連結成分のうち、ボクセルの数が`min_count`未満のものを削除します。
+    /// 鳥や電線などに由来する、孤立した小さなボクセルの塊を取り除けます。
+    fn remove_small_components(&mut self, connectivity: Connectivity, min_count: usize)
+    where
+        P: Int,
+        C: AsPrimitive<W>,
+        W: AsPrimitive<C>,
+    {
+        let labels = self.label_components(connectivity);
+
+        self.retain(|point, _| {
+            labels.label(point).is_some_and(|label| labels.components[label].count >= min_count)
+        });
+    }
+
+    /// 指定された座標値が登録されているかどうかを返します。
+    fn has(&self, point: &Point3D<P>) -> bool;
+
+    /// 指定された座標値のボクセルを返します。
+    /// 登録されていない場合は`None`を返します。
+    fn get(&self, point: &Point3D<P>) -> Option<Voxel<C, W>>;
+
+    /// 指定された座標値のボクセルを削除し、削除したボクセルを返します。
+    /// 登録されていない場合は`None`を返します。
+    /// 境界は必要になった段階で再計算されます。
+    fn remove(&mut self, point: &Point3D<P>) -> Option<Voxel<C, W>>;
+
+    /// 指定された座標値のボクセルを置き換え、置き換える前のボクセルを返します。
+    /// `insert_one`と異なり、色と重みは加算されません。
+    /// 登録されていない場合は新たに挿入し、`None`を返します。
+    fn replace(&mut self, point: Point3D<P>, voxel: Voxel<C, W>) -> Option<Voxel<C, W>> {
+        let previous = self.remove(&point);
+        self.insert_one(point, voxel);
+
+        previous
+    }
+
+    /// 指定された関数が`false`を返すボクセルをすべて削除します。
+    fn retain<F: FnMut(&Point3D<P>, &Voxel<C, W>) -> bool>(&mut self, f: F);
+
+    /// 登録されているすべてのボクセルに対して、指定された関数を適用します。
+    fn batch(&mut self, f: fn(&mut Voxel<C, W>));
+}
+
+/// ボクセルや点群の集合を表現するための構造体です。
+/// 内部的に配列を使用しているため、インスタンスの生成を高速で行える一方で、隣接する座標値の検索にはすべての要素を走査する必要があります。
+/// ボクセル化されていない点群を扱う場合などに使用することを推奨します。
+#[derive(Clone)]
+pub struct PointCloud<P: Number, W: UInt, C: UInt> {
+    _phantom: PhantomData<W>,
+    pub field: Vec<(Point3D<P>, Voxel<C, W>)>,
+    pub bounds: Option<(Point3D<P>, Point3D<P>)>,
+    pub offset: Point3D<P>,
+    pub resolution: f64,
+}
+
+impl<P: Number, W: UInt, C: UInt> Default for PointCloud<P, W, C> {
+    /// 分解能を`1.`として、空のインスタンスを生成します。
+    fn default() -> Self {
+        PointCloud {
+            _phantom: PhantomData,
+            field: Vec::default(),
+            bounds: None,
+            offset: Point3D::default(),
+            resolution: 1.,
+        }
+    }
+}
+
+impl<P: Number, W: UInt, C: UInt> PrivateVoxelCollectionMethod<P, W, C> for PointCloud<P, W, C> {
+    fn get_inner_bounds(&self) -> Option<(Point3D<P>, Point3D<P>)> {
+        self.bounds
+    }
+
+    fn set_inner_bounds(&mut self, bounds: (Point3D<P>, Point3D<P>)) {
+        self.bounds = Some(bounds)
+    }
+}
+
+impl<P, W, C> VoxelCollection<P, W, C> for PointCloud<P, W, C>
+where
+    P: Number,
+    C: UInt + AsPrimitive<W>,
+    W: UInt + AsPrimitive<C>,
+{
+    fn new(voxels: Vec<(Point3D<P>, Voxel<C, W>)>, bounds: Option<(Point3D<P>, Point3D<P>)>, offset: Point3D<P>, resolution: f64) -> Self {
+        Self {
+            _phantom: PhantomData,
+            field: voxels,
+            bounds,
+            offset,
+            resolution,
+        }
+    }
+
+    fn has_bounds(&self) -> bool {
+        self.bounds.is_some()
+    }
+
+    fn get_resolution(&self) -> f64 {
+        self.resolution
+    }
+
+    fn get_offset(&self) -> Point3D<P> {
+        self.offset
+    }
+
+    fn set_offset(&mut self, offset: Point3D<P>) {
+        self.offset = offset;
+    }
+
+    fn iter(&self) -> impl Iterator<Item=(Point3D<P>, Voxel<C, W>)> {
+        self.field.iter().copied()
+    }
+
+    fn into_iter(self) -> impl Iterator<Item=(Point3D<P>, Voxel<C, W>)> {
+        self.field.into_iter()
+    }
+
+    #[cfg(feature = "rayon")]
+    fn par_iter(&self) -> impl ParallelIterator<Item=(Point3D<P>, Voxel<C, W>)> {
+        self.field.par_iter().copied()
     }
 
     // 挿入し、colorとweightを加算する
     fn insert<T: VoxelCollection<P, W, C>>(&mut self, pc: T) {
-        self.field.extend(pc.into_vec_with_offset());
+        self.field.extend(pc.into_iter_with_offset());
         self.bounds = None;
     }
 
@@ -427,6 +1160,29 @@ where
         self.field.iter().any(|(p, _)| p == point)
     }
 
+    // 同じ座標値の点が複数ある場合は、それらを合計したボクセルを返す
+    fn get(&self, point: &Point3D<P>) -> Option<Voxel<C, W>> {
+        self.field.iter().filter(|(p, _)| p == point).fold(None, |sum, (_, voxel)| {
+            let mut sum = sum.unwrap_or_default();
+            Self::add_color_with_weight_check(&mut sum, *voxel);
+            Some(sum)
+        })
+    }
+
+    fn remove(&mut self, point: &Point3D<P>) -> Option<Voxel<C, W>> {
+        let voxel = self.get(point)?;
+
+        self.field.retain(|(p, _)| p != point);
+        self.bounds = None;
+
+        Some(voxel)
+    }
+
+    fn retain<F: FnMut(&Point3D<P>, &Voxel<C, W>) -> bool>(&mut self, mut f: F) {
+        self.field.retain(|(point, voxel)| f(point, voxel));
+        self.bounds = None;
+    }
+
     fn batch(&mut self, f: fn(&mut Voxel<C, W>)) {
         self.field.iter_mut().for_each(|(_, voxel)| {
             f(voxel);
@@ -437,7 +1193,8 @@ where
 
 /// 内部的に3次元配列を使用してボクセルの集合を表現するための構造体です。
 /// 隣接する座標値の検索が高速で行える一方で、境界に合わせて多次元配列を構築するため多くのメモリが必要になります。
-/// また、境界外の値を挿入するにはメモリの再確保を行う必要があります。
+/// 境界外の値を挿入した場合は配列を拡張します。
+/// 拡張が必要になった軸は現在の2倍以上の大きさを確保するため、連続した挿入でも再確保の回数は対数的にしか増えません。
 #[derive(Clone)]
 pub struct Vec3VoxelCollection<P, W, C>
 where
@@ -445,12 +1202,101 @@ where
     W: UInt,
     C: UInt,
 {
+    /// `field[x][y][z]`が`origin + (x, y, z)`のボクセルを表します。
+    /// 拡張のために確保された領域を含むため、境界よりも大きいことがあります。
     pub field: Vec<Vec<Vec<Voxel<C, W>>>>,
-    bounds: (Point3D<P>, Point3D<P>),
+    origin: Point3D<P>,
+    bounds: Option<(Point3D<P>, Point3D<P>)>,
     offset: Point3D<P>,
     resolution: f64,
 }
 
+impl<P, W, C> Vec3VoxelCollection<P, W, C>
+where
+    P: Int + AsPrimitive<usize>,
+    W: UInt,
+    C: UInt,
+    usize: AsPrimitive<P>,
+{
+    // 配列の各軸の大きさ
+    fn field_size(&self) -> [usize; 3] {
+        let x = self.field.len();
+        let y = self.field.first().map_or(0, |y_vec| y_vec.len());
+        let z = self.field.first().and_then(|y_vec| y_vec.first()).map_or(0, |z_vec| z_vec.len());
+
+        [x, y, z]
+    }
+
+    // 座標値に対応する配列のインデックスを返す
+    fn field_index(&self, point: &Point3D<P>) -> Option<[usize; 3]> {
+        let size = self.field_size();
+
+        let mut index = [0; 3];
+        for axis in 0..3 {
+            if point[axis] < self.origin[axis] {
+                return None;
+            }
+
+            index[axis] = (point[axis] - self.origin[axis]).as_();
+
+            if index[axis] >= size[axis] {
+                return None;
+            }
+        }
+
+        Some(index)
+    }
+
+    // 座標値を含むように配列を拡張する
+    fn grow_to_include(&mut self, point: &Point3D<P>) {
+        let size = self.field_size();
+
+        let (origin, new_size) = if size.contains(&0) {
+            (*point, [1; 3])
+        } else {
+            let mut origin = self.origin;
+            let mut new_size = size;
+
+            for axis in 0..3 {
+                let start = self.origin[axis];
+                let end = start + (size[axis] - 1).as_();
+
+                if point[axis] < start {
+                    let required: usize = (end - point[axis]).as_() + 1;
+                    let extra: usize = required.max(size[axis] * 2) - required;
+
+                    origin[axis] = point[axis].checked_sub(&extra.as_()).unwrap_or(P::min_value());
+                    new_size[axis] = (end - origin[axis]).as_() + 1;
+                } else if point[axis] > end {
+                    let required: usize = (point[axis] - start).as_() + 1;
+                    let extra: usize = required.max(size[axis] * 2) - required;
+
+                    let new_end = point[axis].checked_add(&extra.as_()).unwrap_or(P::max_value());
+                    new_size[axis] = (new_end - start).as_() + 1;
+                }
+            }
+
+            (origin, new_size)
+        };
+
+        let mut field = vec![vec![vec![Voxel::<C, W>::default(); new_size[2]]; new_size[1]]; new_size[0]];
+
+        if !size.contains(&0) {
+            let shift: [usize; 3] = (self.origin - origin).as_().data;
+
+            mem::take(&mut self.field).into_iter().enumerate().for_each(|(x, y_vec)| {
+                y_vec.into_iter().enumerate().for_each(|(y, z_vec)| {
+                    let dst = &mut field[x + shift[0]][y + shift[1]][shift[2]..shift[2] + z_vec.len()];
+                    dst.copy_from_slice(&z_vec);
+                });
+            });
+        }
+
+        self.field = field;
+        self.origin = origin;
+    }
+}
+
 
 impl<P, W, C> Default for Vec3VoxelCollection<P, W, C>
 where
@@ -461,7 +1307,8 @@ where
     fn default() -> Self {
         Vec3VoxelCollection {
             field: Vec::default(),
-            bounds: (Point3D::default(), Point3D::default()),
+            origin: Point3D::default(),
+            bounds: None,
             offset: Point3D::<P>::default(),
             resolution: 1.,
         }
@@ -470,11 +1317,11 @@ where
 
 impl<P: Int, W: UInt, C: UInt> PrivateVoxelCollectionMethod<P, W, C> for Vec3VoxelCollection<P, W, C> {
     fn get_inner_bounds(&self) -> Option<(Point3D<P>, Point3D<P>)> {
-        Some(self.bounds)
+        self.bounds
     }
 
-    fn set_inner_bounds(&mut self, _bounds: (Point3D<P>, Point3D<P>)) {
-        panic!("Vec3VoxelCollection can't set bounds manually")
+    fn set_inner_bounds(&mut self, bounds: (Point3D<P>, Point3D<P>)) {
+        self.bounds = Some(bounds);
     }
 }
 
@@ -485,36 +1332,713 @@ where
     W: UInt + AsPrimitive<C>,
     usize: AsPrimitive<P>,
 {
-    fn new(points: Vec<(Point3D<P>, Voxel<C, W>)>, bounds: Option<(Point3D<P>, Point3D<P>)>, offset: Point3D<P>, resolution: f64) -> Self {
-        let (min, max) = bounds.unwrap_or_else(|| {
-            Self::calc_bounds(&points)
-        });
-
-
-        let field_size = max - min + P::one();
-
-        let mut field = vec![vec![vec![Voxel::<C, W>::default(); field_size[2].as_()]; field_size[1].as_()]; field_size[0].as_()];
-
-
-        points.into_iter().for_each(|(point, voxel)| {
-            let point = point - min;
-
-            let x: usize = point[0].as_();
-            let y: usize = point[1].as_();
-            let z: usize = point[2].as_();
+    fn new(points: Vec<(Point3D<P>, Voxel<C, W>)>, bounds: Option<(Point3D<P>, Point3D<P>)>, offset: Point3D<P>, resolution: f64) -> Self {
+        // 境界が決まらない場合は、最初の挿入時に配列を確保する
+        if points.is_empty() && bounds.is_none() {
+            return Self {
+                offset,
+                resolution,
+                ..Self::default()
+            };
+        }
+
+        let (min, max) = bounds.unwrap_or_else(|| {
+            Self::calc_bounds(&points)
+        });
+
+
+        let field_size = max - min + P::one();
+
+        let mut field = vec![vec![vec![Voxel::<C, W>::default(); field_size[2].as_()]; field_size[1].as_()]; field_size[0].as_()];
+
+
+        points.into_iter().for_each(|(point, voxel)| {
+            let point = point - min;
+
+            let x: usize = point[0].as_();
+            let y: usize = point[1].as_();
+            let z: usize = point[2].as_();
+
+            field[x][y][z] = voxel;
+        });
+
+        Self {
+            field,
+            origin: min,
+            bounds: Some((min, max)),
+            offset,
+            resolution,
+        }
+    }
+    fn has_bounds(&self) -> bool {
+        self.bounds.is_some()
+    }
+
+    fn get_resolution(&self) -> f64 {
+        self.resolution
+    }
+
+    fn get_offset(&self) -> Point3D<P> {
+        self.offset
+    }
+
+    fn set_offset(&mut self, offset: Point3D<P>) {
+        self.offset = offset;
+    }
+
+    fn iter(&self) -> impl Iterator<Item=(Point3D<P>, Voxel<C, W>)> {
+        let origin = self.origin;
+
+        self.field.iter().enumerate().flat_map(move |(x, y_vec)| {
+            y_vec.iter().enumerate().flat_map(move |(y, z_vec)| {
+                z_vec.iter().enumerate().map(move |(z, voxel)| {
+                    let point = Point3D::new([x, y, z]).as_() + origin;
+                    (point, *voxel)
+                })
+            })
+        }).filter(|(_, voxel)| voxel.weight.ne(&W::zero()))
+    }
+
+    fn into_iter(self) -> impl Iterator<Item=(Point3D<P>, Voxel<C, W>)> {
+        let origin = self.origin;
+
+        self.field.into_iter().enumerate().flat_map(move |(x, y_vec)| {
+            y_vec.into_iter().enumerate().flat_map(move |(y, z_vec)| {
+                z_vec.into_iter().enumerate().map(move |(z, voxel)| {
+                    let point = Point3D::new([x, y, z]).as_() + origin;
+                    (point, voxel)
+                })
+            })
+        }).filter(|(_, voxel)| voxel.weight.ne(&W::zero()))
+    }
+
+    #[cfg(feature = "rayon")]
+    fn par_iter(&self) -> impl ParallelIterator<Item=(Point3D<P>, Voxel<C, W>)> {
+        let origin = self.origin;
+
+        self.field.par_iter().enumerate().flat_map_iter(move |(x, y_vec)| {
+            y_vec.iter().enumerate().flat_map(move |(y, z_vec)| {
+                z_vec.iter().enumerate().map(move |(z, voxel)| {
+                    let point = Point3D::new([x, y, z]).as_() + origin;
+                    (point, *voxel)
+                })
+            })
+        }).filter(|(_, voxel)| voxel.weight.ne(&W::zero()))
+    }
+
+
+    /// 境界外の座標値が与えられた場合、配列を拡張してから挿入します。
+    fn insert_one(&mut self, point: Point3D<P>, voxel: Voxel<C, W>) {
+        let is_empty = self.field_size().contains(&0);
+
+        let [x, y, z] = match self.field_index(&point) {
+            Some(index) => index,
+            None => {
+                self.grow_to_include(&point);
+                self.field_index(&point).unwrap()
+            }
+        };
+
+        Self::add_color_with_weight_check(&mut self.field[x][y][z], voxel);
+
+        // 境界が未計算の場合は、必要になった段階で計算する
+        self.bounds = if is_empty {
+            Some((point, point))
+        } else {
+            self.bounds.map(|bounds| Self::calc_bounds_from_2(bounds, (point, point)))
+        };
+    }
+
+    fn has(&self, point: &Point3D<P>) -> bool {
+        match self.field_index(point) {
+            Some([x, y, z]) => self.field[x][y][z].weight.ne(&W::zero()),
+            None => false,
+        }
+    }
+
+    fn get(&self, point: &Point3D<P>) -> Option<Voxel<C, W>> {
+        let [x, y, z] = self.field_index(point)?;
+        let voxel = self.field[x][y][z];
+
+        voxel.weight.ne(&W::zero()).then_some(voxel)
+    }
+
+    fn remove(&mut self, point: &Point3D<P>) -> Option<Voxel<C, W>> {
+        let [x, y, z] = self.field_index(point)?;
+        let voxel = mem::take(&mut self.field[x][y][z]);
+
+        if voxel.weight.eq(&W::zero()) {
+            return None;
+        }
+
+        self.bounds = None;
+
+        Some(voxel)
+    }
+
+    fn retain<F: FnMut(&Point3D<P>, &Voxel<C, W>) -> bool>(&mut self, mut f: F) {
+        let origin = self.origin;
+
+        self.field.iter_mut().enumerate().for_each(|(x, y_vec)| {
+            y_vec.iter_mut().enumerate().for_each(|(y, z_vec)| {
+                z_vec.iter_mut().enumerate().for_each(|(z, voxel)| {
+                    let point = Point3D::new([x, y, z]).as_() + origin;
+
+                    if voxel.weight.ne(&W::zero()) && !f(&point, voxel) {
+                        *voxel = Voxel::default();
+                    }
+                });
+            });
+        });
+
+        self.bounds = None;
+    }
+
+    fn batch(&mut self, f: fn(&mut Voxel<C, W>)) {
+        self.field.iter_mut().for_each(|y_vec| {
+            y_vec.iter_mut().for_each(|z_vec| {
+                z_vec.iter_mut().for_each(|voxel| {
+                    f(voxel);
+                });
+            });
+        });
+    }
+}
+
+/// 内部的に2次元配列を使用してボクセルの集合を表現するための構造体です。
+/// 平面座標ごとに1つの高さしか持てませんが、`Vec3VoxelCollection`よりもメモリ効率が良いです。
+/// 隣接する座標値の検索を高速に行えます。
+/// また、境界外の値を挿入するにはメモリの再確保を行う必要があります。
+#[derive(Clone)]
+pub struct Vec2VoxelCollection<P, W, C>
+where
+    P: Int,
+    W: UInt,
+    C: UInt,
+{
+    pub field: Vec<Vec<(P, Voxel<C, W>)>>,
+    bounds_xy: (Point2D<P>, Point2D<P>),
+    bounds_z: Option<(P, P)>,
+    offset: Point3D<P>,
+    resolution: f64,
+}
+
+impl<P, W, C> Vec2VoxelCollection<P, W, C>
+where
+    P: Int,
+    W: UInt,
+    C: UInt,
+{
+    pub fn get_bounds_xy(&self) -> (Point2D<P>, Point2D<P>) {
+        self.bounds_xy
+    }
+
+    // 座標値に対応する配列のインデックスを返す
+    fn field_index(&self, point: &Point3D<P>) -> Option<(usize, usize)>
+    where
+        P: AsPrimitive<usize>,
+    {
+        let (min_xy, _max_xy) = self.bounds_xy;
+
+        if point[0] < min_xy[0] || point[1] < min_xy[1] {
+            return None;
+        }
+
+        let x: usize = (point[0] - min_xy[0]).as_();
+        let y: usize = (point[1] - min_xy[1]).as_();
+
+        self.field.get(x).and_then(|y_vec| y_vec.get(y)).map(|_| (x, y))
+    }
+}
+
+impl<P, W, C> Default for Vec2VoxelCollection<P, W, C>
+where
+    P: Int,
+    W: UInt,
+    C: UInt,
+{
+    fn default() -> Self {
+        Vec2VoxelCollection {
+            field: Vec::default(),
+            bounds_xy: (Point2D::default(), Point2D::default()),
+            bounds_z: Some((P::default(), P::default())),
+            offset: Point3D::<P>::default(),
+            resolution: 1.,
+        }
+    }
+}
+
+impl<P, W, C> PrivateVoxelCollectionMethod<P, W, C> for Vec2VoxelCollection<P, W, C>
+where
+    P: Int,
+    W: UInt,
+    C: UInt,
+{
+    fn get_inner_bounds(&self) -> Option<(Point3D<P>, Point3D<P>)> {
+        if let Some((min_z, max_z)) = self.bounds_z {
+            let (min_xy, max_xy) = self.bounds_xy;
+
+            Some((Point3D::new([min_xy[0], min_xy[1], min_z]), Point3D::new([max_xy[0], max_xy[1], max_z])))
+        } else {
+            None
+        }
+    }
+    fn set_inner_bounds(&mut self, _bounds: (Point3D<P>, Point3D<P>)) {
+        panic!("Vec2VoxelCollection can't set bounds manually")
+    }
+}
+
+impl<P, W, C> VoxelCollection<P, W, C> for Vec2VoxelCollection<P, W, C>
+where
+    P: Int + AsPrimitive<usize>,
+    C: UInt + AsPrimitive<W>,
+    W: UInt + AsPrimitive<C>,
+    usize: AsPrimitive<P>,
+{
+    fn new(points: Vec<(Point3D<P>, Voxel<C, W>)>, bounds: Option<(Point3D<P>, Point3D<P>)>, offset: Point3D<P>, resolution: f64) -> Self {
+        let (min, max) = bounds.unwrap_or_else(|| {
+            Self::calc_bounds(&points)
+        });
+
+        let field_size = max - min + P::one();
+
+        let mut field = vec![vec![(P::default(), Voxel::<C, W>::default()); field_size[1].as_()]; field_size[0].as_()];
+
+        points.into_iter().for_each(|(point, voxel)| {
+            let x: usize = (point[0] - min[0]).as_();
+            let y: usize = (point[1] - min[1]).as_();
+            let z = point[2];
+
+            field[x][y] = (z, voxel);
+        });
+
+        Self {
+            field,
+            bounds_xy: (min.fit(), max.fit()),
+            bounds_z: Some((min[2], max[2])),
+            offset,
+            resolution,
+        }
+    }
+
+    fn has_bounds(&self) -> bool {
+        true
+    }
+
+    fn get_bounds(&mut self) -> (Point3D<P>, Point3D<P>) {
+        let bounds_z = self.bounds_z.unwrap_or_else(|| {
+            let z = self.field.iter().flat_map(|y_vec| {
+                y_vec.iter().filter_map(|(z, voxel)| {
+                    if voxel.weight.ne(&W::zero()) {
+                        Some(*z)
+                    } else {
+                        None
+                    }
+                })
+            }).collect::<Vec<_>>();
+
+            let min = z.iter().min().unwrap();
+            let max = z.iter().max().unwrap();
+
+            (*min, *max)
+        });
+
+        let min_xy = self.bounds_xy.0;
+        let max_xy = self.bounds_xy.1;
+
+        let min = Point3D::new([min_xy[0], min_xy[1], bounds_z.0]);
+        let max = Point3D::new([max_xy[0], max_xy[1], bounds_z.1]);
+
+        (min, max)
+    }
+
+    fn get_resolution(&self) -> f64 {
+        self.resolution
+    }
+
+    fn get_offset(&self) -> Point3D<P> {
+        self.offset
+    }
+
+    fn set_offset(&mut self, offset: Point3D<P>) {
+        self.offset = offset;
+    }
+
+    fn iter(&self) -> impl Iterator<Item=(Point3D<P>, Voxel<C, W>)> {
+        let min_xy = self.bounds_xy.0;
+
+        self.field.iter().enumerate().flat_map(move |(x, y_vec)| {
+            y_vec.iter().enumerate().map(move |(y, (z, voxel))| {
+                let x = x.as_() + min_xy[0];
+                let y = y.as_() + min_xy[1];
+
+                let point = Point3D::new([x, y, *z]);
+                (point, *voxel)
+            })
+        }).filter(|(_, voxel)| voxel.weight.ne(&W::zero()))
+    }
+
+    fn into_iter(self) -> impl Iterator<Item=(Point3D<P>, Voxel<C, W>)> {
+        let min_xy = self.bounds_xy.0;
+
+        self.field.into_iter().enumerate().flat_map(move |(x, y_vec)| {
+            y_vec.into_iter().enumerate().map(move |(y, (z, voxel))| {
+                let x = x.as_() + min_xy[0];
+                let y = y.as_() + min_xy[1];
+
+                let point = Point3D::new([x, y, z]);
+                (point, voxel)
+            })
+        }).filter(|(_, voxel)| voxel.weight.ne(&W::zero()))
+    }
+
+    #[cfg(feature = "rayon")]
+    fn par_iter(&self) -> impl ParallelIterator<Item=(Point3D<P>, Voxel<C, W>)> {
+        let min_xy = self.bounds_xy.0;
+
+        self.field.par_iter().enumerate().flat_map_iter(move |(x, y_vec)| {
+            y_vec.iter().enumerate().map(move |(y, (z, voxel))| {
+                let x = x.as_() + min_xy[0];
+                let y = y.as_() + min_xy[1];
+
+                let point = Point3D::new([x, y, *z]);
+                (point, *voxel)
+            })
+        }).filter(|(_, voxel)| voxel.weight.ne(&W::zero()))
+    }
+
+    fn insert_one(&mut self, point: Point3D<P>, voxel: Voxel<C, W>) {
+        let x: usize = (point[0] - self.bounds_xy.0[0]).as_();
+        let y: usize = (point[1] - self.bounds_xy.0[1]).as_();
+        let z = point[2];
+        if let Some(y_vec) = self.field.get_mut(x) {
+            if let Some((height, current_voxel)) = y_vec.get_mut(y) {
+                if *height != z {
+                    *height = z;
+                    *current_voxel = voxel;
+
+                    return;
+                }
+
+                Self::add_color_with_weight_check(current_voxel, voxel);
+            }
+        }
+
+        self.bounds_z = None;
+    }
+
+    fn has(&self, point: &Point3D<P>) -> bool {
+        self.get(point).is_some()
+    }
+
+    fn get(&self, point: &Point3D<P>) -> Option<Voxel<C, W>> {
+        let (x, y) = self.field_index(point)?;
+        let (height, voxel) = self.field[x][y];
+
+        (height == point[2] && voxel.weight.ne(&W::zero())).then_some(voxel)
+    }
+
+    fn remove(&mut self, point: &Point3D<P>) -> Option<Voxel<C, W>> {
+        let voxel = self.get(point)?;
+        let (x, y) = self.field_index(point)?;
+
+        self.field[x][y] = (P::default(), Voxel::default());
+        self.bounds_z = None;
+
+        Some(voxel)
+    }
+
+    fn retain<F: FnMut(&Point3D<P>, &Voxel<C, W>) -> bool>(&mut self, mut f: F) {
+        let min_xy = self.bounds_xy.0;
+
+        self.field.iter_mut().enumerate().for_each(|(x, y_vec)| {
+            y_vec.iter_mut().enumerate().for_each(|(y, (z, voxel))| {
+                let point = Point3D::new([x.as_() + min_xy[0], y.as_() + min_xy[1], *z]);
+
+                if voxel.weight.ne(&W::zero()) && !f(&point, voxel) {
+                    *z = P::default();
+                    *voxel = Voxel::default();
+                }
+            });
+        });
+
+        self.bounds_z = None;
+    }
+
+    fn batch(&mut self, f: fn(&mut Voxel<C, W>)) {
+        self.field.iter_mut().for_each(|y_vec| {
+            y_vec.iter_mut().for_each(|(_z, voxel)| {
+                f(voxel);
+            });
+        });
+    }
+}
+
+/// 内部的にハッシュマップを使用して座標を管理しています。
+/// 1点挿入するごとにハッシュ値を計算するため、`Vec3VoxelCollection`よりも低速であることが予想されますが、境界外の値を挿入する際にメモリの再確保が不要です。
+/// 隣接点の検索は1回のハッシュ値計算で行えるため、`PointCloud`よりも高速です。
+#[derive(Clone)]
+pub struct HMap3DVoxelCollection<P, W, C, BH>
+where
+    P: Int,
+    W: UInt,
+    C: UInt,
+    BH: BuildHasher,
+{
+    pub field: DashMap<Point3D<P>, Voxel<C, W>, BH>,
+    bounds: Option<(Point3D<P>, Point3D<P>)>,
+    offset: Point3D<P>,
+    resolution: f64,
+}
+
+impl<P, W, C, BH> Default for HMap3DVoxelCollection<P, W, C, BH>
+where
+    P: Int,
+    W: UInt,
+    C: UInt,
+    BH: BuildHasher + Clone + Default,
+{
+    fn default() -> Self {
+        HMap3DVoxelCollection {
+            field: DashMap::with_hasher(BH::default()),
+            bounds: None,
+            offset: Point3D::<P>::default(),
+            resolution: 1.,
+        }
+    }
+}
+
+impl<P, W, C, BH> PrivateVoxelCollectionMethod<P, W, C> for HMap3DVoxelCollection<P, W, C, BH>
+where
+    P: Int,
+    W: UInt,
+    C: UInt,
+    BH: BuildHasher + Clone + Default,
+{
+    fn get_inner_bounds(&self) -> Option<(Point3D<P>, Point3D<P>)> {
+        self.bounds
+    }
+
+    fn set_inner_bounds(&mut self, bounds: (Point3D<P>, Point3D<P>)) {
+        self.bounds = Some(bounds);
+    }
+}
+
+impl<P, W, C, BH> VoxelCollection<P, W, C> for HMap3DVoxelCollection<P, W, C, BH>
+where
+    BH: BuildHasher + Clone + Default,
+    P: Int,
+    C: UInt + AsPrimitive<W>,
+    W: UInt + AsPrimitive<C>,
+{
+    fn new(voxels: Vec<(Point3D<P>, Voxel<C, W>)>, bounds: Option<(Point3D<P>, Point3D<P>)>, offset: Point3D<P>, resolution: f64) -> Self {
+        let field = DashMap::<Point3D<P>, Voxel<C, W>, BH>::with_hasher(BH::default());
+
+        let voxels = voxels.into_iter().map(|(point, voxel)| { (point, voxel) }).collect::<Vec<_>>();
+
+        voxels.into_iter().for_each(|(point, voxel)| {
+            field.entry(point).and_modify(|current_voxel| {
+                Self::add_color_with_weight_check(current_voxel, voxel);
+            }).or_insert(voxel);
+        });
+
+
+        Self {
+            field,
+            bounds,
+            offset,
+            resolution,
+        }
+    }
+
+    fn has_bounds(&self) -> bool {
+        self.bounds.is_some()
+    }
+
+    fn get_resolution(&self) -> f64 {
+        self.resolution
+    }
+
+    fn get_offset(&self) -> Point3D<P> {
+        self.offset
+    }
+
+    fn set_offset(&mut self, offset: Point3D<P>) {
+        self.offset = offset;
+    }
+
+    fn iter(&self) -> impl Iterator<Item=(Point3D<P>, Voxel<C, W>)> {
+        self.field.iter().map(|entry| (*entry.key(), *entry.value()))
+    }
+
+    fn into_iter(self) -> impl Iterator<Item=(Point3D<P>, Voxel<C, W>)> {
+        self.field.into_iter()
+    }
+
+    // ハッシャーが`Sync`であることを要求しないように、シャードの逐次的な走査を分配する
+    #[cfg(feature = "rayon")]
+    fn par_iter(&self) -> impl ParallelIterator<Item=(Point3D<P>, Voxel<C, W>)> {
+        self.iter().par_bridge()
+    }
+
+    fn insert_one(&mut self, point: Point3D<P>, voxel: Voxel<C, W>) {
+        self.field.entry(point).and_modify(|current_voxel| {
+            Self::add_color_with_weight_check(current_voxel, voxel);
+        }).or_insert(voxel);
+
+        self.bounds = None;
+    }
+
+    fn has(&self, point: &Point3D<P>) -> bool {
+        self.field.contains_key(point)
+    }
+
+    fn get(&self, point: &Point3D<P>) -> Option<Voxel<C, W>> {
+        self.field.get(point).map(|voxel| *voxel)
+    }
+
+    fn remove(&mut self, point: &Point3D<P>) -> Option<Voxel<C, W>> {
+        let (_point, voxel) = self.field.remove(point)?;
+        self.bounds = None;
+
+        Some(voxel)
+    }
+
+    fn retain<F: FnMut(&Point3D<P>, &Voxel<C, W>) -> bool>(&mut self, mut f: F) {
+        self.field.retain(|point, voxel| f(point, voxel));
+        self.bounds = None;
+    }
+
+    fn batch(&mut self, f: fn(&mut Voxel<C, W>)) {
+        self.field.iter_mut().for_each(|mut entry| {
+            let (_point, voxel) = entry.pair_mut();
+            f(voxel);
+        });
+    }
+}
+
+/// [`BrickVoxelCollection`]における1つのブリック内のボクセルの配列です。
+pub type Brick<C, W> = Box<[Voxel<C, W>]>;
+
+/// 内部的に一辺`N`ボクセルの立方体(ブリック)ごとに密な配列を持ち、ブリックの座標をハッシュマップで管理しています。
+/// ボクセルが存在するブリックのみメモリを確保するため、`Vec3VoxelCollection`よりも広い範囲に疎に分布するボクセルを扱えます。
+/// ハッシュ値の計算はブリックごとに1回で済み、同じブリック内のボクセルは連続したメモリに配置されるため、`HMap3DVoxelCollection`よりも近傍の検索が高速であることが予想されます。
+/// 境界外の値を挿入する際にメモリの再確保は不要です。
+#[derive(Clone)]
+pub struct BrickVoxelCollection<P, W, C, BH, const N: usize = 8>
+where
+    P: Int,
+    W: UInt,
+    C: UInt,
+    BH: BuildHasher,
+{
+    /// ブリックの座標と、ブリック内のボクセルの配列です。
+    /// ブリック内の座標`(x, y, z)`のボクセルは、`(x * N + y) * N + z`番目に格納されます。
+    pub field: HashMap<Point3D<P>, Brick<C, W>, BH>,
+    bounds: Option<(Point3D<P>, Point3D<P>)>,
+    offset: Point3D<P>,
+    resolution: f64,
+}
+
+impl<P, W, C, BH, const N: usize> BrickVoxelCollection<P, W, C, BH, N>
+where
+    P: Int + AsPrimitive<usize>,
+    W: UInt,
+    C: UInt,
+    BH: BuildHasher,
+    usize: AsPrimitive<P>,
+{
+    // 座標値を、ブリックの座標とブリック内のインデックスに分解する
+    fn brick_index(point: &Point3D<P>) -> (Point3D<P>, usize) {
+        let size: P = N.as_();
+
+        let mut brick = Point3D::default();
+        let mut local = [0_usize; 3];
+
+        for axis in 0..3 {
+            // 負の値についても切り捨てになるように調整する
+            let mut quotient = point[axis] / size;
+            let mut remainder = point[axis] - quotient * size;
+
+            if remainder < P::zero() {
+                quotient -= P::one();
+                remainder += size;
+            }
+
+            brick[axis] = quotient;
+            local[axis] = remainder.as_();
+        }
+
+        (brick, (local[0] * N + local[1]) * N + local[2])
+    }
+
+    // ブリックの座標とブリック内のインデックスから、座標値を復元する
+    fn point_of(brick: &Point3D<P>, index: usize) -> Point3D<P> {
+        let local = Point3D::new([index / (N * N), index / N % N, index % N]).as_::<P>();
+
+        *brick * N.as_() + local
+    }
+}
+
+impl<P, W, C, BH, const N: usize> Default for BrickVoxelCollection<P, W, C, BH, N>
+where
+    P: Int,
+    W: UInt,
+    C: UInt,
+    BH: BuildHasher + Clone + Default,
+{
+    fn default() -> Self {
+        BrickVoxelCollection {
+            field: HashMap::with_hasher(BH::default()),
+            bounds: None,
+            offset: Point3D::<P>::default(),
+            resolution: 1.,
+        }
+    }
+}
+
+impl<P, W, C, BH, const N: usize> PrivateVoxelCollectionMethod<P, W, C> for BrickVoxelCollection<P, W, C, BH, N>
+where
+    P: Int,
+    W: UInt,
+    C: UInt,
+    BH: BuildHasher + Clone + Default,
+{
+    fn get_inner_bounds(&self) -> Option<(Point3D<P>, Point3D<P>)> {
+        self.bounds
+    }
+
+    fn set_inner_bounds(&mut self, bounds: (Point3D<P>, Point3D<P>)) {
+        self.bounds = Some(bounds);
+    }
+}
+
+impl<P, W, C, BH, const N: usize> VoxelCollection<P, W, C> for BrickVoxelCollection<P, W, C, BH, N>
+where
+    BH: BuildHasher + Clone + Default,
+    P: Int + AsPrimitive<usize>,
+    C: UInt + AsPrimitive<W>,
+    W: UInt + AsPrimitive<C>,
+    usize: AsPrimitive<P>,
+{
+    fn new(voxels: Vec<(Point3D<P>, Voxel<C, W>)>, bounds: Option<(Point3D<P>, Point3D<P>)>, offset: Point3D<P>, resolution: f64) -> Self {
+        let mut collection = Self {
+            offset,
+            resolution,
+            ..Self::default()
+        };
 
-            field[x][y][z] = voxel;
+        voxels.into_iter().for_each(|(point, voxel)| {
+            collection.insert_one(point, voxel);
         });
 
-        Self {
-            field,
-            bounds: (min, max),
-            offset,
-            resolution,
-        }
+        collection.bounds = bounds;
+
+        collection
     }
+
     fn has_bounds(&self) -> bool {
-        true
+        self.bounds.is_some()
     }
 
     fn get_resolution(&self) -> f64 {
@@ -529,309 +2053,576 @@ where
         self.offset = offset;
     }
 
-    fn to_vec(&self) -> Vec<(Point3D<P>, Voxel<C, W>)> {
-        self.field.iter().enumerate().flat_map(|(x, y_vec)| {
-            y_vec.iter().enumerate().flat_map(move |(y, z_vec)| {
-                z_vec.iter().enumerate().map(move |(z, voxel)| {
-                    let point = Point3D::new([x, y, z]).as_() + self.bounds.0;
-                    (point, *voxel)
-                })
-            })
-        }).filter(|(_, voxel)| voxel.weight.ne(&W::zero())).collect()
+    fn iter(&self) -> impl Iterator<Item=(Point3D<P>, Voxel<C, W>)> {
+        self.field.iter().flat_map(|(brick, voxels)| {
+            voxels.iter().enumerate()
+                .filter(|(_, voxel)| voxel.weight.ne(&W::zero()))
+                .map(move |(index, voxel)| (Self::point_of(brick, index), *voxel))
+        })
     }
 
-    fn into_vec(self) -> Vec<(Point3D<P>, Voxel<C, W>)> {
-        self.field.into_iter().enumerate().flat_map(|(x, y_vec)| {
-            y_vec.into_iter().enumerate().flat_map(move |(y, z_vec)| {
-                z_vec.into_iter().enumerate().map(move |(z, voxel)| {
-                    let point = Point3D::new([x, y, z]).as_() + self.bounds.0;
-                    (point, voxel)
-                })
-            })
-        }).filter(|(_, voxel)| voxel.weight.ne(&W::zero())).collect()
+    fn into_iter(self) -> impl Iterator<Item=(Point3D<P>, Voxel<C, W>)> {
+        self.field.into_iter().flat_map(|(brick, voxels)| {
+            voxels.into_vec().into_iter().enumerate()
+                .filter(|(_, voxel)| voxel.weight.ne(&W::zero()))
+                .map(move |(index, voxel)| (Self::point_of(&brick, index), voxel))
+        })
     }
 
+    // ブリックごとに並列に走査する
+    #[cfg(feature = "rayon")]
+    fn par_iter(&self) -> impl ParallelIterator<Item=(Point3D<P>, Voxel<C, W>)> {
+        self.field.iter().par_bridge().flat_map_iter(|(brick, voxels)| {
+            voxels.iter().enumerate()
+                .filter(|(_, voxel)| voxel.weight.ne(&W::zero()))
+                .map(move |(index, voxel)| (Self::point_of(brick, index), *voxel))
+        })
+    }
 
     fn insert_one(&mut self, point: Point3D<P>, voxel: Voxel<C, W>) {
-        let point = point - self.bounds.0;
-        let x: usize = point[0].as_();
-        let y: usize = point[1].as_();
-        let z: usize = point[2].as_();
+        let (brick, index) = Self::brick_index(&point);
 
-        if let Some(y_vec) = self.field.get_mut(x) {
-            if let Some(z_vec) = y_vec.get_mut(y) {
-                if let Some(current_voxel) = z_vec.get_mut(z) {
-                    Self::add_color_with_weight_check(current_voxel, voxel);
-                }
-            }
+        let voxels = self.field.entry(brick).or_insert_with(|| {
+            vec![Voxel::default(); N * N * N].into_boxed_slice()
+        });
+
+        Self::add_color_with_weight_check(&mut voxels[index], voxel);
+
+        // 境界が計算済みの場合のみ更新し、未計算の場合は必要になった段階で計算する
+        if let Some(bounds) = self.bounds {
+            self.bounds = Some(Self::calc_bounds_from_2(bounds, (point, point)));
         }
     }
 
     fn has(&self, point: &Point3D<P>) -> bool {
-        let point = *point - self.bounds.0;
-        let x: usize = point[0].as_();
-        let y: usize = point[1].as_();
-        let z: usize = point[2].as_();
-
-        if let Some(y_vec) = self.field.get(x) {
-            if let Some(z_vec) = y_vec.get(y) {
-                if let Some(voxel) = z_vec.get(z) {
-                    return voxel.weight.ne(&W::zero());
+        let (brick, index) = Self::brick_index(point);
+
+        self.field.get(&brick).is_some_and(|voxels| voxels[index].weight.ne(&W::zero()))
+    }
+
+    fn get(&self, point: &Point3D<P>) -> Option<Voxel<C, W>> {
+        let (brick, index) = Self::brick_index(point);
+        let voxel = self.field.get(&brick)?[index];
+
+        voxel.weight.ne(&W::zero()).then_some(voxel)
+    }
+
+    // ブリック内のボクセルがすべて削除された場合は、ブリックも削除する
+    fn remove(&mut self, point: &Point3D<P>) -> Option<Voxel<C, W>> {
+        let (brick, index) = Self::brick_index(point);
+        let voxels = self.field.get_mut(&brick)?;
+        let voxel = mem::take(&mut voxels[index]);
+
+        if voxel.weight.eq(&W::zero()) {
+            return None;
+        }
+
+        if voxels.iter().all(|voxel| voxel.weight.eq(&W::zero())) {
+            self.field.remove(&brick);
+        }
+
+        self.bounds = None;
+
+        Some(voxel)
+    }
+
+    fn retain<F: FnMut(&Point3D<P>, &Voxel<C, W>) -> bool>(&mut self, mut f: F) {
+        self.field.retain(|brick, voxels| {
+            voxels.iter_mut().enumerate().for_each(|(index, voxel)| {
+                if voxel.weight.ne(&W::zero()) && !f(&Self::point_of(brick, index), voxel) {
+                    *voxel = Voxel::default();
                 }
+            });
+
+            voxels.iter().any(|voxel| voxel.weight.ne(&W::zero()))
+        });
+
+        self.bounds = None;
+    }
+
+    fn batch(&mut self, f: fn(&mut Voxel<C, W>)) {
+        self.field.values_mut().for_each(|voxels| {
+            voxels.iter_mut().filter(|voxel| voxel.weight.ne(&W::zero())).for_each(f);
+        });
+    }
+}
+
+type OctreeChildren<C, W> = Box<[Option<OctreeNode<C, W>>; 8]>;
+
+// ノード以下の葉の色の和と重みの和
+// 多数の葉を集約すると色や重みの型の最大値を超えるため、`f64`で保持する
+#[derive(Clone, Copy, Default)]
+struct OctreeSum {
+    color: [f64; 3],
+    weight: f64,
+}
+
+impl OctreeSum {
+    fn of<C, W>(voxel: Voxel<C, W>) -> Self
+    where
+        C: UInt + AsPrimitive<f64>,
+        W: UInt + AsPrimitive<f64>,
+    {
+        Self { color: voxel.color.data.map(|a| a.as_()), weight: voxel.weight.as_() }
+    }
+
+    fn add(&mut self, other: Self) {
+        (0..3).for_each(|i| self.color[i] += other.color[i]);
+        self.weight += other.weight;
+    }
+
+    fn sub(mut self, other: Self) -> Self {
+        (0..3).for_each(|i| self.color[i] -= other.color[i]);
+        self.weight -= other.weight;
+        self
+    }
+}
+
+// 八分木の1つのノード
+// 葉(レベル0)は子を持たずにボクセルを保持し、すべてのノードは子孫の葉の和を保持する
+#[derive(Clone)]
+struct OctreeNode<C: UInt, W: UInt> {
+    voxel: Voxel<C, W>,
+    sum: OctreeSum,
+    count: usize,
+    children: Option<OctreeChildren<C, W>>,
+}
+
+impl<C: UInt, W: UInt> OctreeNode<C, W> {
+    fn new(level: u32) -> Self {
+        Self {
+            voxel: Voxel { color: Color::default(), weight: W::zero() },
+            sum: OctreeSum::default(),
+            count: 0,
+            children: (level > 0).then(Default::default),
+        }
+    }
+
+    // 子ノードのインデックスと、その原点を返す
+    fn child_index(origin: Point3D<i64>, level: u32, point: Point3D<i64>) -> (usize, Point3D<i64>) {
+        let half = 1_i64 << (level - 1);
+
+        let mut index = 0;
+        let mut child_origin = origin;
+        for axis in 0..3 {
+            if point[axis] >= origin[axis] + half {
+                index |= 1 << axis;
+                child_origin[axis] += half;
             }
         }
-        false
+
+        (index, child_origin)
     }
 
-    fn batch(&mut self, f: fn(&mut Voxel<C, W>)) {
-        self.field.iter_mut().for_each(|y_vec| {
-            y_vec.iter_mut().for_each(|z_vec| {
-                z_vec.iter_mut().for_each(|voxel| {
-                    f(voxel);
-                });
+    fn child_origin(origin: Point3D<i64>, level: u32, index: usize) -> Point3D<i64> {
+        let half = 1_i64 << (level - 1);
+
+        let mut child_origin = origin;
+        for axis in 0..3 {
+            if index & (1 << axis) != 0 {
+                child_origin[axis] += half;
+            }
+        }
+
+        child_origin
+    }
+
+    fn for_each_at_level<F: FnMut(Point3D<i64>, &OctreeNode<C, W>)>(&self, origin: Point3D<i64>, level: u32, target: u32, f: &mut F) {
+        if level == target {
+            f(origin, self);
+            return;
+        }
+
+        if let Some(children) = &self.children {
+            children.iter().enumerate().for_each(|(i, child)| {
+                if let Some(child) = child {
+                    child.for_each_at_level(Self::child_origin(origin, level, i), level - 1, target, f);
+                }
             });
-        });
+        }
     }
 }
 
-/// 内部的に2次元配列を使用してボクセルの集合を表現するための構造体です。
-/// 平面座標ごとに1つの高さしか持てませんが、`Vec3VoxelCollection`よりもメモリ効率が良いです。
-/// 隣接する座標値の検索を高速に行えます。
-/// また、境界外の値を挿入するにはメモリの再確保を行う必要があります。
+/// 内部的に疎な八分木を使用してボクセルの集合を表現するための構造体です。
+/// 内部ノードは子孫のボクセルの色と重みを集約して保持するため、任意の詳細度(LOD)で粗くしたボクセルを走査せずに取得できます。
+/// また、範囲の検索やレイキャストは空のノードを読み飛ばして行えます。
+/// 1点の挿入・検索には木の深さに比例する時間がかかるため、`HMap3DVoxelCollection`よりも低速であることが予想されます。
+/// 境界外の値を挿入した場合は、木の根を上位のノードに置き換えて拡張します。
+/// ただし、木の深さは[`OctreeVoxelCollection::MAX_DEPTH`]までに制限され、各軸の座標値が`-2^(MAX_DEPTH - 1)`以上`2^(MAX_DEPTH - 1)`未満でないボクセルは挿入時に無視されます。
 #[derive(Clone)]
-pub struct Vec2VoxelCollection<P, W, C>
+pub struct OctreeVoxelCollection<P, W, C>
 where
     P: Int,
     W: UInt,
     C: UInt,
 {
-    pub field: Vec<Vec<(P, Voxel<C, W>)>>,
-    bounds_xy: (Point2D<P>, Point2D<P>),
-    bounds_z: Option<(P, P)>,
+    root: Option<OctreeNode<C, W>>,
+    // 根のノードのレベル(一辺の長さは2^level)
+    root_level: u32,
+    bounds: Option<(Point3D<P>, Point3D<P>)>,
     offset: Point3D<P>,
     resolution: f64,
 }
 
-impl<P, W, C> Vec2VoxelCollection<P, W, C>
+impl<P, W, C> OctreeVoxelCollection<P, W, C>
 where
-    P: Int,
-    W: UInt,
-    C: UInt,
+    P: Int + AsPrimitive<i64>,
+    C: UInt + AsPrimitive<W> + AsPrimitive<f64>,
+    W: UInt + AsPrimitive<C> + AsPrimitive<f64>,
+    i64: AsPrimitive<P>,
+    f64: AsPrimitive<C> + AsPrimitive<W>,
 {
-    pub fn get_bounds_xy(&self) -> (Point2D<P>, Point2D<P>) {
-        self.bounds_xy
+    /// 木の深さの上限です。
+    /// 根の一辺の長さや座標値の計算が`i64`の範囲に収まるように制限しています。
+    pub const MAX_DEPTH: u32 = 62;
+
+    fn to_inner(point: &Point3D<P>) -> Point3D<i64> {
+        Point3D::new(point.data.map(|a| a.as_()))
     }
-}
 
-impl<P, W, C> Default for Vec2VoxelCollection<P, W, C>
-where
-    P: Int,
-    W: UInt,
-    C: UInt,
-{
-    fn default() -> Self {
-        Vec2VoxelCollection {
-            field: Vec::default(),
-            bounds_xy: (Point2D::default(), Point2D::default()),
-            bounds_z: Some((P::default(), P::default())),
-            offset: Point3D::<P>::default(),
-            resolution: 1.,
-        }
+    // 木の深さの上限で表現できる座標値に変換する
+    // `i64`に変換すると符号が変わる値(`u64`の大きな値など)も表現できないものとして扱う
+    fn to_inner_checked(point: &Point3D<P>) -> Option<Point3D<i64>> {
+        let inner = Self::to_inner(point);
+        let half = 1_i64 << (Self::MAX_DEPTH - 1);
+
+        (0..3).all(|axis| {
+            (point[axis] >= P::zero()) == (inner[axis] >= 0) && -half <= inner[axis] && inner[axis] < half
+        }).then_some(inner)
     }
-}
 
-impl<P, W, C> PrivateVoxelCollectionMethod<P, W, C> for Vec2VoxelCollection<P, W, C>
-where
-    P: Int,
-    W: UInt,
-    C: UInt,
-{
-    fn get_inner_bounds(&self) -> Option<(Point3D<P>, Point3D<P>)> {
-        if let Some((min_z, max_z)) = self.bounds_z {
-            let (min_xy, max_xy) = self.bounds_xy;
+    fn from_inner(point: Point3D<i64>) -> Point3D<P> {
+        Point3D::new(point.data.map(|a| a.as_()))
+    }
 
-            Some((Point3D::new([min_xy[0], min_xy[1], min_z]), Point3D::new([max_xy[0], max_xy[1], max_z])))
-        } else {
-            None
-        }
+    // 根のノードの原点
+    // 根は原点を中心とする一辺2^levelの立方体を表すため、レベルが根より低いノードの原点は常に2^levelの倍数になる
+    fn root_origin(&self) -> Point3D<i64> {
+        Point3D::from(-(1_i64 << (self.root_level - 1)))
     }
-    fn set_inner_bounds(&mut self, _bounds: (Point3D<P>, Point3D<P>)) {
-        panic!("Vec2VoxelCollection can't set bounds manually")
+
+    // 根のノードが座標値を含むまで木を拡張する
+    fn grow_to_include(&mut self, point: Point3D<i64>) {
+        if self.root.is_none() {
+            self.root = Some(OctreeNode::new(self.root_level));
+        }
+
+        while !self.contains(point) {
+            let root = self.root.take().unwrap();
+
+            // 根の子ノードを、一段深い位置に移動する
+            // 例えばx軸の負側の子ノードは、新しい根の負側の子ノードの正側の子ノードになる
+            let level = self.root_level + 1;
+
+            let mut parent = OctreeNode::new(level);
+            parent.sum = root.sum;
+            parent.count = root.count;
+
+            if let (Some(children), Some(parent_children)) = (root.children, parent.children.as_mut()) {
+                children.into_iter().enumerate().for_each(|(i, child)| {
+                    if let Some(child) = child {
+                        let mut middle = OctreeNode::new(level - 1);
+                        middle.sum = child.sum;
+                        middle.count = child.count;
+                        middle.children.as_mut().unwrap()[i ^ 7] = Some(child);
+                        parent_children[i] = Some(middle);
+                    }
+                });
+            }
+
+            self.root = Some(parent);
+            self.root_level = level;
+        }
     }
-}
 
-impl<P, W, C> VoxelCollection<P, W, C> for Vec2VoxelCollection<P, W, C>
-where
-    P: Int + AsPrimitive<usize>,
-    C: UInt + AsPrimitive<W>,
-    W: UInt + AsPrimitive<C>,
-    usize: AsPrimitive<P>,
-{
-    fn new(points: Vec<(Point3D<P>, Voxel<C, W>)>, bounds: Option<(Point3D<P>, Point3D<P>)>, offset: Point3D<P>, resolution: f64) -> Self {
-        let (min, max) = bounds.unwrap_or_else(|| {
-            Self::calc_bounds(&points)
+    // 子ノードの集約値から、ノードの集約値を再計算する
+    fn aggregate(node: &mut OctreeNode<C, W>) {
+        let Some(children) = node.children.as_ref() else { return };
+
+        let mut sum = OctreeSum::default();
+        let mut count = 0;
+
+        children.iter().flatten().for_each(|child| {
+            sum.add(child.sum);
+            count += child.count;
         });
 
-        let field_size = max - min + P::one();
+        node.sum = sum;
+        node.count = count;
+    }
 
-        let mut field = vec![vec![(P::default(), Voxel::<C, W>::default()); field_size[1].as_()]; field_size[0].as_()];
+    // 葉のボクセルを削除し、経路上のノードの集約値を再計算する
+    // 空になった子ノードは削除する
+    fn remove_from(node: &mut OctreeNode<C, W>, origin: Point3D<i64>, level: u32, point: Point3D<i64>) -> Option<Voxel<C, W>> {
+        if level == 0 {
+            node.count = 0;
+            node.sum = OctreeSum::default();
+            return Some(mem::take(&mut node.voxel)).filter(|voxel| voxel.weight.ne(&W::zero()));
+        }
 
-        points.into_iter().for_each(|(point, voxel)| {
-            let x: usize = (point[0] - min[0]).as_();
-            let y: usize = (point[1] - min[1]).as_();
-            let z = point[2];
+        let (index, child_origin) = OctreeNode::<C, W>::child_index(origin, level, point);
+        let children = node.children.as_mut()?;
+        let voxel = Self::remove_from(children[index].as_mut()?, child_origin, level - 1, point)?;
 
-            field[x][y] = (z, voxel);
+        if children[index].as_ref().is_some_and(|child| child.count == 0) {
+            children[index] = None;
+        }
+
+        Self::aggregate(node);
+
+        Some(voxel)
+    }
+
+    fn retain_in<F: FnMut(&Point3D<P>, &Voxel<C, W>) -> bool>(node: &mut OctreeNode<C, W>, origin: Point3D<i64>, level: u32, f: &mut F) {
+        let Some(children) = node.children.as_mut() else {
+            if !f(&Self::from_inner(origin), &node.voxel) {
+                node.voxel = Voxel::default();
+                node.sum = OctreeSum::default();
+                node.count = 0;
+            }
+            return;
+        };
+
+        children.iter_mut().enumerate().for_each(|(i, child)| {
+            if let Some(node) = child.as_mut() {
+                Self::retain_in(node, OctreeNode::<C, W>::child_origin(origin, level, i), level - 1, f);
+            }
+
+            if child.as_ref().is_some_and(|node| node.count == 0) {
+                *child = None;
+            }
         });
 
-        Self {
-            field,
-            bounds_xy: (min.fit(), max.fit()),
-            bounds_z: Some((min[2], max[2])),
-            offset,
-            resolution,
+        Self::aggregate(node);
+    }
+
+    // 葉にボクセルを加算し、経路上のノードの和と葉の数を更新する
+    // 葉の和の変化量と、新しい葉かどうかを返す
+    fn insert_into(node: &mut OctreeNode<C, W>, origin: Point3D<i64>, level: u32, point: Point3D<i64>, voxel: Voxel<C, W>) -> (OctreeSum, bool) {
+        let (delta, is_new) = if level == 0 {
+            let (before, is_new) = (node.sum, node.voxel.weight == W::zero());
+
+            Self::add_color_with_weight_check(&mut node.voxel, voxel);
+            node.sum = OctreeSum::of(node.voxel);
+
+            (node.sum.sub(before), is_new)
+        } else {
+            let (index, child_origin) = OctreeNode::<C, W>::child_index(origin, level, point);
+            let child = node.children.as_mut().unwrap()[index].get_or_insert_with(|| OctreeNode::new(level - 1));
+
+            let (delta, is_new) = Self::insert_into(child, child_origin, level - 1, point, voxel);
+            node.sum.add(delta);
+
+            (delta, is_new)
+        };
+
+        if is_new {
+            node.count += 1;
         }
+
+        (delta, is_new)
     }
 
-    fn has_bounds(&self) -> bool {
-        true
+    // 木が空になった場合は、根を初期状態に戻す
+    fn prune_root(&mut self) {
+        if self.root.as_ref().is_some_and(|root| root.count == 0) {
+            self.root = None;
+            self.root_level = 1;
+        }
     }
 
-    fn get_bounds(&mut self) -> (Point3D<P>, Point3D<P>) {
-        let bounds_z = self.bounds_z.unwrap_or_else(|| {
-            let z = self.field.iter().flat_map(|y_vec| {
-                y_vec.iter().filter_map(|(z, voxel)| {
-                    if voxel.weight.ne(&W::zero()) {
-                        Some(*z)
-                    } else {
-                        None
-                    }
-                })
-            }).collect::<Vec<_>>();
+    // ノード以下の葉のボクセルを深さ優先で返す
+    fn leaves(node: &OctreeNode<C, W>, origin: Point3D<i64>, level: u32) -> impl Iterator<Item=(Point3D<P>, Voxel<C, W>)> + '_ {
+        let mut stack = vec![(node, origin, level)];
 
-            let min = z.iter().min().unwrap();
-            let max = z.iter().max().unwrap();
+        iter::from_fn(move || {
+            while let Some((node, origin, level)) = stack.pop() {
+                let Some(children) = &node.children else {
+                    return Some((Self::from_inner(origin), node.voxel));
+                };
 
-            (*min, *max)
-        });
+                stack.extend(children.iter().enumerate().filter_map(|(i, child)| {
+                    Some((child.as_ref()?, OctreeNode::<C, W>::child_origin(origin, level, i), level - 1))
+                }));
+            }
 
-        let min_xy = self.bounds_xy.0;
-        let max_xy = self.bounds_xy.1;
+            None
+        })
+    }
+
+    // 根のノードが座標値を含むかどうかを返す
+    fn contains(&self, point: Point3D<i64>) -> bool {
+        let (origin, size) = (self.root_origin(), 1_i64 << self.root_level);
+
+        (0..3).all(|axis| origin[axis] <= point[axis] && point[axis] < origin[axis] + size)
+    }
+
+    // 根から葉までのノードを返す
+    fn find(&self, point: &Point3D<P>) -> Option<&OctreeNode<C, W>> {
+        let point = Self::to_inner(point);
+
+        if !self.contains(point) {
+            return None;
+        }
+
+        let mut origin = self.root_origin();
+        let mut node = self.root.as_ref()?;
+        let mut level = self.root_level;
 
-        let min = Point3D::new([min_xy[0], min_xy[1], bounds_z.0]);
-        let max = Point3D::new([max_xy[0], max_xy[1], bounds_z.1]);
+        while level > 0 {
+            let (index, child_origin) = OctreeNode::<C, W>::child_index(origin, level, point);
+            node = node.children.as_ref()?[index].as_ref()?;
+            origin = child_origin;
+            level -= 1;
+        }
 
-        (min, max)
+        Some(node)
     }
 
-    fn get_resolution(&self) -> f64 {
-        self.resolution
+    // 指定した座標のボクセルを返す
+    fn voxel_at(&self, point: &Point3D<P>) -> Option<Voxel<C, W>> {
+        self.find(point).map(|node| node.voxel)
     }
 
-    fn get_offset(&self) -> Point3D<P> {
-        self.offset
+    /// 木の深さを返します。
+    /// 根のノードは一辺が`2^depth`ボクセルの立方体を表します。
+    pub fn depth(&self) -> u32 {
+        self.root_level
     }
 
-    fn set_offset(&mut self, offset: Point3D<P>) {
-        self.offset = offset;
-    }
+    /// 一辺が`2^level`ボクセルの立方体ごとに集約したボクセルを返します。
+    /// 座標値は元の座標値を`2^level`で割って切り捨てた値になります。
+    /// 色は集約されたボクセルの色の和、重みは重みの和です。
+    /// 和が色や重みの型で表現できない場合は、平均色を保ったまま重みを飽和させます。
+    /// `level`が0の場合は`to_vec`と同じ結果を返します。
+    /// `level`が[`OctreeVoxelCollection::depth`]以上の場合は、すべて原点の負側と正側の2つのボクセルに集約されるため、同じ結果を返します。
+    pub fn to_vec_at_level(&self, level: u32) -> Vec<(Point3D<P>, Voxel<C, W>)> {
+        let Some(root) = &self.root else { return Vec::new() };
 
-    fn to_vec(&self) -> Vec<(Point3D<P>, Voxel<C, W>)> {
-        self.field.iter().enumerate().flat_map(|(x, y_vec)| {
-            y_vec.iter().enumerate().map(move |(y, (z, voxel))| {
-                let min_xy = self.bounds_xy.0;
+        // 根の大きさ以上のレベルはすべて同じ結果になるため、シフト量が座標値のビット数を超えないように制限する
+        let level = level.min(self.root_level).min(i64::BITS - 1);
 
-                let x = x.as_() + min_xy[0];
-                let y = y.as_() + min_xy[1];
+        if level == 0 {
+            return Self::leaves(root, self.root_origin(), self.root_level).collect();
+        }
 
-                let point = Point3D::new([x, y, *z]);
-                (point, *voxel)
-            })
-        }).filter(|(_, voxel)| voxel.weight.ne(&W::zero())).collect()
-    }
+        // 根は2^levelの倍数に揃っていないため、根の子ノードより粗い場合は子ノードを集約する
+        let target = level.min(self.root_level - 1);
 
-    fn into_vec(self) -> Vec<(Point3D<P>, Voxel<C, W>)> {
-        self.field.into_iter().enumerate().flat_map(|(x, y_vec)| {
-            y_vec.into_iter().enumerate().map(move |(y, (z, voxel))| {
-                let min_xy = self.bounds_xy.0;
+        let mut sums: Vec<(Point3D<P>, OctreeSum)> = Vec::new();
+        root.for_each_at_level(self.root_origin(), self.root_level, target, &mut |origin, node| {
+            let point = Self::from_inner(origin.batch(|a| a >> level));
 
-                let x = x.as_() + min_xy[0];
-                let y = y.as_() + min_xy[1];
+            match sums.iter_mut().find(|(p, _)| level > target && *p == point) {
+                Some((_, sum)) => sum.add(node.sum),
+                None => sums.push((point, node.sum)),
+            }
+        });
 
-                let point = Point3D::new([x, y, z]);
-                (point, voxel)
-            })
-        }).filter(|(_, voxel)| voxel.weight.ne(&W::zero())).collect()
+        sums.into_iter().map(|(point, sum)| (point, voxel_from_sum(sum.color, sum.weight))).collect()
     }
 
-    fn insert_one(&mut self, point: Point3D<P>, voxel: Voxel<C, W>) {
-        let x: usize = (point[0] - self.bounds_xy.0[0]).as_();
-        let y: usize = (point[1] - self.bounds_xy.0[1]).as_();
-        let z = point[2];
-        if let Some(y_vec) = self.field.get_mut(x) {
-            if let Some((height, current_voxel)) = y_vec.get_mut(y) {
-                if *height != z {
-                    *height = z;
-                    *current_voxel = voxel;
+    /// 一辺が`2^level`ボクセルの立方体ごとに集約したボクセルから、新しい`VoxelCollection`を生成します。
+    /// 分解能は`2^level`倍になり、オフセットは`2^level`で割って切り捨てた値になります。
+    pub fn to_lod<VC: VoxelCollection<P, W, C>>(&self, level: u32) -> VC {
+        let offset = Self::from_inner(Self::to_inner(&self.offset).batch(|a| a >> level.min(i64::BITS - 1)));
 
-                    return;
-                }
+        VC::builder()
+            .voxels(self.to_vec_at_level(level))
+            .offset(offset)
+            .resolution(self.resolution * 2_f64.powf(level as f64))
+            .build()
+    }
 
-                Self::add_color_with_weight_check(current_voxel, voxel);
+    /// `min`から`max`まで(両端を含む)の直方体に含まれるボクセルの数を返します。
+    /// 直方体に完全に含まれるノードや、直方体と交わらないノードの子孫は走査しません。
+    pub fn count_in_box(&self, min: &Point3D<P>, max: &Point3D<P>) -> usize {
+        let Some(root) = &self.root else { return 0 };
+        let (min, max) = (Self::to_inner(min), Self::to_inner(max));
+
+        fn count<C: UInt, W: UInt>(node: &OctreeNode<C, W>, origin: Point3D<i64>, level: u32, min: Point3D<i64>, max: Point3D<i64>) -> usize {
+            let end = origin + Point3D::from((1_i64 << level) - 1);
+
+            if (0..3).any(|axis| end[axis] < min[axis] || origin[axis] > max[axis]) {
+                return 0;
             }
+
+            if (0..3).all(|axis| min[axis] <= origin[axis] && end[axis] <= max[axis]) {
+                return node.count;
+            }
+
+            node.children.iter().flat_map(|children| children.iter().enumerate()).map(|(i, child)| {
+                child.as_ref().map_or(0, |child| count(child, OctreeNode::<C, W>::child_origin(origin, level, i), level - 1, min, max))
+            }).sum()
         }
 
-        self.bounds_z = None;
+        count(root, self.root_origin(), self.root_level, min, max)
     }
 
-    fn has(&self, point: &Point3D<P>) -> bool {
-        let x: usize = point[0].as_();
-        let y: usize = point[1].as_();
-        let z = point[2];
-        if let Some(y_vec) = self.field.get(x) {
-            if let Some((height, voxel)) = y_vec.get(y) {
-                return *height == z && voxel.weight.ne(&W::zero());
+    /// 半直線と最初に交わるボクセルの座標と、交点までの距離を返します。
+    /// `origin`と`direction`は、ボクセルの一辺を1とした座標系で指定します。座標`(x, y, z)`のボクセルは`[x, x + 1)`の範囲を占めます。
+    /// 距離は`direction`の長さを単位とし、`max_distance`以内の交点のみを対象とします。
+    pub fn raycast(&self, origin: [f64; 3], direction: [f64; 3], max_distance: f64) -> Option<(Point3D<P>, f64)> {
+        let root = self.root.as_ref()?;
+
+        // 立方体と半直線の交差区間を返す
+        let intersect = |node_origin: Point3D<i64>, level: u32| -> Option<(f64, f64)> {
+            let size = (1_i64 << level) as f64;
+            let (mut t_min, mut t_max) = (0_f64, max_distance);
+
+            for axis in 0..3 {
+                let min = node_origin[axis] as f64;
+                let max = min + size;
+
+                if direction[axis] == 0. {
+                    if origin[axis] < min || origin[axis] >= max {
+                        return None;
+                    }
+                    continue;
+                }
+
+                let t1 = (min - origin[axis]) / direction[axis];
+                let t2 = (max - origin[axis]) / direction[axis];
+                t_min = t_min.max(t1.min(t2));
+                t_max = t_max.min(t1.max(t2));
             }
+
+            (t_min <= t_max).then_some((t_min, t_max))
+        };
+
+        fn hit<C: UInt, W: UInt>(node: &OctreeNode<C, W>, origin: Point3D<i64>, level: u32, t: f64, intersect: &dyn Fn(Point3D<i64>, u32) -> Option<(f64, f64)>) -> Option<(Point3D<i64>, f64)> {
+            let Some(children) = &node.children else {
+                return Some((origin, t));
+            };
+
+            let mut candidates = children.iter().enumerate().filter_map(|(i, child)| {
+                let child = child.as_ref()?;
+                let child_origin = OctreeNode::<C, W>::child_origin(origin, level, i);
+                let (t, _) = intersect(child_origin, level - 1)?;
+                Some((t, child, child_origin))
+            }).collect::<Vec<_>>();
+
+            candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            candidates.into_iter().find_map(|(t, child, child_origin)| hit(child, child_origin, level - 1, t, intersect))
         }
-        false
-    }
 
-    fn batch(&mut self, f: fn(&mut Voxel<C, W>)) {
-        self.field.iter_mut().for_each(|y_vec| {
-            y_vec.iter_mut().for_each(|(_z, voxel)| {
-                f(voxel);
-            });
-        });
-    }
-}
+        let (t, _) = intersect(self.root_origin(), self.root_level)?;
 
-/// 内部的にハッシュマップを使用して座標を管理しています。
-/// 1点挿入するごとにハッシュ値を計算するため、`Vec3VoxelCollection`よりも低速であることが予想されますが、境界外の値を挿入する際にメモリの再確保が不要です。
-/// 隣接点の検索は1回のハッシュ値計算で行えるため、`PointCloud`よりも高速です。
-#[derive(Clone)]
-pub struct HMap3DVoxelCollection<P, W, C, BH>
-where
-    P: Int,
-    W: UInt,
-    C: UInt,
-    BH: BuildHasher,
-{
-    pub field: DashMap<Point3D<P>, Voxel<C, W>, BH>,
-    bounds: Option<(Point3D<P>, Point3D<P>)>,
-    offset: Point3D<P>,
-    resolution: f64,
+        hit(root, self.root_origin(), self.root_level, t, &intersect).map(|(point, t)| (Self::from_inner(point), t))
+    }
 }
 
-impl<P, W, C, BH> Default for HMap3DVoxelCollection<P, W, C, BH>
+impl<P, W, C> Default for OctreeVoxelCollection<P, W, C>
 where
     P: Int,
     W: UInt,
     C: UInt,
-    BH: BuildHasher + Clone + Default,
 {
     fn default() -> Self {
-        HMap3DVoxelCollection {
-            field: DashMap::with_hasher(BH::default()),
+        OctreeVoxelCollection {
+            root: None,
+            root_level: 1,
             bounds: None,
             offset: Point3D::<P>::default(),
             resolution: 1.,
@@ -839,13 +2630,7 @@ where
     }
 }
 
-impl<P, W, C, BH> PrivateVoxelCollectionMethod<P, W, C> for HMap3DVoxelCollection<P, W, C, BH>
-where
-    P: Int,
-    W: UInt,
-    C: UInt,
-    BH: BuildHasher + Clone + Default,
-{
+impl<P: Int, W: UInt, C: UInt> PrivateVoxelCollectionMethod<P, W, C> for OctreeVoxelCollection<P, W, C> {
     fn get_inner_bounds(&self) -> Option<(Point3D<P>, Point3D<P>)> {
         self.bounds
     }
@@ -855,31 +2640,30 @@ where
     }
 }
 
-impl<P, W, C, BH> VoxelCollection<P, W, C> for HMap3DVoxelCollection<P, W, C, BH>
+impl<P, W, C> VoxelCollection<P, W, C> for OctreeVoxelCollection<P, W, C>
 where
-    BH: BuildHasher + Clone + Default,
-    P: Int,
-    C: UInt + AsPrimitive<W>,
-    W: UInt + AsPrimitive<C>,
+    P: Int + AsPrimitive<i64>,
+    C: UInt + AsPrimitive<W> + AsPrimitive<f64>,
+    W: UInt + AsPrimitive<C> + AsPrimitive<f64>,
+    i64: AsPrimitive<P>,
+    f64: AsPrimitive<C> + AsPrimitive<W>,
 {
     fn new(voxels: Vec<(Point3D<P>, Voxel<C, W>)>, bounds: Option<(Point3D<P>, Point3D<P>)>, offset: Point3D<P>, resolution: f64) -> Self {
-        let field = DashMap::<Point3D<P>, Voxel<C, W>, BH>::with_hasher(BH::default());
-
-        let voxels = voxels.into_iter().map(|(point, voxel)| { (point, voxel) }).collect::<Vec<_>>();
+        let mut collection = Self {
+            offset,
+            resolution,
+            ..Self::default()
+        };
 
         voxels.into_iter().for_each(|(point, voxel)| {
-            field.entry(point).and_modify(|current_voxel| {
-                Self::add_color_with_weight_check(current_voxel, voxel);
-            }).or_insert(voxel);
+            collection.insert_one(point, voxel);
         });
 
-
-        Self {
-            field,
-            bounds,
-            offset,
-            resolution,
+        if bounds.is_some() {
+            collection.bounds = bounds;
         }
+
+        collection
     }
 
     fn has_bounds(&self) -> bool {
@@ -898,37 +2682,116 @@ where
         self.offset = offset;
     }
 
-    fn to_vec(&self) -> Vec<(Point3D<P>, Voxel<C, W>)> {
-        self.field.clone().into_iter().map(|entry| {
-            let (point, voxel) = entry;
-            (point, voxel)
-        }).collect()
+    fn iter(&self) -> impl Iterator<Item=(Point3D<P>, Voxel<C, W>)> {
+        self.root.iter().flat_map(|root| Self::leaves(root, self.root_origin(), self.root_level))
     }
 
-    fn into_vec(self) -> Vec<(Point3D<P>, Voxel<C, W>)> {
-        self.field.into_iter().map(|entry| {
-            let (point, voxel) = entry;
-            (point, voxel)
-        }).collect()
+    fn into_iter(self) -> impl Iterator<Item=(Point3D<P>, Voxel<C, W>)> {
+        let (origin, level) = (self.root_origin(), self.root_level);
+        let mut stack = Vec::from_iter(self.root.map(|root| (root, origin, level)));
+
+        iter::from_fn(move || {
+            while let Some((node, origin, level)) = stack.pop() {
+                let Some(children) = node.children else {
+                    return Some((Self::from_inner(origin), node.voxel));
+                };
+
+                stack.extend(children.into_iter().enumerate().filter_map(|(i, child)| {
+                    Some((child?, OctreeNode::<C, W>::child_origin(origin, level, i), level - 1))
+                }));
+            }
+
+            None
+        })
+    }
+
+    // 根の子ノードごとに並列に走査する
+    #[cfg(feature = "rayon")]
+    fn par_iter(&self) -> impl ParallelIterator<Item=(Point3D<P>, Voxel<C, W>)> {
+        let (origin, level) = (self.root_origin(), self.root_level);
+
+        let subtrees = self.root.iter()
+            .filter_map(|root| root.children.as_ref())
+            .flat_map(|children| children.iter().enumerate())
+            .filter_map(|(i, child)| Some((child.as_ref()?, OctreeNode::<C, W>::child_origin(origin, level, i))))
+            .collect::<Vec<_>>();
+
+        subtrees.into_par_iter().flat_map_iter(move |(node, origin)| Self::leaves(node, origin, level - 1))
     }
 
     fn insert_one(&mut self, point: Point3D<P>, voxel: Voxel<C, W>) {
-        self.field.entry(point).and_modify(|current_voxel| {
-            Self::add_color_with_weight_check(current_voxel, voxel);
-        }).or_insert(voxel);
+        // 木の深さの上限を超える座標値は無視する
+        let Some(inner) = Self::to_inner_checked(&point) else { return };
+        self.grow_to_include(inner);
 
-        self.bounds = None;
+        let (origin, level) = (self.root_origin(), self.root_level);
+        Self::insert_into(self.root.as_mut().unwrap(), origin, level, inner, voxel);
+
+        self.bounds = Some(match self.bounds {
+            Some(bounds) => Self::calc_bounds_from_2(bounds, (point, point)),
+            None => (point, point),
+        });
     }
 
     fn has(&self, point: &Point3D<P>) -> bool {
-        self.field.contains_key(point)
+        self.voxel_at(point).is_some_and(|voxel| voxel.weight.ne(&W::zero()))
+    }
+
+    fn get(&self, point: &Point3D<P>) -> Option<Voxel<C, W>> {
+        self.voxel_at(point).filter(|voxel| voxel.weight.ne(&W::zero()))
+    }
+
+    fn remove(&mut self, point: &Point3D<P>) -> Option<Voxel<C, W>> {
+        let inner = Self::to_inner(point);
+
+        if !self.contains(inner) {
+            return None;
+        }
+
+        let (origin, level) = (self.root_origin(), self.root_level);
+        let voxel = Self::remove_from(self.root.as_mut()?, origin, level, inner)?;
+
+        self.prune_root();
+        self.bounds = None;
+
+        Some(voxel)
+    }
+
+    fn retain<F: FnMut(&Point3D<P>, &Voxel<C, W>) -> bool>(&mut self, mut f: F) {
+        let (origin, level) = (self.root_origin(), self.root_level);
+
+        if let Some(root) = self.root.as_mut() {
+            Self::retain_in(root, origin, level, &mut f);
+        }
+
+        self.prune_root();
+        self.bounds = None;
     }
 
+    // 葉のボクセルに関数を適用し、内部ノードの集約値を再計算する
     fn batch(&mut self, f: fn(&mut Voxel<C, W>)) {
-        self.field.iter_mut().for_each(|mut entry| {
-            let (_point, voxel) = entry.pair_mut();
-            f(voxel);
-        });
+        fn apply<C, W>(node: &mut OctreeNode<C, W>, f: fn(&mut Voxel<C, W>))
+        where
+            C: UInt + AsPrimitive<f64>,
+            W: UInt + AsPrimitive<f64>,
+        {
+            let Some(children) = node.children.as_mut() else {
+                f(&mut node.voxel);
+                node.sum = OctreeSum::of(node.voxel);
+                return;
+            };
+
+            node.sum = OctreeSum::default();
+
+            children.iter_mut().flatten().for_each(|child| {
+                apply(child, f);
+                node.sum.add(child.sum);
+            });
+        }
+
+        if let Some(root) = self.root.as_mut() {
+            apply(root, f);
+        }
     }
 }
 
@@ -1025,20 +2888,23 @@ where
         self.offset = offset;
     }
 
-    fn to_vec(&self) -> Vec<(Point3D<P>, Voxel<C, W>)> {
-        self.field.clone().into_iter().map(|entry| {
-            let (point, voxel) = entry;
-            (Point3D::new([point[0], point[1], voxel.0]), voxel.1)
-        }).collect()
+    fn iter(&self) -> impl Iterator<Item=(Point3D<P>, Voxel<C, W>)> {
+        self.field.iter().map(|entry| {
+            let (point, (z, voxel)) = entry.pair();
+            (Point3D::new([point[0], point[1], *z]), *voxel)
+        })
     }
 
-    fn into_vec(self) -> Vec<(Point3D<P>, Voxel<C, W>)> {
-        self.field.into_iter().map(|entry| {
-            let (point, (z, voxel)) = entry;
-            let x = point[0];
-            let y = point[1];
-            (Point3D::new([x, y, z]), voxel)
-        }).collect()
+    fn into_iter(self) -> impl Iterator<Item=(Point3D<P>, Voxel<C, W>)> {
+        self.field.into_iter().map(|(point, (z, voxel))| {
+            (Point3D::new([point[0], point[1], z]), voxel)
+        })
+    }
+
+    // ハッシャーが`Sync`であることを要求しないように、シャードの逐次的な走査を分配する
+    #[cfg(feature = "rayon")]
+    fn par_iter(&self) -> impl ParallelIterator<Item=(Point3D<P>, Voxel<C, W>)> {
+        self.iter().par_bridge()
     }
 
     fn insert_one(&mut self, point: Point3D<P>, voxel: Voxel<C, W>) {
@@ -1063,6 +2929,33 @@ where
         false
     }
 
+    fn get(&self, point: &Point3D<P>) -> Option<Voxel<C, W>> {
+        let r = self.field.get(&point.fit())?;
+        let (height, voxel) = *r;
+
+        (height == point[2]).then_some(voxel)
+    }
+
+    fn remove(&mut self, point: &Point3D<P>) -> Option<Voxel<C, W>> {
+        let (_point, (_height, voxel)) = self.field.remove_if(&point.fit(), |_, (height, _)| *height == point[2])?;
+        self.bounds = None;
+
+        Some(voxel)
+    }
+
+    // 平面座標ごとに1つの高さしか持てないため、高さが異なる場合も置き換える
+    fn replace(&mut self, point: Point3D<P>, voxel: Voxel<C, W>) -> Option<Voxel<C, W>> {
+        let previous = self.field.insert(point.fit(), (point[2], voxel));
+        self.bounds = None;
+
+        previous.and_then(|(height, voxel)| (height == point[2]).then_some(voxel))
+    }
+
+    fn retain<F: FnMut(&Point3D<P>, &Voxel<C, W>) -> bool>(&mut self, mut f: F) {
+        self.field.retain(|point, (height, voxel)| f(&Point3D::new([point[0], point[1], *height]), voxel));
+        self.bounds = None;
+    }
+
     fn batch(&mut self, f: fn(&mut Voxel<C, W>)) {
         self.field.iter_mut().for_each(|mut entry| {
             let (_point, (_height, voxel)) = entry.pair_mut();
@@ -1070,3 +2963,540 @@ where
         });
     }
 }
+
+#[cfg(test)]
+mod test {
+    use fxhash::FxBuildHasher;
+
+    use crate::collection::{BrickVoxelCollection, Connectivity, HMap2DVoxelCollection, HMap3DVoxelCollection, OctreeVoxelCollection, PointCloud, Vec2VoxelCollection, Vec3VoxelCollection, VoxelCollection, VoxelMergeRule};
+    use crate::element::{Color, Point3D, Voxel};
+
+    #[test]
+    fn test_vec3_grow_on_insert() {
+        let mut vc = Vec3VoxelCollection::<i32, u8, u8>::builder()
+            .points(vec![(Point3D::new([0, 0, 0]), Color::new([10, 10, 10]))])
+            .build();
+
+        let points = [[-3, 5, 2], [10, 0, 0], [4, -7, 1], [-3, 5, 2]];
+        points.iter().for_each(|&point| {
+            vc.insert_one(Point3D::new(point), Voxel::new(Color::new([20, 20, 20])));
+        });
+
+        assert_eq!(vc.get_bounds(), (Point3D::new([-3, -7, 0]), Point3D::new([10, 5, 2])));
+        assert!(points.iter().all(|&point| vc.has(&Point3D::new(point))));
+        assert!(!vc.has(&Point3D::new([1, 1, 1])));
+
+        let voxels = vc.to_vec();
+        assert_eq!(voxels.len(), 4);
+
+        let (_, voxel) = voxels.iter().find(|(point, _)| *point == Point3D::new([-3, 5, 2])).unwrap();
+        assert_eq!(voxel.weight, 2);
+        assert_eq!(voxel.color, Color::new([40, 40, 40]));
+    }
+
+    #[test]
+    fn test_vec3_grow_from_empty() {
+        let mut vc = Vec3VoxelCollection::<u32, u8, u8>::default();
+
+        (0..20_u32).rev().for_each(|i| {
+            vc.insert_one(Point3D::new([i, 3, i * 2]), Voxel::new(Color::new([1, 2, 3])));
+        });
+
+        assert_eq!(vc.get_bounds(), (Point3D::new([0, 3, 0]), Point3D::new([19, 3, 38])));
+        assert_eq!(vc.to_vec().len(), 20);
+        assert!((0..20_u32).all(|i| vc.has(&Point3D::new([i, 3, i * 2]))));
+    }
+
+    #[test]
+    fn test_brick_collection() {
+        let points = [[0, 0, 0], [7, 7, 7], [8, 0, 0], [-1, -1, -1], [-8, 3, 100], [7, 7, 7]];
+
+        let mut vc = BrickVoxelCollection::<i32, u8, u8, FxBuildHasher>::builder()
+            .points(points.iter().map(|&point| (Point3D::new(point), Color::new([10, 20, 30]))).collect())
+            .build();
+
+        // (0,0,0), (1,0,0), (-1,-1,-1), (-1,0,12)の4つのブリックのみ確保される
+        assert_eq!(vc.field.len(), 4);
+        assert!(points.iter().all(|&point| vc.has(&Point3D::new(point))));
+        assert!(!vc.has(&Point3D::new([1, 0, 0])));
+        assert!(!vc.has(&Point3D::new([-9, 3, 100])));
+
+        assert_eq!(vc.get_bounds(), (Point3D::new([-8, -1, -1]), Point3D::new([8, 7, 100])));
+
+        vc.insert_one(Point3D::new([20, -30, 0]), Voxel::new(Color::new([1, 1, 1])));
+        assert_eq!(vc.get_bounds(), (Point3D::new([-8, -30, -1]), Point3D::new([20, 7, 100])));
+
+        let mut voxels = vc.into_vec();
+        voxels.sort_by_key(|(point, _)| point.data);
+
+        assert_eq!(voxels.len(), 6);
+        let (_, voxel) = voxels.iter().find(|(point, _)| *point == Point3D::new([7, 7, 7])).unwrap();
+        assert_eq!(voxel.weight, 2);
+    }
+
+    #[test]
+    fn test_octree_collection() {
+        let points = [[0, 0, 0], [1, 0, 0], [3, 3, 3], [-5, 2, 9], [1, 0, 0]];
+
+        let mut vc = OctreeVoxelCollection::<i32, u8, u8>::builder()
+            .points(points.iter().map(|&point| (Point3D::new(point), Color::new([10, 20, 30]))).collect())
+            .build();
+
+        assert!(points.iter().all(|&point| vc.has(&Point3D::new(point))));
+        assert!(!vc.has(&Point3D::new([2, 0, 0])));
+        assert!(!vc.has(&Point3D::new([100, 0, 0])));
+        assert_eq!(vc.get_bounds(), (Point3D::new([-5, 0, 0]), Point3D::new([3, 3, 9])));
+
+        let voxels = vc.to_vec();
+        assert_eq!(voxels.len(), 4);
+        let (_, voxel) = voxels.iter().find(|(point, _)| *point == Point3D::new([1, 0, 0])).unwrap();
+        assert_eq!(voxel.weight, 2);
+
+        assert_eq!(vc.count_in_box(&Point3D::new([0, 0, 0]), &Point3D::new([3, 3, 3])), 3);
+        assert_eq!(vc.count_in_box(&Point3D::new([-8, -8, -8]), &Point3D::new([16, 16, 16])), 4);
+        assert_eq!(vc.count_in_box(&Point3D::new([4, 4, 4]), &Point3D::new([8, 8, 8])), 0);
+    }
+
+    #[test]
+    fn test_octree_lod() {
+        let points = [[0, 0, 0], [1, 1, 1], [2, 0, 0], [-1, 0, 0]];
+
+        let vc = OctreeVoxelCollection::<i32, u8, u8>::builder()
+            .points(points.iter().map(|&point| (Point3D::new(point), Color::new([10, 20, 30]))).collect())
+            .resolution(0.5)
+            .build();
+
+        let mut voxels = vc.to_vec_at_level(1);
+        voxels.sort_by_key(|(point, _)| point.data);
+
+        assert_eq!(voxels.len(), 3);
+        assert_eq!(voxels[0].0, Point3D::new([-1, 0, 0]));
+        assert_eq!(voxels[1].0, Point3D::new([0, 0, 0]));
+        assert_eq!(voxels[1].1.weight, 2);
+        assert_eq!(voxels[1].1.color, Color::new([20, 40, 60]));
+        assert_eq!(voxels[2].0, Point3D::new([1, 0, 0]));
+
+        let lod: HMap3DVoxelCollection<i32, u8, u8, FxBuildHasher> = vc.to_lod(1);
+        assert_eq!(lod.get_resolution(), 1.);
+        assert_eq!(lod.to_vec().len(), 3);
+
+        // 根より粗いレベルでは、原点をまたぐボクセルは負側と正側に分かれる
+        let mut all = vc.to_vec_at_level(vc.depth() + 1);
+        all.sort_by_key(|(point, _)| point.data);
+        assert_eq!(all.len(), 2);
+        assert_eq!((all[0].1.weight, all[1].1.weight), (1, 3));
+
+        // 座標値のビット数を超えるレベルでも同じ結果になる
+        let mut coarsest = vc.to_vec_at_level(u32::MAX);
+        coarsest.sort_by_key(|(point, _)| point.data);
+        assert_eq!(coarsest, all);
+
+        let lod: HMap3DVoxelCollection<i32, u8, u8, FxBuildHasher> = vc.to_lod(64);
+        assert_eq!(lod.get_resolution(), 0.5 * 2_f64.powi(64));
+        assert_eq!(lod.to_vec().len(), 2);
+    }
+
+    #[test]
+    fn test_octree_bright_voxels() {
+        // 集約した色の和は色の型を超えるため、平均色を保ったまま重みを飽和させる
+        let points = (0..64).map(|i| (Point3D::new([i % 4, i / 4 % 4, i / 16]), Color::new([200, 100, 0]))).collect();
+
+        let mut vc = OctreeVoxelCollection::<i32, u8, u8>::builder().points(points).build();
+        vc.batch(|voxel| voxel.color = Color::new([250, 125, 0]));
+
+        let coarse = vc.to_vec_at_level(2);
+        assert_eq!(coarse.len(), 1);
+        assert_eq!(coarse[0].1, Voxel { color: Color::new([250, 125, 0]), weight: 1 });
+
+        let lod: HMap3DVoxelCollection<i32, u8, u8, FxBuildHasher> = vc.to_lod(1);
+        assert_eq!(lod.to_points().len(), 8);
+        assert!(lod.to_points().iter().all(|(_, color)| *color == Color::new([250, 125, 0])));
+
+        assert!(vc.remove(&Point3D::new([0, 0, 0])).is_some());
+        assert_eq!(vc.to_vec_at_level(2)[0].1.color, Color::new([250, 125, 0]));
+        assert_eq!(vc.count_in_box(&Point3D::new([0, 0, 0]), &Point3D::new([3, 3, 3])), 63);
+    }
+
+    #[test]
+    fn test_octree_depth_limit() {
+        let half = 1_i64 << (OctreeVoxelCollection::<i64, u8, u8>::MAX_DEPTH - 1);
+
+        let vc = OctreeVoxelCollection::<i64, u8, u8>::builder()
+            .points(vec![
+                (Point3D::new([half - 1, 0, -half]), Color::new([10, 20, 30])),
+                (Point3D::new([half, 0, 0]), Color::new([10, 20, 30])),
+                (Point3D::new([i64::MIN, 0, 0]), Color::new([10, 20, 30])),
+            ])
+            .build();
+
+        // 深さの上限で表現できない座標値は無視される
+        assert_eq!(vc.depth(), OctreeVoxelCollection::<i64, u8, u8>::MAX_DEPTH);
+        assert_eq!(vc.to_vec().len(), 1);
+        assert!(vc.has(&Point3D::new([half - 1, 0, -half])));
+
+        // `i64`に変換すると負になる値も無視される
+        let vc = OctreeVoxelCollection::<u64, u8, u8>::builder()
+            .points(vec![(Point3D::new([u64::MAX, 0, 0]), Color::new([10, 20, 30]))])
+            .build();
+        assert!(vc.to_vec().is_empty());
+    }
+
+    #[test]
+    fn test_octree_raycast() {
+        let points = [[5, 0, 0], [9, 0, 0], [5, 3, 0]];
+
+        let vc = OctreeVoxelCollection::<i32, u8, u8>::builder()
+            .points(points.iter().map(|&point| (Point3D::new(point), Color::new([10, 20, 30]))).collect())
+            .build();
+
+        let (point, distance) = vc.raycast([0.5, 0.5, 0.5], [1., 0., 0.], 100.).unwrap();
+        assert_eq!(point, Point3D::new([5, 0, 0]));
+        assert_eq!(distance, 4.5);
+
+        let (point, _) = vc.raycast([20.5, 0.5, 0.5], [-1., 0., 0.], 100.).unwrap();
+        assert_eq!(point, Point3D::new([9, 0, 0]));
+
+        assert!(vc.raycast([0.5, 0.5, 0.5], [1., 0., 0.], 3.).is_none());
+        assert!(vc.raycast([0.5, 1.5, 0.5], [1., 0., 0.], 100.).is_none());
+    }
+
+    // 平面座標が重ならない点を使用して、すべての`VoxelCollection`で同じ結果になることを確認する
+    fn check_edit<VC: VoxelCollection<u32, u8, u8>>() {
+        let mut vc = VC::builder()
+            .points(vec![
+                (Point3D::new([1, 1, 1]), Color::new([10, 10, 10])),
+                (Point3D::new([2, 3, 4]), Color::new([20, 20, 20])),
+                (Point3D::new([5, 0, 2]), Color::new([30, 30, 30])),
+                (Point3D::new([4, 4, 0]), Color::new([40, 40, 40])),
+            ])
+            .build();
+
+        assert_eq!(vc.get(&Point3D::new([2, 3, 4])), Some(Voxel::new(Color::new([20, 20, 20]))));
+        assert_eq!(vc.get(&Point3D::new([2, 3, 3])), None);
+
+        assert_eq!(vc.remove(&Point3D::new([1, 1, 1])), Some(Voxel::new(Color::new([10, 10, 10]))));
+        assert_eq!(vc.remove(&Point3D::new([1, 1, 1])), None);
+        assert!(!vc.has(&Point3D::new([1, 1, 1])));
+
+        let voxel = Voxel { color: Color::new([5, 5, 5]), weight: 3 };
+        assert_eq!(vc.replace(Point3D::new([2, 3, 4]), voxel), Some(Voxel::new(Color::new([20, 20, 20]))));
+        assert_eq!(vc.get(&Point3D::new([2, 3, 4])), Some(voxel));
+
+        vc.retain(|point, _| point[2] != 0);
+        assert!(!vc.has(&Point3D::new([4, 4, 0])));
+
+        let mut voxels = vc.to_vec();
+        voxels.sort_by_key(|(point, _)| point.data);
+        assert_eq!(voxels, vec![(Point3D::new([2, 3, 4]), voxel), (Point3D::new([5, 0, 2]), Voxel::new(Color::new([30, 30, 30])))]);
+        assert_eq!(vc.get_bounds(), (Point3D::new([2, 0, 2]), Point3D::new([5, 3, 4])));
+    }
+
+    #[test]
+    fn test_edit() {
+        check_edit::<PointCloud<u32, u8, u8>>();
+        check_edit::<Vec3VoxelCollection<u32, u8, u8>>();
+        check_edit::<HMap3DVoxelCollection<u32, u8, u8, FxBuildHasher>>();
+        check_edit::<HMap2DVoxelCollection<u32, u8, u8, FxBuildHasher>>();
+        check_edit::<BrickVoxelCollection<u32, u8, u8, FxBuildHasher>>();
+        check_edit::<OctreeVoxelCollection<u32, u8, u8>>();
+    }
+
+    #[test]
+    fn test_vec2_edit() {
+        let mut vc = Vec2VoxelCollection::<u32, u8, u8>::builder()
+            .points(vec![
+                (Point3D::new([1, 1, 1]), Color::new([10, 10, 10])),
+                (Point3D::new([2, 3, 4]), Color::new([20, 20, 20])),
+            ])
+            .build();
+
+        assert_eq!(vc.get(&Point3D::new([2, 3, 4])), Some(Voxel::new(Color::new([20, 20, 20]))));
+        assert_eq!(vc.get(&Point3D::new([2, 3, 5])), None);
+        assert_eq!(vc.get(&Point3D::new([0, 0, 1])), None);
+
+        assert!(vc.remove(&Point3D::new([1, 1, 1])).is_some());
+        assert!(!vc.has(&Point3D::new([1, 1, 1])));
+
+        assert_eq!(vc.replace(Point3D::new([2, 3, 7]), Voxel::new(Color::new([1, 1, 1]))), None);
+        assert!(vc.has(&Point3D::new([2, 3, 7])));
+        assert!(!vc.has(&Point3D::new([2, 3, 4])));
+
+        vc.retain(|_, voxel| voxel.color != Color::new([1, 1, 1]));
+        assert!(vc.to_vec().is_empty());
+    }
+
+    #[test]
+    fn test_octree_remove() {
+        let points = [[-3, 0, 0], [0, 0, 0], [1, 0, 0], [9, 9, 9]];
+
+        let mut vc = OctreeVoxelCollection::<i32, u8, u8>::builder()
+            .points(points.iter().map(|&point| (Point3D::new(point), Color::new([10, 20, 30]))).collect())
+            .build();
+
+        assert!(vc.remove(&Point3D::new([9, 9, 9])).is_some());
+        assert_eq!(vc.count_in_box(&Point3D::new([-16, -16, -16]), &Point3D::new([16, 16, 16])), 3);
+        assert_eq!(vc.to_vec_at_level(vc.depth()).iter().map(|(_, voxel)| voxel.weight).sum::<u8>(), 3);
+        assert!(vc.raycast([20.5, 0.5, 0.5], [-1., 0., 0.], 100.).is_some_and(|(point, _)| point == Point3D::new([1, 0, 0])));
+
+        vc.retain(|point, _| point[0] < 0);
+        assert_eq!(vc.to_vec().len(), 1);
+
+        assert!(vc.remove(&Point3D::new([-3, 0, 0])).is_some());
+        assert!(vc.to_vec().is_empty());
+        assert!(vc.remove(&Point3D::new([-3, 0, 0])).is_none());
+    }
+
+    // すべての`VoxelCollection`で、イテレータと`to_vec`の結果が一致することを確認する
+    fn check_iter<VC: VoxelCollection<u32, u8, u8> + Clone>() {
+        let vc = VC::builder()
+            .points(vec![
+                (Point3D::new([1, 1, 1]), Color::new([10, 10, 10])),
+                (Point3D::new([2, 3, 4]), Color::new([20, 20, 20])),
+                (Point3D::new([5, 0, 2]), Color::new([30, 30, 30])),
+            ])
+            .offset(Point3D::new([1, 0, 0]))
+            .build();
+
+        let sorted = |mut voxels: Vec<(Point3D<u32>, Voxel<u8, u8>)>| {
+            voxels.sort_by_key(|(point, _)| point.data);
+            voxels
+        };
+
+        let expected = sorted(vc.to_vec());
+        assert_eq!(expected.len(), 3);
+
+        assert_eq!(sorted(vc.iter().collect()), expected);
+        assert_eq!(sorted(vc.clone().into_iter().collect()), expected);
+        assert_eq!(sorted(vc.iter_with_offset().collect()), sorted(vc.to_vec_with_offset()));
+        assert_eq!(vc.iter_points().count(), 3);
+
+        #[cfg(feature = "rayon")]
+        {
+            use rayon::prelude::*;
+            assert_eq!(sorted(vc.par_iter().collect()), expected);
+        }
+    }
+
+    #[test]
+    fn test_iter() {
+        check_iter::<PointCloud<u32, u8, u8>>();
+        check_iter::<Vec3VoxelCollection<u32, u8, u8>>();
+        check_iter::<Vec2VoxelCollection<u32, u8, u8>>();
+        check_iter::<HMap3DVoxelCollection<u32, u8, u8, FxBuildHasher>>();
+        check_iter::<HMap2DVoxelCollection<u32, u8, u8, FxBuildHasher>>();
+        check_iter::<BrickVoxelCollection<u32, u8, u8, FxBuildHasher>>();
+        check_iter::<OctreeVoxelCollection<u32, u8, u8>>();
+    }
+
+    #[test]
+    fn test_set_operations() {
+        let build = |xs: &[u32], color: u8| {
+            HMap3DVoxelCollection::<u32, u8, u8, FxBuildHasher>::builder()
+                .points(xs.iter().map(|&x| (Point3D::new([x, 0, 0]), Color::new([color; 3]))).collect())
+                .build()
+        };
+
+        let xs = |vc: HMap3DVoxelCollection<u32, u8, u8, FxBuildHasher>| {
+            let mut voxels = vc.into_vec();
+            voxels.sort_by_key(|(point, _)| point.data);
+            voxels.into_iter().map(|(point, voxel)| (point[0], voxel.color[0], voxel.weight)).collect::<Vec<_>>()
+        };
+
+        let a = build(&[0, 1, 2], 10);
+        let b = build(&[1, 2, 3], 20);
+
+        assert_eq!(xs(a.union(&b, VoxelMergeRule::Sum).unwrap()), vec![(0, 10, 1), (1, 30, 2), (2, 30, 2), (3, 20, 1)]);
+        assert_eq!(xs(a.union(&b, VoxelMergeRule::Right).unwrap()), vec![(0, 10, 1), (1, 20, 1), (2, 20, 1), (3, 20, 1)]);
+        assert_eq!(xs(a.intersection(&b, VoxelMergeRule::Left).unwrap()), vec![(1, 10, 1), (2, 10, 1)]);
+        assert_eq!(xs(a.difference(&b).unwrap()), vec![(0, 10, 1)]);
+        assert_eq!(xs(a.symmetric_difference(&b).unwrap()), vec![(0, 10, 1), (3, 20, 1)]);
+
+        // オフセットを適用した座標値で比較する
+        let mut shifted = build(&[2, 3, 4], 20);
+        shifted.set_offset(Point3D::new([1, 0, 0]));
+        assert_eq!(xs(a.intersection(&shifted, VoxelMergeRule::MaxWeight).unwrap()), vec![(1, 10, 1), (2, 10, 1)]);
+
+        let coarse = HMap3DVoxelCollection::<u32, u8, u8, FxBuildHasher>::builder().resolution(2.).build();
+        assert!(a.union(&coarse, VoxelMergeRule::Sum).is_err());
+
+        // 同じ座標値を複数含む`PointCloud`は、先に1つのボクセルにまとめてから比較する
+        let cloud = PointCloud::<u32, u8, u8>::builder()
+            .points([1, 1, 4].iter().map(|&x| (Point3D::new([x, 0, 0]), Color::new([10; 3]))).collect())
+            .build();
+
+        let mut voxels = cloud.union(&b, VoxelMergeRule::Sum).unwrap().into_vec();
+        voxels.sort_by_key(|(point, _)| point.data);
+        let voxels = voxels.into_iter().map(|(point, voxel)| (point[0], voxel.color[0], voxel.weight)).collect::<Vec<_>>();
+        assert_eq!(voxels, vec![(1, 40, 3), (2, 20, 1), (3, 20, 1), (4, 10, 1)]);
+
+        assert_eq!(cloud.intersection(&b, VoxelMergeRule::Left).unwrap().into_vec().len(), 1);
+        assert_eq!(cloud.difference(&b).unwrap().into_vec().len(), 1);
+        assert_eq!(cloud.symmetric_difference(&b).unwrap().into_vec().len(), 3);
+    }
+
+    #[test]
+    fn test_connectivity() {
+        assert_eq!(Connectivity::Six.offsets().count(), 6);
+        assert_eq!(Connectivity::Eighteen.offsets().count(), 18);
+        assert_eq!(Connectivity::TwentySix.offsets().count(), 26);
+
+        // 0未満の座標値は表現できないため含まれない
+        assert_eq!(Connectivity::Six.neighbors(Point3D::<u32>::new([0, 5, 5])).count(), 5);
+        assert_eq!(Connectivity::TwentySix.neighbors(Point3D::<u32>::new([0, 0, 0])).count(), 7);
+    }
+
+    #[test]
+    fn test_morphology() {
+        let cube = (0..27).map(|i| Point3D::new([i / 9, i / 3 % 3, i % 3])).collect::<Vec<_>>();
+
+        // 中心に穴の空いた立方体
+        let vc = HMap3DVoxelCollection::<i32, u8, u8, FxBuildHasher>::builder()
+            .points(cube.iter().filter(|point| **point != Point3D::new([1, 1, 1])).map(|&point| {
+                let color = if point[0] == 0 { Color::new([0, 0, 0]) } else { Color::new([120, 120, 120]) };
+                (point, color)
+            }).collect())
+            .build();
+
+        let dilated = vc.dilate(Connectivity::Six);
+        assert_eq!(dilated.to_vec().len(), 27 + 6 * 9);
+
+        let closed = vc.close(Connectivity::Six);
+        assert_eq!(closed.to_vec().len(), 27);
+        // 6つの隣接ボクセルのうち1つの色が(0, 0, 0)
+        assert_eq!(closed.get(&Point3D::new([1, 1, 1])), Some(Voxel::new(Color::new([100, 100, 100]))));
+        assert_eq!(closed.get(&Point3D::new([0, 0, 0])), Some(Voxel::new(Color::new([0, 0, 0]))));
+
+        assert!(vc.erode(Connectivity::Six).to_vec().is_empty());
+
+        let mut points = cube.iter().map(|&point| (point, Color::new([10, 20, 30]))).collect::<Vec<_>>();
+        points.push((Point3D::new([10, 10, 10]), Color::new([10, 20, 30])));
+
+        let noisy = Vec3VoxelCollection::<i32, u8, u8>::builder().points(points).build();
+        let opened = noisy.open(Connectivity::Six);
+
+        assert!(!opened.has(&Point3D::new([10, 10, 10])));
+        assert!(opened.has(&Point3D::new([1, 1, 1])));
+        assert_eq!(opened.to_vec().len(), 7);
+        assert!(opened.iter().all(|(_, voxel)| voxel == Voxel::new(Color::new([10, 20, 30]))));
+    }
+
+    #[test]
+    fn test_fill_solid() {
+        let cube = (0..125).map(|i| Point3D::new([i / 25 - 2, i / 5 % 5 - 2, i % 5 - 2])).collect::<Vec<_>>();
+
+        let solid = HMap3DVoxelCollection::<i32, u8, u8, FxBuildHasher>::builder()
+            .points(cube.iter().map(|&point| (point, Color::new([10, 20, 30]))).collect())
+            .build();
+
+        let shell = solid.hollow(Connectivity::Six);
+        assert_eq!(shell.to_vec().len(), 125 - 27);
+        assert!(!shell.has(&Point3D::new([0, 0, 0])));
+        assert_eq!(solid.hollow(Connectivity::TwentySix).to_vec().len(), 125 - 27);
+
+        let filled = shell.fill_solid().unwrap();
+        assert_eq!(filled.to_vec().len(), 125);
+        assert_eq!(filled.get(&Point3D::new([0, 0, 0])), Some(Voxel::new(Color::new([10, 20, 30]))));
+
+        // 表面に穴が空いている場合は、内部に外から到達できるため埋められない
+        let mut open_shell = shell.clone();
+        open_shell.remove(&Point3D::new([0, 0, 2]));
+        assert_eq!(open_shell.fill_solid().unwrap().to_vec().len(), 125 - 28);
+
+        // 大きく離れた2つの殻は、それぞれの外接直方体の範囲のみを探索する
+        let mut shells = shell.clone();
+        shell.iter().for_each(|(point, voxel)| shells.insert_one(point + Point3D::from(1_000_000), voxel));
+        assert_eq!(shells.fill_solid().unwrap().to_vec().len(), 125 * 2);
+
+        // 1つの連結成分の外接直方体が大きすぎる場合はエラーになる
+        let line = HMap3DVoxelCollection::<i32, u8, u8, FxBuildHasher>::builder()
+            .points((0..3000).map(|i| {
+                let mut point = [0; 3];
+                point[i as usize / 1000] = i % 1000;
+                (Point3D::new(point), Color::new([10, 20, 30]))
+            }).collect())
+            .build();
+        assert!(line.fill_solid().is_err());
+    }
+
+    #[test]
+    fn test_resample() {
+        let mut vc = HMap3DVoxelCollection::<i32, u8, u8, FxBuildHasher>::builder()
+            .points(vec![
+                (Point3D::new([0, 0, 0]), Color::new([10, 10, 10])),
+                (Point3D::new([0, 0, 0]), Color::new([10, 10, 10])),
+                (Point3D::new([1, 1, 1]), Color::new([40, 40, 40])),
+                (Point3D::new([-1, 0, 0]), Color::new([50, 50, 50])),
+            ])
+            .build();
+        vc.set_offset(Point3D::new([4, 0, 0]));
+
+        let coarse = vc.resample(2.).unwrap();
+        assert_eq!(coarse.get_resolution(), 2.);
+        assert_eq!(coarse.get_offset(), Point3D::new([2, 0, 0]));
+
+        let mut points = coarse.to_points_with_offset();
+        points.sort_by_key(|(point, _)| point[0]);
+        // 重みが2のボクセルと重みが1のボクセルの加重平均
+        assert_eq!(points, vec![
+            (Point3D::new([-3, 0, 0]), Color::new([50, 50, 50])),
+            (Point3D::new([-2, 0, 0]), Color::new([20, 20, 20])),
+        ]);
+
+        let fine = coarse.resample_to(0.5).unwrap();
+        assert_eq!(fine.get_resolution(), 0.5);
+        assert_eq!(fine.get_offset(), Point3D::new([8, 0, 0]));
+        assert_eq!(fine.to_vec().len(), 2 * 64);
+        assert!(fine.has(&(Point3D::new([-12, 3, 3]) + fine.get_offset())));
+        assert!(!fine.has(&(Point3D::new([-12, 4, 3]) + fine.get_offset())));
+
+        let mut restored = fine.resample_to(2.).unwrap().to_points_with_offset();
+        restored.sort_by_key(|(point, _)| point[0]);
+        assert_eq!(restored, points);
+
+        assert!(vc.resample(0.).is_err());
+        assert!(vc.resample_to(f64::NAN).is_err());
+
+        // 色の和が色の型を超える場合は、平均色を保ったまま重みを飽和させる
+        let bright = HMap3DVoxelCollection::<i32, u8, u8, FxBuildHasher>::builder()
+            .points(vec![
+                (Point3D::new([0, 0, 0]), Color::new([200, 200, 200])),
+                (Point3D::new([1, 0, 0]), Color::new([200, 100, 0])),
+            ])
+            .build();
+
+        let coarse = bright.resample(2.).unwrap();
+        assert_eq!(coarse.to_vec(), vec![(Point3D::new([0, 0, 0]), Voxel { color: Color::new([200, 150, 100]), weight: 1 })]);
+    }
+
+    #[test]
+    fn test_label_components() {
+        let mut points = (0..8).map(|i| Point3D::new([i / 4, i / 2 % 2, i % 2])).collect::<Vec<_>>();
+        // 頂点のみで接する塊と、孤立したボクセル
+        points.extend([[2, 2, 2], [3, 2, 2], [10, 0, 0]].map(Point3D::new));
+
+        let mut vc = BrickVoxelCollection::<i32, u8, u8, FxBuildHasher>::builder()
+            .points(points.iter().map(|&point| (point, Color::new([1, 2, 3]))).collect())
+            .build();
+
+        let labels = vc.label_components(Connectivity::Six);
+        assert_eq!(labels.len(), 3);
+
+        let mut counts = labels.components().iter().map(|component| component.count).collect::<Vec<_>>();
+        counts.sort();
+        assert_eq!(counts, vec![1, 2, 8]);
+
+        let cube = labels.label(&Point3D::new([0, 0, 0])).unwrap();
+        assert_eq!(labels.label(&Point3D::new([1, 1, 1])), Some(cube));
+        assert_eq!(labels.components()[cube].bounds, (Point3D::new([0, 0, 0]), Point3D::new([1, 1, 1])));
+        assert_eq!(labels.label(&Point3D::new([5, 5, 5])), None);
+
+        assert_eq!(vc.label_components(Connectivity::TwentySix).len(), 2);
+
+        vc.remove_small_components(Connectivity::Six, 2);
+        assert_eq!(vc.to_vec().len(), 10);
+        assert!(!vc.has(&Point3D::new([10, 0, 0])));
+
+        vc.remove_small_components(Connectivity::TwentySix, 9);
+        assert_eq!(vc.to_vec().len(), 10);
+    }
+}
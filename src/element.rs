@@ -9,7 +9,7 @@ use vec_x::VecX;
 
 /// 扱うことのできる数値型を表します。
 /// Rustの浮動小数点数は`Eq`や`Hash`を実装できないため、小数表現には[`OrderedFloat`]を使用します。
-pub trait Number: Num + Copy + Send + NumAssignOps + Default + PartialEq + Eq + PartialOrd + Ord + Hash + Bounded + Debug {}
+pub trait Number: Num + Copy + Send + Sync + NumAssignOps + Default + PartialEq + Eq + PartialOrd + Ord + Hash + Bounded + Debug {}
 
 impl Number for u8 {}
 
@@ -4,6 +4,7 @@ use std::default::Default;
 use std::mem;
 
 use anyhow::anyhow;
+use fxhash::FxBuildHasher;
 use gltf::binary::Header;
 use gltf::buffer::Target::{ArrayBuffer, ElementArrayBuffer};
 /// [`gltf::Glb`]に[`VoxelMesh`]からインスタンスを生成するメソッドを追加しています。
@@ -20,9 +21,10 @@ use gltf::json::validation::USize64;
 use gltf::mesh::Mode;
 use gltf::Semantic;
 use gltf::texture::{MagFilter, MinFilter};
+use indexmap::IndexSet;
 use num::cast::AsPrimitive;
 
-use crate::element::{Int, Point3D, UInt};
+use crate::element::{Color, Int, Point3D, UInt};
 use crate::glb::private::GlbGenPrivateMethod;
 use crate::mesh::VoxelMesh;
 
@@ -34,6 +36,10 @@ struct Vertex([f32; 3]);
 #[repr(C)]
 struct UV([f32; 2]);
 
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(C)]
+struct VertexColor([f32; 4]);
+
 /// テクスチャのMIMEタイプを表す列挙型です。
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Mime {
@@ -53,17 +59,46 @@ pub struct TextureInfo {
 }
 
 /// 現在のボクセルメッシュの色情報の表現モードを表す列挙型です。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ColorMode {
     Srgb,
     Linear,
 }
 
+/// ボクセルメッシュの色をglbファイルに書き込む方法を表す列挙型です。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorOutput {
+    /// 色ごとにプリミティブとマテリアルを生成します。
+    Material,
+    /// 単一のプリミティブに頂点カラー(`COLOR_0`)として色を書き込みます。
+    /// 異なる色の面で共有されている頂点は、色ごとに複製されます。
+    /// [`crate::mesh::SmoothMesher`]のように頂点ごとの色を持つメッシュでは、面の色の代わりにその頂点色を使用するため、頂点は複製されず色が補間されます。
+    /// 多数の色を持つ点群から生成したメッシュを出力する場合は、マテリアルの数が増えすぎないようにこちらを使用することを推奨します。
+    VertexColor,
+}
+
 mod private {
+    use std::borrow::Cow::Owned;
+    use std::collections::BTreeMap;
     use std::mem;
 
+    use anyhow::anyhow;
+    use gltf::binary::Header;
+    use gltf::buffer::Target::{ArrayBuffer, ElementArrayBuffer};
+    use gltf::Glb;
+    use gltf::json::{Accessor, Buffer, Material, Mesh, Node, Root, Scene, Value};
+    use gltf::json::accessor::{ComponentType, GenericComponentType, Type};
+    use gltf::json::buffer::{Stride, View};
+    use gltf::json::material::{PbrBaseColorFactor, PbrMetallicRoughness};
+    use gltf::json::mesh::Primitive;
+    use gltf::json::validation::Checked::Valid;
+    use gltf::json::validation::USize64;
+    use gltf::mesh::Mode;
+    use gltf::Semantic;
     use num::cast::AsPrimitive;
 
-    use crate::element::{Color, UInt};
+    use crate::element::{Color, Point3D, UInt};
+    use crate::glb::{Vertex, VertexColor};
 
     pub trait GlbGenPrivateMethod {
         fn srgb_to_liner_rgba<C>(color: Color<C>) -> [f32; 4]
@@ -130,195 +165,273 @@ mod private {
                 n + 4 - remainder
             }
         }
-    }
-}
-
-
-pub trait GlbGen<'a>: GlbGenPrivateMethod {
-    /// ボクセルメッシュから[`Glb`]のインスタンスを生成します。
-    fn from_voxel_mesh<P, C>(voxel_mesh: VoxelMesh<P, C>, color_mode: ColorMode) -> Result<Glb<'a>, anyhow::Error>
-    where
-        P: Int + AsPrimitive<f32>,
-        C: UInt + AsPrimitive<f32>,
-        f32: AsPrimitive<P> + AsPrimitive<C>,
-    {
-        let mut root = Root::default();
-
-        let vertices = voxel_mesh.points.into_iter().map(|point| {
-            let [x, y, z] = point.as_().data;
-            // gltfの座標系に合わせる
-            Vertex([x, z, -y])
-        }).collect::<Vec<_>>();
-
-        let (colors, indices): (Vec<_>, Vec<_>) = voxel_mesh.faces.into_iter().map(|(color, vertex_ids)| {
-            let color = match color_mode {
-                ColorMode::Srgb => Self::srgb_to_liner_rgba(color),
-                ColorMode::Linear => Self::liner_rgb_to_srgb(color)
-            };
-
-            let vertex_ids = vertex_ids.into_iter().map(|vertex_id| {
-                vertex_id as u32
-            }).collect::<Vec<_>>();
-
-            (color, vertex_ids)
-        }).unzip();
 
-        let padded_vertices_length = Self::round_up_to_mul_of_four(vertices.len()) * mem::size_of::<Vertex>();
-        let padded_indices_length = indices.iter().map(|v| Self::round_up_to_mul_of_four(v.len()) * mem::size_of::<u32>()).collect::<Vec<_>>();
-
-        let buffer_length = padded_vertices_length + padded_indices_length.iter().sum::<usize>();
-        let buffer = root.push(Buffer {
-            byte_length: USize64::from(buffer_length),
-            name: None,
-            uri: None,
-            extensions: Default::default(),
-            extras: Default::default(),
-        });
-
-        let vertices_buffer_view = root.push(View {
-            buffer,
-            byte_length: USize64::from(padded_vertices_length),
-            byte_offset: None,
-            byte_stride: Some(Stride(mem::size_of::<Vertex>())),
-            name: None,
-            target: Some(Valid(ArrayBuffer)),
-            extensions: Default::default(),
-            extras: Default::default(),
-        });
-
-        let indices_buffer_view = root.push(View {
-            buffer,
-            byte_length: USize64::from(padded_indices_length.iter().sum::<usize>()),
-            byte_offset: Some(USize64::from(padded_vertices_length)),
-            byte_stride: None,
-            name: None,
-            target: Some(Valid(ElementArrayBuffer)),
-            extensions: Default::default(),
-            extras: Default::default(),
-        });
+        // gltfの座標系に変換した頂点、頂点カラー、プリミティブごとのインデックスとベースカラーから、glbを組み立てる
+        // `bounds`と`offset`はボクセルメッシュの座標系で与える
+        fn assemble_glb<'a>(
+            vertices: Vec<[f32; 3]>,
+            colors: Option<Vec<[f32; 4]>>,
+            primitives: Vec<(Vec<u32>, [f32; 4])>,
+            bounds: (Point3D<f32>, Point3D<f32>),
+            offset: Point3D<f32>,
+            resolution: f64,
+        ) -> Result<Glb<'a>, anyhow::Error> {
+            let mut root = Root::default();
+
+            let vertices = vertices.into_iter().map(Vertex).collect::<Vec<_>>();
+            let colors = colors.map(|colors| colors.into_iter().map(VertexColor).collect::<Vec<_>>());
+
+            let padded_vertices_length = Self::round_up_to_mul_of_four(vertices.len()) * mem::size_of::<Vertex>();
+            let padded_colors_length = colors.as_ref().map_or(0, |colors| Self::round_up_to_mul_of_four(colors.len()) * mem::size_of::<VertexColor>());
+            let padded_indices_length = primitives.iter().map(|(indices, _)| Self::round_up_to_mul_of_four(indices.len()) * mem::size_of::<u32>()).collect::<Vec<_>>();
+
+            let buffer_length = padded_vertices_length + padded_colors_length + padded_indices_length.iter().sum::<usize>();
+            let buffer = root.push(Buffer {
+                byte_length: USize64::from(buffer_length),
+                name: None,
+                uri: None,
+                extensions: Default::default(),
+                extras: Default::default(),
+            });
 
-        let (min, max) = {
-            let [min_x,min_y,min_z] = voxel_mesh.bounds.0.as_::<f32>().data;
-            let [max_x,max_y,max_z] = voxel_mesh.bounds.1.as_::<f32>().data;
+            let vertices_buffer_view = root.push(View {
+                buffer,
+                byte_length: USize64::from(padded_vertices_length),
+                byte_offset: None,
+                byte_stride: Some(Stride(mem::size_of::<Vertex>())),
+                name: None,
+                target: Some(Valid(ArrayBuffer)),
+                extensions: Default::default(),
+                extras: Default::default(),
+            });
 
-            // gltfの座標系に合わせる
-            let min = [min_x,min_z,-max_y];
-            let max = [max_x,max_z,-min_y];
+            let indices_buffer_view = root.push(View {
+                buffer,
+                byte_length: USize64::from(padded_indices_length.iter().sum::<usize>()),
+                byte_offset: Some(USize64::from(padded_vertices_length + padded_colors_length)),
+                byte_stride: None,
+                name: None,
+                target: Some(Valid(ElementArrayBuffer)),
+                extensions: Default::default(),
+                extras: Default::default(),
+            });
 
-            (min, max)
-        };
+            let (min, max) = {
+                let [min_x, min_y, min_z] = bounds.0.data;
+                let [max_x, max_y, max_z] = bounds.1.data;
 
+                // gltfの座標系に合わせる
+                let min = [min_x, min_z, -max_y];
+                let max = [max_x, max_z, -min_y];
 
-        let positions_accessor = root.push(Accessor {
-            buffer_view: Some(vertices_buffer_view),
-            byte_offset: Some(USize64(0)),
-            count: USize64::from(vertices.len()),
-            component_type: Valid(GenericComponentType(ComponentType::F32)),
-            extensions: Default::default(),
-            extras: Default::default(),
-            type_: Valid(Type::Vec3),
-            min: Some(Value::from(Vec::from(min))),
-            max: Some(Value::from(Vec::from(max))),
-            name: None,
-            normalized: false,
-            sparse: None,
-        });
-
-        let primitives = colors.into_iter().enumerate().map(|(i, color)| {
-            let offset = padded_indices_length[0..i].iter().sum::<usize>();
+                (min, max)
+            };
 
-            let indices_accessor = root.push(Accessor {
-                buffer_view: Some(indices_buffer_view),
-                byte_offset: Some(USize64::from(offset)),
-                count: USize64::from(indices[i].len()),
-                component_type: Valid(GenericComponentType(ComponentType::U32)),
+            let positions_accessor = root.push(Accessor {
+                buffer_view: Some(vertices_buffer_view),
+                byte_offset: Some(USize64(0)),
+                count: USize64::from(vertices.len()),
+                component_type: Valid(GenericComponentType(ComponentType::F32)),
                 extensions: Default::default(),
                 extras: Default::default(),
-                type_: Valid(Type::Scalar),
-                min: None,
-                max: None,
+                type_: Valid(Type::Vec3),
+                min: Some(Value::from(Vec::from(min))),
+                max: Some(Value::from(Vec::from(max))),
                 name: None,
                 normalized: false,
                 sparse: None,
             });
 
-            let pbr_metallic_roughness = PbrMetallicRoughness {
-                base_color_factor: PbrBaseColorFactor(color),
-                base_color_texture: None,
-                metallic_factor: Default::default(),
-                roughness_factor: Default::default(),
-                metallic_roughness_texture: None,
-                extensions: Default::default(),
-                extras: Default::default(),
-            };
+            let colors_accessor = colors.as_ref().map(|colors| {
+                let colors_buffer_view = root.push(View {
+                    buffer,
+                    byte_length: USize64::from(padded_colors_length),
+                    byte_offset: Some(USize64::from(padded_vertices_length)),
+                    byte_stride: Some(Stride(mem::size_of::<VertexColor>())),
+                    name: None,
+                    target: Some(Valid(ArrayBuffer)),
+                    extensions: Default::default(),
+                    extras: Default::default(),
+                });
+
+                root.push(Accessor {
+                    buffer_view: Some(colors_buffer_view),
+                    byte_offset: Some(USize64(0)),
+                    count: USize64::from(colors.len()),
+                    component_type: Valid(GenericComponentType(ComponentType::F32)),
+                    extensions: Default::default(),
+                    extras: Default::default(),
+                    type_: Valid(Type::Vec4),
+                    min: None,
+                    max: None,
+                    name: None,
+                    normalized: false,
+                    sparse: None,
+                })
+            });
 
-            let material = root.push(Material {
-                alpha_cutoff: None,
-                alpha_mode: Default::default(),
-                double_sided: false,
-                name: None,
-                pbr_metallic_roughness,
-                normal_texture: None,
-                occlusion_texture: None,
-                emissive_texture: None,
-                emissive_factor: Default::default(),
+            let (base_colors, indices): (Vec<_>, Vec<_>) = primitives.into_iter().map(|(indices, color)| (color, indices)).unzip();
+
+            let primitives = base_colors.into_iter().enumerate().map(|(i, color)| {
+                let offset = padded_indices_length[0..i].iter().sum::<usize>();
+
+                let indices_accessor = root.push(Accessor {
+                    buffer_view: Some(indices_buffer_view),
+                    byte_offset: Some(USize64::from(offset)),
+                    count: USize64::from(indices[i].len()),
+                    component_type: Valid(GenericComponentType(ComponentType::U32)),
+                    extensions: Default::default(),
+                    extras: Default::default(),
+                    type_: Valid(Type::Scalar),
+                    min: None,
+                    max: None,
+                    name: None,
+                    normalized: false,
+                    sparse: None,
+                });
+
+                let pbr_metallic_roughness = PbrMetallicRoughness {
+                    base_color_factor: PbrBaseColorFactor(color),
+                    base_color_texture: None,
+                    metallic_factor: Default::default(),
+                    roughness_factor: Default::default(),
+                    metallic_roughness_texture: None,
+                    extensions: Default::default(),
+                    extras: Default::default(),
+                };
+
+                let material = root.push(Material {
+                    alpha_cutoff: None,
+                    alpha_mode: Default::default(),
+                    double_sided: false,
+                    name: None,
+                    pbr_metallic_roughness,
+                    normal_texture: None,
+                    occlusion_texture: None,
+                    emissive_texture: None,
+                    emissive_factor: Default::default(),
+                    extensions: Default::default(),
+                    extras: Default::default(),
+                });
+
+                let mut attributes = BTreeMap::from([(Valid(Semantic::Positions), positions_accessor)]);
+                if let Some(colors_accessor) = colors_accessor {
+                    attributes.insert(Valid(Semantic::Colors(0)), colors_accessor);
+                }
+
+                Primitive {
+                    attributes,
+                    extensions: None,
+                    extras: Default::default(),
+                    indices: Some(indices_accessor),
+                    material: Some(material),
+                    mode: Valid(Mode::Triangles),
+                    targets: None,
+                }
+            }).collect::<Vec<_>>();
+
+            let mesh = root.push(Mesh {
                 extensions: Default::default(),
                 extras: Default::default(),
+                name: None,
+                primitives,
+                weights: None,
             });
 
+            let node = root.push(Node {
+                mesh: Some(mesh),
+                translation: Some((offset * Point3D::from(-1.)).data),
+                scale: Some([resolution as f32; 3]),
+                ..Default::default()
+            });
 
-            Primitive {
-                attributes: BTreeMap::from([(Valid(Semantic::Positions), positions_accessor)]),
-                extensions: None,
+            let scene = root.push(Scene {
+                extensions: Default::default(),
                 extras: Default::default(),
-                indices: Some(indices_accessor),
-                material: Some(material),
-                mode: Valid(Mode::Triangles),
-                targets: None,
-            }
-        }).collect::<Vec<_>>();
+                name: None,
+                nodes: vec![node],
+            });
 
-        let mesh = root.push(Mesh {
-            extensions: Default::default(),
-            extras: Default::default(),
-            name: None,
-            primitives,
-            weights: None,
-        });
+            root.scene = Some(scene);
+
+            let json = root.to_string().map_err(|_| anyhow!("Serialization error"))?.into_bytes();
+            let json_offset = Self::round_up_to_mul_of_four(json.len());
+
+            let bin = [
+                Self::convert_to_byte_vec(Self::pad_to_mul_of_four(vertices)),
+                colors.map_or_else(Vec::new, |colors| Self::convert_to_byte_vec(Self::pad_to_mul_of_four(colors))),
+                indices.into_iter().flat_map(|v| Self::convert_to_byte_vec(Self::pad_to_mul_of_four(v))).collect::<Vec<_>>(),
+            ].concat();
+
+            Ok(Glb {
+                header: Header {
+                    magic: *b"glTF",
+                    version: 2,
+                    length: (json_offset + buffer_length).try_into().map_err(|_| anyhow!("file size exceeds binary glTF limit"))?,
+                },
+                json: Owned(json),
+                bin: Some(Owned(bin)),
+            })
+        }
+    }
+}
 
-        let node = root.push(Node {
-            mesh: Some(mesh),
-            translation: Some((voxel_mesh.offset.as_::<f32>() * Point3D::from(-1.)).data),
-            scale: Some([voxel_mesh.resolution as f32; 3]),
-            ..Default::default()
-        });
 
-        let scene = root.push(Scene {
-            extensions: Default::default(),
-            extras: Default::default(),
-            name: None,
-            nodes: vec![node],
-        });
+pub trait GlbGen<'a>: GlbGenPrivateMethod {
+    /// ボクセルメッシュから[`Glb`]のインスタンスを生成します。
+    /// 色の書き込み方法は`color_output`で指定します。詳細は[`ColorOutput`]を参照してください。
+    fn from_voxel_mesh<P, C>(voxel_mesh: VoxelMesh<P, C>, color_mode: ColorMode, color_output: ColorOutput) -> Result<Glb<'a>, anyhow::Error>
+    where
+        P: Int + AsPrimitive<f32>,
+        C: UInt + AsPrimitive<f32>,
+        f32: AsPrimitive<P> + AsPrimitive<C>,
+    {
+        let to_rgba = |color: Color<C>| match color_mode {
+            ColorMode::Srgb => Self::srgb_to_liner_rgba(color),
+            ColorMode::Linear => Self::liner_rgb_to_srgb(color)
+        };
 
-        root.scene = Some(scene);
+        // gltfの座標系に合わせる
+        let to_gltf = |point: Point3D<P>| {
+            let [x, y, z] = point.as_::<f32>().data;
+            [x, z, -y]
+        };
 
-        let json = root.to_string().map_err(|_| anyhow!("Serialization error"))?.into_bytes();
-        let json_offset = Self::round_up_to_mul_of_four(json.len());
+        let bounds = (voxel_mesh.bounds.0.as_::<f32>(), voxel_mesh.bounds.1.as_::<f32>());
+        let (offset, resolution) = (voxel_mesh.offset.as_::<f32>(), voxel_mesh.resolution);
 
-        let bin = [
-            Self::convert_to_byte_vec(Self::pad_to_mul_of_four(vertices)),
-            indices.into_iter().flat_map(|v| Self::convert_to_byte_vec(Self::pad_to_mul_of_four(v))).collect::<Vec<_>>(),
-        ].concat();
+        match color_output {
+            ColorOutput::Material => {
+                let vertices = voxel_mesh.points.into_iter().map(to_gltf).collect::<Vec<_>>();
 
-        Ok(Glb {
-            header: Header {
-                magic: *b"glTF",
-                version: 2,
-                length: (json_offset + buffer_length).try_into().map_err(|_| anyhow!("file size exceeds binary glTF limit"))?,
-            },
-            json: Owned(json),
-            bin: Some(Owned(bin)),
-        })
+                let primitives = voxel_mesh.faces.into_iter().map(|(color, vertex_ids)| {
+                    let vertex_ids = vertex_ids.into_iter().map(|vertex_id| vertex_id as u32).collect::<Vec<_>>();
+                    (vertex_ids, to_rgba(color))
+                }).collect::<Vec<_>>();
+
+                Self::assemble_glb(vertices, None, primitives, bounds, offset, resolution)
+            }
+            ColorOutput::VertexColor => {
+                // 位置と色の組み合わせごとに頂点を割り当てる
+                // 頂点ごとの色を持つメッシュでは、面の色の代わりにその頂点色を使用する
+                let mut vertex_set = IndexSet::<(usize, Color<C>), FxBuildHasher>::with_hasher(Default::default());
+                let vertex_colors = &voxel_mesh.vertex_colors;
+
+                let indices = voxel_mesh.faces.into_iter().flat_map(|(color, vertex_ids)| {
+                    vertex_ids.into_iter().map(|vertex_id| {
+                        let color = vertex_colors.get(vertex_id).copied().unwrap_or(color);
+                        vertex_set.insert_full((vertex_id, color)).0 as u32
+                    }).collect::<Vec<_>>()
+                }).collect::<Vec<_>>();
+
+                let (vertices, colors): (Vec<_>, Vec<_>) = vertex_set.into_iter().map(|(vertex_id, color)| {
+                    (to_gltf(voxel_mesh.points[vertex_id]), to_rgba(color))
+                }).unzip();
+
+                // 頂点カラーにはベースカラーが乗算されるため、白色のマテリアルを割り当てる
+                Self::assemble_glb(vertices, Some(colors), vec![(indices, [1.; 4])], bounds, offset, resolution)
+            }
+        }
     }
 
     /// ボクセルメッシュからz軸に対してテクスチャを投影した[`Glb`]のインスタンスを生成します。
@@ -638,4 +751,76 @@ mod test {
         assert_eq!(TestStruct::pad_to_mul_of_four(vec![1, 2, 3, 4]), vec![1, 2, 3, 4]);
         assert_eq!(TestStruct::pad_to_mul_of_four(vec![1, 2, 3, 4, 5]), vec![1, 2, 3, 4, 5, 0, 0, 0]);
     }
+
+    #[test]
+    fn test_from_voxel_mesh_color_output() {
+        use fxhash::FxBuildHasher;
+        use gltf::{Glb, Gltf, Semantic};
+
+        use crate::collection::{HMap3DVoxelCollection, VoxelCollection};
+        use crate::element::{Color, Point3D};
+        use crate::glb::{ColorMode, ColorOutput, GlbGen};
+        use crate::mesh::{Mesher, ValidSide};
+
+        let vc = HMap3DVoxelCollection::<i32, u8, u8, FxBuildHasher>::builder().points(vec![
+            (Point3D::new([0, 0, 0]), Color::new([255, 0, 0])),
+            (Point3D::new([1, 0, 0]), Color::new([0, 255, 0])),
+        ]).build();
+
+        let mesh = Mesher::meshing(vc, ValidSide::all());
+
+        // 色ごとにプリミティブとマテリアルを生成する場合は、頂点カラーを持たない
+        let glb = Glb::from_voxel_mesh(mesh.clone(), ColorMode::Srgb, ColorOutput::Material).unwrap();
+        let gltf = Gltf::from_slice(&glb.to_vec().unwrap()).unwrap();
+        let primitives = gltf.meshes().flat_map(|mesh| mesh.primitives().collect::<Vec<_>>()).collect::<Vec<_>>();
+
+        assert_eq!(primitives.len(), 2);
+        assert_eq!(gltf.materials().len(), 2);
+        assert!(primitives.iter().all(|primitive| primitive.get(&Semantic::Colors(0)).is_none()));
+        assert_eq!(primitives.iter().map(|primitive| primitive.indices().unwrap().count()).sum::<usize>(), 10 * 6);
+
+        let glb = Glb::from_voxel_mesh(mesh, ColorMode::Srgb, ColorOutput::VertexColor).unwrap();
+
+        let gltf = Gltf::from_slice(&glb.to_vec().unwrap()).unwrap();
+        let primitives = gltf.meshes().flat_map(|mesh| mesh.primitives().collect::<Vec<_>>()).collect::<Vec<_>>();
+
+        assert_eq!(primitives.len(), 1);
+
+        let positions = primitives[0].get(&Semantic::Positions).unwrap();
+        let colors = primitives[0].get(&Semantic::Colors(0)).unwrap();
+
+        // 2つのボクセルが接する面上の4頂点は色ごとに複製される
+        assert_eq!(positions.count(), 12 + 4);
+        assert_eq!(colors.count(), positions.count());
+        assert_eq!(primitives[0].indices().unwrap().count(), 10 * 6);
+    }
+
+    #[test]
+    fn test_from_smooth_mesh_with_vertex_color() {
+        use fxhash::FxBuildHasher;
+        use gltf::{Glb, Gltf, Semantic};
+
+        use crate::collection::{HMap3DVoxelCollection, VoxelCollection};
+        use crate::element::{Color, Point3D};
+        use crate::glb::{ColorMode, ColorOutput, GlbGen};
+        use crate::mesh::SmoothMesher;
+
+        let vc = HMap3DVoxelCollection::<i32, u8, u8, FxBuildHasher>::builder().points(vec![
+            (Point3D::new([0, 0, 0]), Color::new([255, 0, 0])),
+            (Point3D::new([1, 0, 0]), Color::new([0, 255, 0])),
+        ]).build();
+
+        let mesh = SmoothMesher::meshing(vc);
+        let point_count = mesh.points.len();
+
+        let glb = Glb::from_voxel_mesh(mesh, ColorMode::Srgb, ColorOutput::VertexColor).unwrap();
+
+        let gltf = Gltf::from_slice(&glb.to_vec().unwrap()).unwrap();
+        let primitives = gltf.meshes().flat_map(|mesh| mesh.primitives().collect::<Vec<_>>()).collect::<Vec<_>>();
+
+        // 頂点色を持つメッシュでは頂点が色ごとに複製されない
+        assert_eq!(primitives.len(), 1);
+        assert_eq!(primitives[0].get(&Semantic::Positions).unwrap().count(), point_count);
+        assert_eq!(primitives[0].get(&Semantic::Colors(0)).unwrap().count(), point_count);
+    }
 }
@@ -1,9 +1,15 @@
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+
+use fxhash::FxHashMap;
 use las::{Point, Read};
 use num::cast::AsPrimitive;
 use ordered_float::OrderedFloat;
 
+use crate::build_voxelizer::VoxelizerOption;
 use crate::collection::{PointCloud, VoxelCollection};
-use crate::element::{Color, Point3D, UInt};
+use crate::element::{Color, Number, Point3D, UInt};
+use crate::voxelizer::{Resolution, Voxelizer};
 
 impl<W> PointCloud<OrderedFloat<f64>, W, u16>
 where
@@ -12,27 +18,593 @@ where
 {
     /// lasファイルから点群を読み込みます。
     /// 使用するには`las`featureを有効にしてください。
-    pub fn from_las(mut reader: las::Reader) -> Self {
-        let points = reader.points().flatten().map(|p| {
-            let Point { x, y, z, color, .. } = p;
-            let color = {
-                if let Some(color) = color {
-                    let las::Color { red, green, blue } = color;
-                    Color::new([red, green, blue])
-                } else {
-                    Color::new([0, 0, 0])
-                }
-            };
-
-            let point = Point3D::new([
-                OrderedFloat::from(x),
-                OrderedFloat::from(y),
-                OrderedFloat::from(z)
-            ]);
-
-            (point, color)
-        }).collect();
+    pub fn from_las(reader: las::Reader) -> Self {
+        Self::from_las_filtered(reader, &ClassificationFilter::All)
+    }
+
+    /// lasファイルから、分類コードが`filter`に一致する点のみを読み込みます。
+    /// 例えば、[`ClassificationFilter::ground`]を指定して地表面のみをボクセル化することで、1つのファイルからDTMを生成できます。
+    /// 使用するには`las`featureを有効にしてください。
+    pub fn from_las_filtered(mut reader: las::Reader, filter: &ClassificationFilter) -> Self {
+        let points = reader.points().flatten()
+            .filter(|point| filter.matches(u8::from(point.classification)))
+            .map(Self::convert_las_point)
+            .collect();
 
         PointCloud::<OrderedFloat<f64>, W, u16>::builder().points(points).build()
     }
+
+    /// lasファイルから`batch_size`点ずつ点群を読み込むイテレータを返します。
+    /// すべての点を一度にメモリ上に展開しないため、巨大なlas/lazファイルを扱う場合に使用してください。
+    /// 読み込みに失敗した場合、その要素はエラーになります。
+    /// [`BuildVoxelizer::voxelize_batches`](crate::build_voxelizer::BuildVoxelizer::voxelize_batches)にはそのまま渡すことができます。
+    /// 使用するには`las`featureを有効にしてください。
+    pub fn las_batches<'a>(reader: las::Reader<'a>, batch_size: usize) -> LasBatches<'a, W> {
+        LasBatches {
+            reader,
+            batch_size: batch_size.max(1) as u64,
+            buf: Vec::new(),
+            filter: ClassificationFilter::All,
+            _phantom: PhantomData,
+        }
+    }
+
+    fn convert_las_point(point: Point) -> (Point3D<OrderedFloat<f64>>, Color<u16>) {
+        let Point { x, y, z, color, .. } = point;
+        let color = {
+            if let Some(color) = color {
+                let las::Color { red, green, blue } = color;
+                Color::new([red, green, blue])
+            } else {
+                Color::new([0, 0, 0])
+            }
+        };
+
+        let point = Point3D::new([
+            OrderedFloat::from(x),
+            OrderedFloat::from(y),
+            OrderedFloat::from(z)
+        ]);
+
+        (point, color)
+    }
+}
+
+/// lasファイルから一定数ずつ点群を読み込むイテレータです。
+/// [`PointCloud::las_batches`]で生成します。
+/// 使用するには`las`featureを有効にしてください。
+pub struct LasBatches<'a, W> {
+    reader: las::Reader<'a>,
+    batch_size: u64,
+    buf: Vec<Point>,
+    filter: ClassificationFilter,
+    _phantom: PhantomData<W>,
+}
+
+impl<'a, W> LasBatches<'a, W> {
+    /// 分類コードが`filter`に一致する点のみを読み込むように設定します。
+    /// 除外された点の分だけ、各要素の点の数は`batch_size`より少なくなります。
+    pub fn classification_filter(mut self, filter: ClassificationFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// 各点の属性も読み込み、[`LasAttributeCloud`]を返すイテレータに変換します。
+    pub fn with_attributes(self) -> LasAttributeBatches<'a, W> {
+        LasAttributeBatches { batches: self }
+    }
+
+    // 次の`batch_size`点を読み込み、フィルターに一致する点を変換して返す
+    fn next_points<T>(&mut self, convert: impl FnMut(Point) -> T) -> Option<Result<Vec<T>, anyhow::Error>> {
+        self.buf.clear();
+
+        match self.reader.read_n_into(self.batch_size, &mut self.buf) {
+            Ok(0) => None,
+            Ok(_) => {
+                let filter = &self.filter;
+                let points = self.buf.drain(..)
+                    .filter(|point| filter.matches(u8::from(point.classification)))
+                    .map(convert)
+                    .collect();
+
+                Some(Ok(points))
+            }
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+}
+
+impl<W> Iterator for LasBatches<'_, W>
+where
+    W: UInt + AsPrimitive<u16>,
+    u16: AsPrimitive<W>,
+{
+    type Item = Result<PointCloud<OrderedFloat<f64>, W, u16>, anyhow::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let points = self.next_points(PointCloud::<OrderedFloat<f64>, W, u16>::convert_las_point);
+        points.map(|points| points.map(|points| PointCloud::builder().points(points).build()))
+    }
+}
+
+/// lasファイルから一定数ずつ、属性付きの点群を読み込むイテレータです。
+/// [`LasBatches::with_attributes`]で生成します。
+/// 使用するには`las`featureを有効にしてください。
+pub struct LasAttributeBatches<'a, W> {
+    batches: LasBatches<'a, W>,
+}
+
+impl<W> Iterator for LasAttributeBatches<'_, W> {
+    type Item = Result<LasAttributeCloud, anyhow::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let points = self.batches.next_points(LasAttributeCloud::convert_las_point);
+        points.map(|points| points.map(|points| LasAttributeCloud { points }))
+    }
+}
+
+/// 座標と色に加えて、各点の属性を保持した点群です。
+/// 点と属性を1つの要素として保持するため、座標変換などを行っても対応関係は失われません。
+/// [`LasAttributeVoxelizer`]に追加することで、ボクセルごとに属性を集約できます。
+/// 使用するには`las`featureを有効にしてください。
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LasAttributeCloud {
+    points: Vec<(Point3D<f64>, Color<u16>, LasAttribute)>,
+}
+
+impl LasAttributeCloud {
+    /// lasファイルから、属性付きの点群を読み込みます。
+    pub fn from_las(reader: las::Reader) -> Self {
+        Self::from_las_filtered(reader, &ClassificationFilter::All)
+    }
+
+    /// lasファイルから、分類コードが`filter`に一致する点のみを属性付きで読み込みます。
+    pub fn from_las_filtered(mut reader: las::Reader, filter: &ClassificationFilter) -> Self {
+        let points = reader.points().flatten()
+            .filter(|point| filter.matches(u8::from(point.classification)))
+            .map(Self::convert_las_point)
+            .collect();
+
+        Self { points }
+    }
+
+    fn convert_las_point(point: Point) -> (Point3D<f64>, Color<u16>, LasAttribute) {
+        let attribute = LasAttribute::from(&point);
+        let (xyz, color) = PointCloud::<OrderedFloat<f64>, u8, u16>::convert_las_point(point);
+
+        (Point3D::new(xyz.data.map(|a| a.into_inner())), color, attribute)
+    }
+
+    /// 点の数を返します。
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    /// 点が1つも含まれない場合に`true`を返します。
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// 座標、色、属性の組を返します。
+    pub fn points(&self) -> &[(Point3D<f64>, Color<u16>, LasAttribute)] {
+        &self.points
+    }
+
+    /// すべての点の座標を`f`で変換します。
+    /// 平面直角座標系から経緯度への変換など、ボクセル化の前の座標変換に使用してください。
+    pub fn transform<F: FnMut(Point3D<f64>) -> Point3D<f64>>(mut self, mut f: F) -> Self {
+        self.points.iter_mut().for_each(|(point, _, _)| *point = f(*point));
+        self
+    }
+
+    /// 属性を取り除いた点群に変換します。
+    pub fn into_point_cloud<P, W>(self) -> PointCloud<P, W, u16>
+    where
+        P: Number + 'static,
+        W: UInt + AsPrimitive<u16>,
+        u16: AsPrimitive<W>,
+        f64: AsPrimitive<P>,
+    {
+        let points = self.points.into_iter().map(|(point, color, _)| (Point3D::new(point.data.map(|a| a.as_())), color)).collect();
+        PointCloud::builder().points(points).build()
+    }
+}
+
+/// 読み込む点を分類コードで選択するためのフィルターです。
+/// 分類コードはASPRSの定義に従います。
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ClassificationFilter {
+    /// すべての点を読み込みます。
+    #[default]
+    All,
+    /// 指定された分類コードの点のみを読み込みます。
+    Include(Vec<u8>),
+    /// 指定された分類コード以外の点を読み込みます。
+    Exclude(Vec<u8>),
+}
+
+impl ClassificationFilter {
+    /// 地表面の分類コードです。
+    pub const GROUND: u8 = 2;
+    /// 建物の分類コードです。
+    pub const BUILDING: u8 = 6;
+    /// 低いノイズの分類コードです。
+    pub const LOW_NOISE: u8 = 7;
+    /// 高いノイズの分類コードです。
+    pub const HIGH_NOISE: u8 = 18;
+
+    /// 地表面の点のみを読み込むフィルターを返します。
+    pub fn ground() -> Self {
+        Self::Include(vec![Self::GROUND])
+    }
+
+    /// 建物の点のみを読み込むフィルターを返します。
+    pub fn building() -> Self {
+        Self::Include(vec![Self::BUILDING])
+    }
+
+    /// ノイズ以外の点を読み込むフィルターを返します。
+    pub fn exclude_noise() -> Self {
+        Self::Exclude(vec![Self::LOW_NOISE, Self::HIGH_NOISE])
+    }
+
+    /// 分類コードがフィルターに一致するかどうかを返します。
+    pub fn matches(&self, classification: u8) -> bool {
+        match self {
+            ClassificationFilter::All => true,
+            ClassificationFilter::Include(classes) => classes.contains(&classification),
+            ClassificationFilter::Exclude(classes) => !classes.contains(&classification),
+        }
+    }
+}
+
+/// lasファイルの各点が持つ、座標と色以外の属性です。
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LasAttribute {
+    /// ASPRSの定める分類コードです。
+    pub classification: u8,
+    /// 反射強度です。
+    pub intensity: u16,
+    /// 何番目の反射であるかを表します。
+    pub return_number: u8,
+    /// 1つのパルスに対する反射の数です。
+    pub number_of_returns: u8,
+    /// GPS時刻です。点のフォーマットによっては存在しません。
+    pub gps_time: Option<f64>,
+}
+
+impl LasAttribute {
+    /// 最初の反射であるかどうかを返します。
+    /// 反射番号が記録されていない(0である)場合も最初の反射として扱います。
+    pub fn is_first_return(&self) -> bool {
+        self.return_number <= 1
+    }
+}
+
+impl From<&Point> for LasAttribute {
+    fn from(point: &Point) -> Self {
+        Self {
+            classification: u8::from(point.classification),
+            intensity: point.intensity,
+            return_number: point.return_number,
+            number_of_returns: point.number_of_returns,
+            gps_time: point.gps_time,
+        }
+    }
+}
+
+/// ボクセルに含まれる点の属性を集約したものです。
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LasVoxelAttribute {
+    point_count: u32,
+    classification_counts: BTreeMap<u8, u32>,
+    intensity_sum: u64,
+    max_intensity: u16,
+    first_return_count: u32,
+}
+
+impl LasVoxelAttribute {
+    /// 点の属性を1つ追加します。
+    pub fn add(&mut self, attribute: &LasAttribute) {
+        self.point_count += 1;
+        *self.classification_counts.entry(attribute.classification).or_default() += 1;
+        self.intensity_sum += attribute.intensity as u64;
+        self.max_intensity = self.max_intensity.max(attribute.intensity);
+
+        if attribute.is_first_return() {
+            self.first_return_count += 1;
+        }
+    }
+
+    /// 別のボクセルの属性を統合します。
+    pub fn merge(&mut self, other: &LasVoxelAttribute) {
+        self.point_count += other.point_count;
+        other.classification_counts.iter().for_each(|(&classification, &count)| {
+            *self.classification_counts.entry(classification).or_default() += count;
+        });
+        self.intensity_sum += other.intensity_sum;
+        self.max_intensity = self.max_intensity.max(other.max_intensity);
+        self.first_return_count += other.first_return_count;
+    }
+
+    /// ボクセルに含まれる点の数を返します。
+    pub fn point_count(&self) -> u32 {
+        self.point_count
+    }
+
+    /// 分類コードごとの点の数を返します。
+    pub fn classification_counts(&self) -> &BTreeMap<u8, u32> {
+        &self.classification_counts
+    }
+
+    /// 最も多くの点が属する分類コードを返します。
+    /// 点の数が同じ場合は、小さい分類コードを返します。
+    /// 点が含まれない場合は`None`を返します。
+    pub fn majority_classification(&self) -> Option<u8> {
+        self.classification_counts.iter()
+            .max_by(|(a_class, a_count), (b_class, b_count)| a_count.cmp(b_count).then(b_class.cmp(a_class)))
+            .map(|(&classification, _)| classification)
+    }
+
+    /// 反射強度の平均値を返します。
+    /// 点が含まれない場合は0を返します。
+    pub fn mean_intensity(&self) -> f64 {
+        if self.point_count == 0 {
+            return 0.;
+        }
+
+        self.intensity_sum as f64 / self.point_count as f64
+    }
+
+    /// 反射強度の最大値を返します。
+    pub fn max_intensity(&self) -> u16 {
+        self.max_intensity
+    }
+
+    /// 含まれる点のうち、最初の反射である点の割合を返します。
+    /// 点が含まれない場合は0を返します。
+    pub fn first_return_ratio(&self) -> f64 {
+        if self.point_count == 0 {
+            return 0.;
+        }
+
+        self.first_return_count as f64 / self.point_count as f64
+    }
+
+    /// 最も多くの点が属する分類コードに対応する色を返します。
+    /// 色は[`classification_color`]で決定されます。
+    pub fn classification_color(&self) -> Color<u8> {
+        classification_color(self.majority_classification().unwrap_or(0))
+    }
+}
+
+/// ASPRSの分類コードに対応する表示用の色を返します。
+/// 定義されていない分類コードには灰色を返します。
+pub fn classification_color(classification: u8) -> Color<u8> {
+    let rgb = match classification {
+        // 地表面
+        2 => [166, 117, 65],
+        // 低い植生
+        3 => [173, 217, 98],
+        // 中くらいの植生
+        4 => [77, 176, 56],
+        // 高い植生
+        5 => [26, 110, 38],
+        // 建物
+        6 => [219, 68, 55],
+        // ノイズ
+        7 | 18 => [255, 0, 255],
+        // 水面
+        9 => [51, 102, 204],
+        // 線路
+        10 => [110, 80, 90],
+        // 道路面
+        11 => [90, 90, 90],
+        // 電線
+        13 | 14 => [255, 200, 0],
+        // 橋
+        17 => [200, 140, 200],
+        _ => [180, 180, 180],
+    };
+
+    Color::new(rgb)
+}
+
+/// ボクセル座標ごとに集約された点の属性です。
+/// 座標値は、ボクセライザーが出力するボクセルデータの`to_vec`で得られる座標値と対応します。
+pub type LasAttributeField<P> = FxHashMap<Point3D<P>, LasVoxelAttribute>;
+
+/// 属性付きの点群をボクセル化し、ボクセルごとに点の属性を集約するための構造体です。
+/// 内部のボクセライザーに点群を追加し、同じ点を[`Voxelizer::voxel_point`]で求めたボクセルに属性を集約します。
+/// 属性の座標値は`finish`の時点で[`Voxelizer::output_point`]によって出力の座標値に変換されるため、
+/// 追加後にオフセットを変更した場合も、出力されるボクセルと属性は常に同じ座標値で対応します。
+/// 使用するには`las`featureを有効にしてください。
+pub struct LasAttributeVoxelizer<V: Voxelizer<Option>, Option: VoxelizerOption<Color=u16>>
+{
+    voxelizer: V,
+    field: LasAttributeField<Option::OutPoint>,
+}
+
+impl<V, Option> LasAttributeVoxelizer<V, Option>
+where
+    V: Voxelizer<Option>,
+    Option: VoxelizerOption<Color=u16>,
+    Option::InPoint: 'static,
+    Option::Weight: AsPrimitive<u16>,
+    u16: AsPrimitive<Option::Weight>,
+    f64: AsPrimitive<Option::InPoint>,
+{
+    /// 分解能を指定して新しいインスタンスを生成します。
+    pub fn new(resolution: Resolution) -> Self {
+        Self {
+            voxelizer: V::new(resolution),
+            field: LasAttributeField::default(),
+        }
+    }
+
+    /// 内部のボクセライザーを返します。
+    /// [`Voxelizer::set_min_weight`]などの設定に使用してください。
+    pub fn voxelizer_mut(&mut self) -> &mut V {
+        &mut self.voxelizer
+    }
+
+    /// 属性付きの点群を追加します。
+    pub fn add(&mut self, cloud: LasAttributeCloud) {
+        cloud.points.iter().for_each(|(point, _, attribute)| {
+            let point = self.voxelizer.voxel_point(Point3D::new(point.data.map(|a| a.as_())));
+            self.field.entry(point).or_default().add(attribute);
+        });
+
+        self.voxelizer.add(cloud.into_point_cloud::<Option::InPoint, Option::Weight>());
+    }
+
+    /// ボクセルデータと、ボクセル座標ごとに集約された属性を返します。
+    /// 重みの閾値などによって出力されなかったボクセルの属性は含まれません。
+    pub fn finish(self) -> (Option::OutVC, LasAttributeField<Option::OutPoint>) {
+        let mut field = self.field.into_iter()
+            .map(|(point, attribute)| (self.voxelizer.output_point(point), attribute))
+            .collect::<LasAttributeField<_>>();
+
+        let vc = self.voxelizer.finish();
+        field.retain(|point, _| vc.has(point));
+
+        (vc, field)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use coordinate_transformer::ZoomLv;
+    use fxhash::FxBuildHasher;
+    use las::{Builder, Color, Point, Write, Writer};
+    use las::point::Classification;
+    use ordered_float::OrderedFloat;
+
+    use crate::build_voxelizer::{MapTileVoxelizerDefaultOptions, VoxelizerOption};
+    use crate::collection::{HMap3DVoxelCollection, PointCloud, VoxelCollection};
+    use crate::element::Point3D;
+    use crate::las::{ClassificationFilter, LasAttributeCloud, LasAttributeVoxelizer};
+    use crate::voxelizer::{MapTileVoxelizer, Resolution, SimpleVoxelizer, Voxelizer};
+
+    fn las_buf(count: usize) -> Vec<u8> {
+        let mut builder = Builder::default();
+        builder.point_format.has_color = true;
+
+        let mut writer = Writer::new(Cursor::new(Vec::new()), builder.into_header().unwrap()).unwrap();
+
+        (0..count).for_each(|i| {
+            writer.write(Point {
+                x: i as f64,
+                y: 0.,
+                z: 0.,
+                color: Some(Color::new(i as u16, 0, 0)),
+                classification: Classification::new(i as u8 % 3).unwrap(),
+                ..Default::default()
+            }).unwrap();
+        });
+
+        writer.close().unwrap();
+        writer.into_inner().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_las_batches() {
+        let reader = las::Reader::new(Cursor::new(las_buf(10))).unwrap();
+
+        let batches = PointCloud::<OrderedFloat<f64>, u8, u16>::las_batches(reader, 4)
+            .map(|pc| pc.unwrap().into_points())
+            .collect::<Vec<_>>();
+
+        assert_eq!(batches.iter().map(|points| points.len()).collect::<Vec<_>>(), vec![4, 4, 2]);
+
+        let (point, color) = batches[2][1];
+        assert_eq!(point[0], OrderedFloat(9.));
+        assert_eq!(color[0], 9);
+    }
+
+    struct AttributeOptions {}
+
+    impl VoxelizerOption for AttributeOptions {
+        type InPoint = OrderedFloat<f64>;
+        type OutPoint = i64;
+        type Color = u16;
+        type Weight = u8;
+        type ColorPool = u32;
+        type CalcVC = HMap3DVoxelCollection<Self::OutPoint, Self::Weight, Self::ColorPool, FxBuildHasher>;
+        type OutVC = HMap3DVoxelCollection<Self::OutPoint, Self::Weight, Self::Color, FxBuildHasher>;
+    }
+
+    type AttributeVoxelizer = LasAttributeVoxelizer<SimpleVoxelizer<AttributeOptions>, AttributeOptions>;
+
+    #[test]
+    fn test_las_attribute_voxelizer() {
+        // x = 0.5, 1.5, ..., 10.5の点が、分解能2で2点ずつボクセルにまとめられる
+        let reader = las::Reader::new(Cursor::new(las_buf(11))).unwrap();
+        let batches = PointCloud::<OrderedFloat<f64>, u8, u16>::las_batches(reader, 4).with_attributes();
+
+        let mut voxelizer = AttributeVoxelizer::new(Resolution::Mater(2.));
+        voxelizer.voxelizer_mut().set_min_weight(2);
+
+        batches.for_each(|cloud| {
+            voxelizer.add(cloud.unwrap().transform(|point| point + Point3D::new([0.5, 0., 0.])));
+        });
+
+        let (vc, field) = voxelizer.finish();
+        let voxels = vc.to_vec();
+
+        // 1点のみのボクセルは出力されず、属性も含まれない
+        assert_eq!(voxels.len(), 5);
+        assert_eq!(field.len(), 5);
+        assert!(voxels.iter().all(|(point, _)| field[point].point_count() == 2));
+
+        // 分類コード0と1の点を含むボクセル
+        let voxel = &field[&Point3D::new([0, 0, 0])];
+        assert_eq!(voxel.majority_classification(), Some(0));
+        assert_eq!(voxel.classification_counts().len(), 2);
+
+        let reader = las::Reader::new(Cursor::new(las_buf(11))).unwrap();
+        let ground = LasAttributeCloud::from_las_filtered(reader, &ClassificationFilter::Include(vec![2]));
+        assert_eq!(ground.len(), 3);
+        assert!(ground.points().iter().all(|(_, _, attribute)| attribute.classification == 2));
+    }
+
+    #[test]
+    fn test_las_attribute_voxelizer_map_tile() {
+        // 経緯度(ラジアン)に変換した点を、ズームレベル17のピクセル座標でボクセル化する
+        let reader = las::Reader::new(Cursor::new(las_buf(11))).unwrap();
+        let cloud = LasAttributeCloud::from_las(reader).transform(|point| {
+            Point3D::new([139.7649308_f64.to_radians() + point[0] * 1e-6, 35.6812405_f64.to_radians(), point[2]])
+        });
+
+        let mut voxelizer = LasAttributeVoxelizer::<MapTileVoxelizer<MapTileVoxelizerDefaultOptions>, MapTileVoxelizerDefaultOptions>::new(Resolution::Tile { zoom_lv: ZoomLv::Lv17 });
+        voxelizer.add(cloud);
+
+        // 点の追加後にオフセットを変更しても、属性は出力されるボクセルと対応する
+        voxelizer.voxelizer_mut().fit_offset_to_min_tile();
+
+        let (vc, field) = voxelizer.finish();
+        let voxels = vc.to_vec();
+
+        assert!(voxels.len() > 1);
+        assert_eq!(field.len(), voxels.len());
+        assert!(voxels.iter().all(|(point, voxel)| field[point].point_count() == voxel.weight as u32));
+    }
+
+    #[test]
+    fn test_classification_filter() {
+        let reader = las::Reader::new(Cursor::new(las_buf(10))).unwrap();
+        let ground = PointCloud::<OrderedFloat<f64>, u8, u16>::from_las_filtered(reader, &ClassificationFilter::ground()).into_points();
+
+        assert_eq!(ground.iter().map(|(point, _)| point[0].into_inner()).collect::<Vec<_>>(), vec![2., 5., 8.]);
+
+        let reader = las::Reader::new(Cursor::new(las_buf(10))).unwrap();
+        let counts = PointCloud::<OrderedFloat<f64>, u8, u16>::las_batches(reader, 4)
+            .classification_filter(ClassificationFilter::Exclude(vec![0]))
+            .map(|pc| pc.unwrap().into_points().len())
+            .collect::<Vec<_>>();
+
+        assert_eq!(counts, vec![2, 3, 1]);
+    }
 }
@@ -36,6 +36,16 @@ pub mod glb;
 pub mod ply;
 /// ボクセル化された点群にメッシュを貼るためのモジュール。
 pub mod mesh;
+/// タイルごとのボクセルから3D Tilesのタイルセットを生成するためのモジュールです。
+pub mod tileset;
+/// MagicaVoxelの.voxファイルを読み書きするためのモジュールです。
+pub mod vox;
+/// obj/stl/gltfの三角形メッシュを読み込み、ボクセル化するためのモジュールです。
+pub mod triangle_mesh;
+/// 2時期のボクセルデータを比較し、変化を検出するためのモジュールです。
+pub mod change;
+/// 点群から外れ値を取り除くためのモジュールです。
+pub mod outlier;
 
 /// lasファイルから点群を読むためのモジュールです。
 /// 使用するには`las`featureを有効にしてください。
@@ -1,6 +1,6 @@
 use bitflags::bitflags;
 use dashmap::DashMap;
-use fxhash::FxBuildHasher;
+use fxhash::{FxBuildHasher, FxHashMap, FxHashSet};
 use indexmap::IndexSet;
 use meshopt::{simplify_decoder, SimplifyOptions};
 use num::cast::AsPrimitive;
@@ -15,6 +15,8 @@ pub struct VoxelMesh<P: Int, C: UInt> {
     pub(crate) offset: Point3D<P>,
     pub(crate) points: IndexSet<Point3D<P>, FxBuildHasher>,
     pub(crate) faces: DashMap<Color<C>, Vec<usize>, FxBuildHasher>,
+    // `points`と同じ順序の頂点色。頂点ごとの色を持たないメッシュでは空になる
+    pub(crate) vertex_colors: Vec<Color<C>>,
     pub(crate) resolution: f64,
 }
 
@@ -28,13 +30,14 @@ where
     /// 連続した同色の平面ごとに簡略化を行います。
     pub fn simplify(self) -> Self
     {
-        let VoxelMesh { points, faces, bounds, offset, resolution, .. } = self;
+        let VoxelMesh { points, faces, vertex_colors, bounds, offset, resolution } = self;
 
         let point_f32: Vec<[f32; 3]> = points.iter()
             .map(|point| point.as_::<f32>().data)
             .collect();
 
         let mut new_points = IndexSet::<Point3D<P>, FxBuildHasher>::with_hasher(Default::default());
+        let mut new_vertex_colors = Vec::new();
 
         let simplified_points = faces.into_iter().map(|(color, indices)| {
             let indices: Vec<u32> = indices.into_iter()
@@ -42,7 +45,15 @@ where
 
             let new_indices = simplify_decoder(&indices, &point_f32, 0, 0.05, SimplifyOptions::all(), None)
                 .into_iter().map(|i| {
-                new_points.insert_full(points[i as usize]).0
+                let (index, inserted) = new_points.insert_full(points[i as usize]);
+
+                if inserted {
+                    if let Some(&color) = vertex_colors.get(i as usize) {
+                        new_vertex_colors.push(color);
+                    }
+                }
+
+                index
             }).collect::<Vec<_>>();
 
             (color, new_indices)
@@ -54,6 +65,23 @@ where
             offset,
             points: new_points,
             faces: simplified_points,
+            vertex_colors: new_vertex_colors,
+            resolution,
+        }
+    }
+}
+
+impl<P: Int, C: UInt> VoxelMesh<P, C> {
+    // すべての頂点から`origin`を減算し、オフセットを原点に戻す
+    pub(crate) fn shift_origin(self, origin: Point3D<P>) -> Self {
+        let VoxelMesh { bounds, points, faces, vertex_colors, resolution, .. } = self;
+
+        VoxelMesh {
+            bounds: (bounds.0 - origin, bounds.1 - origin),
+            offset: Point3D::default(),
+            points: points.into_iter().map(|point| point - origin).collect(),
+            faces,
+            vertex_colors,
             resolution,
         }
     }
@@ -89,6 +117,31 @@ bitflags! {
 
 
 
+// 面の向き、面に垂直な軸、単位面を構成する2つの三角形の頂点の相対座標
+type UnitFace = (ValidSide, usize, [(i32, i32, i32); 6]);
+
+// `neighbor`の添字と順序を揃える必要がある
+const UNIT_FACES: [UnitFace; 6] = [
+    (ValidSide::LEFT, 0, [(0, 0, 0), (0, 0, 1), (0, 1, 1), (0, 1, 1), (0, 1, 0), (0, 0, 0)]),
+    (ValidSide::RIGHT, 0, [(1, 0, 0), (1, 1, 0), (1, 1, 1), (1, 1, 1), (1, 0, 1), (1, 0, 0)]),
+    (ValidSide::BOTTOM, 2, [(0, 0, 0), (0, 1, 0), (1, 1, 0), (1, 1, 0), (1, 0, 0), (0, 0, 0)]),
+    (ValidSide::TOP, 2, [(0, 0, 1), (1, 0, 1), (1, 1, 1), (1, 1, 1), (0, 1, 1), (0, 0, 1)]),
+    (ValidSide::BACK, 1, [(0, 0, 0), (1, 0, 0), (1, 0, 1), (1, 0, 1), (0, 0, 1), (0, 0, 0)]),
+    (ValidSide::FRONT, 1, [(1, 1, 1), (1, 1, 0), (0, 1, 0), (0, 1, 0), (0, 1, 1), (1, 1, 1)]),
+];
+
+// `UNIT_FACES`の`i`番目の面と接する隣接ボクセルの座標を返す
+fn neighbor<P: Int>(point: Point3D<P>, i: usize) -> Option<Point3D<P>> {
+    match i {
+        0 => point.left(),
+        1 => point.right(),
+        2 => point.bottom(),
+        3 => point.top(),
+        4 => point.back(),
+        _ => point.front(),
+    }
+}
+
 /// ボクセルメッシュを生成するための構造体です。
 pub struct Mesher;
 
@@ -132,15 +185,9 @@ impl Mesher
                 point[2] == min[2] || point[2] == max[2]
         };
 
-        vc.to_points().into_iter().for_each(|(point, color)| {
-            let unit_faces = [
-                (valid_side.contains(ValidSide::LEFT), [(0, 0, 0), (0, 0, 1), (0, 1, 1), (0, 1, 1), (0, 1, 0), (0, 0, 0)], is_required(point.left())),
-                (valid_side.contains(ValidSide::RIGHT), [(1, 0, 0), (1, 1, 0), (1, 1, 1), (1, 1, 1), (1, 0, 1), (1, 0, 0)], is_required(point.right())),
-                (valid_side.contains(ValidSide::BOTTOM), [(0, 0, 0), (0, 1, 0), (1, 1, 0), (1, 1, 0), (1, 0, 0), (0, 0, 0)], is_required(point.bottom())),
-                (valid_side.contains(ValidSide::TOP), [(0, 0, 1), (1, 0, 1), (1, 1, 1), (1, 1, 1), (0, 1, 1), (0, 0, 1)], is_required(point.top())),
-                (valid_side.contains(ValidSide::BACK), [(0, 0, 0), (1, 0, 0), (1, 0, 1), (1, 0, 1), (0, 0, 1), (0, 0, 0)], is_required(point.back())),
-                (valid_side.contains(ValidSide::FRONT), [(1, 1, 1), (1, 1, 0), (0, 1, 0), (0, 1, 0), (0, 1, 1), (1, 1, 1)], is_required(point.front())),
-            ].into_iter()
+        vc.iter_points().for_each(|(point, color)| {
+            let unit_faces = UNIT_FACES.into_iter().enumerate()
+                .map(|(i, (side, _axis, delta))| (valid_side.contains(side), delta, is_required(neighbor(point, i))))
                 .filter(|&(valid, _, required)| valid && required)
                 .filter_map(|(_, delta, _)| {
                     let vertices = delta.into_iter().map(|(dx, dy, dz)| {
@@ -167,4 +214,390 @@ impl Mesher
 
         mesh
     }
+
+    /// 同一平面上で隣接する同色の面を最大の長方形に結合しながらボクセルメッシュを生成します。
+    /// [`Mesher::meshing`]と比べて三角形の数を大幅に削減できます。
+    /// `valid_side`の扱いは[`Mesher::meshing`]と同じで、結合前の単位面ごとに判定します。
+    ///
+    /// 結合された面の辺上には他の面の頂点が乗ることがあります(T字接合)。
+    pub fn greedy_meshing<P, W, C, VCF>(mut vc: VCF, valid_side: ValidSide) -> VoxelMesh<P, C>
+    where
+        P: Int + AsPrimitive<i32>,
+        W: UInt + AsPrimitive<C>,
+        C: UInt + AsPrimitive<W>,
+        VCF: VoxelCollection<P, W, C>,
+        i32: AsPrimitive<P>,
+    {
+        let mut mesh = VoxelMesh {
+            bounds: vc.get_bounds(),
+            offset: vc.get_offset(),
+            resolution: vc.get_resolution(),
+            ..Default::default()
+        };
+
+        // ボクセルのAABBから頂点のAABBにったため
+        mesh.bounds.1 += P::one();
+
+        let on_border = |point: Point3D<P>| -> bool{
+            let (min, max) = mesh.bounds;
+
+            point[0] == min[0] || point[0] == max[0] ||
+                point[1] == min[1] || point[1] == max[1] ||
+                point[2] == min[2] || point[2] == max[2]
+        };
+
+        // (面の向き, 面に垂直な軸の座標, 色)ごとに、露出している単位面の平面座標を集める
+        let mut planes = FxHashMap::<(usize, P, Color<C>), FxHashSet<(P, P)>>::default();
+
+        vc.iter_points().for_each(|(point, color)| {
+            UNIT_FACES.into_iter().enumerate().for_each(|(i, (side, axis, delta))| {
+                if !valid_side.contains(side) {
+                    return;
+                }
+
+                if let Some(neighbor) = neighbor(point, i) {
+                    if vc.has(&neighbor) {
+                        return;
+                    }
+                }
+
+                if !valid_side.contains(ValidSide::BORDER) && delta.into_iter().all(|(dx, dy, dz)| {
+                    on_border(point + Point3D::new([dx, dy, dz]).as_())
+                }) {
+                    return;
+                }
+
+                let (u, v) = Self::plane_axes(axis);
+
+                planes.entry((i, point[axis], color)).or_default().insert((point[u], point[v]));
+            });
+        });
+
+        planes.into_iter().for_each(|((i, depth, color), mut cells)| {
+            let (_, axis, delta) = UNIT_FACES[i];
+            let (u_axis, v_axis) = Self::plane_axes(axis);
+
+            let mut sorted = cells.iter().copied().collect::<Vec<_>>();
+            sorted.sort_unstable_by_key(|&(u, v)| (v, u));
+
+            let mut vertex_indices = Vec::new();
+
+            sorted.into_iter().for_each(|(u0, v0)| {
+                if !cells.contains(&(u0, v0)) {
+                    return;
+                }
+
+                // u方向に伸ばせるだけ伸ばす
+                let mut u1 = u0;
+                while let Some(next) = u1.checked_add(&P::one()) {
+                    if !cells.contains(&(next, v0)) {
+                        break;
+                    }
+                    u1 = next;
+                }
+
+                // 同じ幅の行が続く限りv方向に伸ばす
+                let mut v1 = v0;
+                while let Some(next) = v1.checked_add(&P::one()) {
+                    if !Self::range(u0, u1).all(|u| cells.contains(&(u, next))) {
+                        break;
+                    }
+                    v1 = next;
+                }
+
+                Self::range(v0, v1).for_each(|v| {
+                    Self::range(u0, u1).for_each(|u| {
+                        cells.remove(&(u, v));
+                    });
+                });
+
+                let mut origin = Point3D::<P>::default();
+                origin[axis] = depth;
+                origin[u_axis] = u0;
+                origin[v_axis] = v0;
+
+                let mut size = Point3D::<P>::from(P::one());
+                size[u_axis] = u1 - u0 + P::one();
+                size[v_axis] = v1 - v0 + P::one();
+
+                vertex_indices.extend(delta.into_iter().map(|(dx, dy, dz)| {
+                    let vertex = origin + Point3D::new([dx, dy, dz]).as_() * size;
+                    mesh.points.insert_full(vertex).0
+                }));
+            });
+
+            mesh.faces.entry(color).or_default().extend(vertex_indices);
+        });
+
+        mesh
+    }
+
+    // 指定した軸に垂直な平面を張る2つの軸を返す
+    fn plane_axes(axis: usize) -> (usize, usize) {
+        match axis {
+            0 => (1, 2),
+            1 => (0, 2),
+            _ => (0, 1),
+        }
+    }
+
+    // `start`から`end`までの値を順に返す
+    // `P`は`Step`を実装していないため、範囲演算子の代わりに使用する
+    fn range<P: Int>(start: P, end: P) -> impl Iterator<Item=P> {
+        std::iter::successors(Some(start), move |&p| {
+            if p < end { Some(p + P::one()) } else { None }
+        })
+    }
+}
+
+/// ボクセルの集合から滑らかな等値面のメッシュを生成するための構造体です。
+/// naive surface netsを用いて、ボクセルの占有状態が変化する位置に頂点を配置します。
+pub struct SmoothMesher;
+
+impl SmoothMesher
+{
+    /// 頂点座標を表現するために、1ボクセルを何分割するかを表します。
+    /// 出力される[`VoxelMesh`]の座標値とオフセットはこの値倍され、分解能はこの値で割られます。
+    pub const SUBDIVISION: i64 = 8;
+
+    /// 滑らかなボクセルメッシュを生成します。
+    ///
+    /// 頂点色は周囲のボクセルの色の平均とし、[`VoxelMesh`]の頂点ごとに保持します。
+    /// 面は四角形を張ったボクセルの色ごとにまとめられるため、頂点色を使わない出力では[`Mesher::meshing`]と同じく面単位の色になります。
+    /// 補間された色を出力するには[`crate::glb::ColorOutput::VertexColor`]を指定してglbを出力してください。
+    /// 出力される[`VoxelMesh`]の座標値は[`SmoothMesher::SUBDIVISION`]倍されるため、`P`はその分の値を表現できる必要があります。
+    pub fn meshing<P, W, C, VCF>(vc: VCF) -> VoxelMesh<P, C>
+    where
+        P: Int + AsPrimitive<i64>,
+        W: UInt + AsPrimitive<C>,
+        C: UInt + AsPrimitive<W> + AsPrimitive<f64>,
+        VCF: VoxelCollection<P, W, C>,
+        i64: AsPrimitive<P>,
+        f64: AsPrimitive<C>,
+    {
+        let n = Self::SUBDIVISION;
+
+        let mut mesh = VoxelMesh {
+            offset: (vc.get_offset().as_::<i64>() * n).as_(),
+            resolution: vc.get_resolution() / n as f64,
+            ..Default::default()
+        };
+
+        let voxels = vc.iter_points()
+            .map(|(point, color)| (point.as_::<i64>(), color.as_::<f64>()))
+            .collect::<FxHashMap<_, _>>();
+
+        if voxels.is_empty() {
+            return mesh;
+        }
+
+        // 占有されたボクセルを頂点に含む立方体(セル)ごとに、等値面上の頂点とその色を計算する
+        let corners = Self::corners();
+
+        let cells = voxels.keys()
+            .flat_map(|&point| corners.iter().map(move |&corner| point - corner))
+            .collect::<FxHashSet<_>>();
+
+        let vertices = cells.into_iter().filter_map(|cell| {
+            let mut sum = Point3D::<f64>::default();
+            let mut crossings = 0;
+
+            corners.iter().for_each(|&a| {
+                (0..3).for_each(|axis| {
+                    if a[axis] != 0 {
+                        return;
+                    }
+
+                    let mut b = a;
+                    b[axis] = 1;
+
+                    if voxels.contains_key(&(cell + a)) != voxels.contains_key(&(cell + b)) {
+                        // 隣接するボクセルの中心を結ぶ辺の中点
+                        sum += ((cell + a) * n + n / 2 + b * (n / 2) - a * (n / 2)).as_::<f64>();
+                        crossings += 1;
+                    }
+                });
+            });
+
+            if crossings == 0 {
+                return None;
+            }
+
+            let colors = corners.iter().filter_map(|&corner| voxels.get(&(cell + corner))).collect::<Vec<_>>();
+            let color = colors.iter().fold(Color::<f64>::default(), |acc, &&c| acc + c) / colors.len() as f64;
+
+            let position = (sum / crossings as f64).batch(|a| a.round()).as_::<i64>();
+
+            Some((cell, (position, color)))
+        }).collect::<FxHashMap<_, _>>();
+
+        // 占有状態が異なる2つのボクセルの間に、その辺を共有する4つのセルの頂点を結んだ四角形を張る
+        voxels.iter().for_each(|(&point, &color)| {
+            let color = color.batch(|a| a.round()).as_::<C>();
+
+            (0..3).for_each(|axis| {
+                let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+
+                [1, -1].into_iter().for_each(|sign| {
+                    let mut neighbor = point;
+                    neighbor[axis] += sign;
+
+                    if voxels.contains_key(&neighbor) {
+                        return;
+                    }
+
+                    let mut base = point;
+                    base[axis] = point[axis].min(neighbor[axis]);
+
+                    let mut ring = [(1, 1), (0, 1), (0, 0), (1, 0)].map(|(du, dv)| {
+                        let mut cell = base;
+                        cell[u] -= du;
+                        cell[v] -= dv;
+                        vertices[&cell]
+                    });
+
+                    // 法線が常にボクセルの外側を向くように頂点の順序を揃える
+                    if sign < 0 {
+                        ring.reverse();
+                    }
+
+                    let ring = ring.map(|(position, vertex_color)| {
+                        let (index, inserted) = mesh.points.insert_full(position.as_());
+
+                        if inserted {
+                            mesh.vertex_colors.push(vertex_color.batch(|a| a.round()).as_());
+                        }
+
+                        index
+                    });
+
+                    mesh.faces.entry(color).or_default()
+                        .extend([ring[0], ring[1], ring[2], ring[2], ring[3], ring[0]]);
+                });
+            });
+        });
+
+        let points = mesh.points.iter().copied().collect::<Vec<_>>();
+        mesh.bounds = Point3D::calc_bounds(&points);
+
+        mesh
+    }
+
+    // セルを構成する8つのボクセルの相対座標
+    fn corners() -> [Point3D<i64>; 8] {
+        [
+            [0, 0, 0], [1, 0, 0], [0, 1, 0], [1, 1, 0],
+            [0, 0, 1], [1, 0, 1], [0, 1, 1], [1, 1, 1],
+        ].map(Point3D::new)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fxhash::FxBuildHasher;
+
+    use crate::collection::{HMap3DVoxelCollection, VoxelCollection};
+    use crate::element::{Color, Point3D};
+    use crate::mesh::{Mesher, SmoothMesher, ValidSide};
+
+    type TestVC = HMap3DVoxelCollection<i32, u8, u8, FxBuildHasher>;
+
+    fn slab(color_of: fn(i32) -> Color<u8>) -> TestVC {
+        let points = (0..3).flat_map(|x| {
+            (0..2).map(move |y| (Point3D::new([x, y, 0]), color_of(x)))
+        }).collect();
+
+        TestVC::builder().points(points).build()
+    }
+
+    #[test]
+    fn test_greedy_meshing_merges_coplanar_faces() {
+        let mesh = Mesher::greedy_meshing(slab(|_| Color::new([255, 0, 0])), ValidSide::all());
+
+        // 3x2x1の直方体は6つの長方形(12個の三角形)になる
+        let indices = mesh.faces.iter().map(|entry| entry.value().len()).sum::<usize>();
+        assert_eq!(indices, 6 * 6);
+        assert_eq!(mesh.points.len(), 8);
+
+        let unit_mesh = Mesher::meshing(slab(|_| Color::new([255, 0, 0])), ValidSide::all());
+        let unit_indices = unit_mesh.faces.iter().map(|entry| entry.value().len()).sum::<usize>();
+        assert_eq!(unit_indices, 22 * 6);
+    }
+
+    #[test]
+    fn test_greedy_meshing_keeps_colors_apart() {
+        let mesh = Mesher::greedy_meshing(slab(|x| Color::new([x as u8, 0, 0])), ValidSide::TOP | ValidSide::BORDER);
+
+        assert_eq!(mesh.faces.len(), 3);
+        mesh.faces.iter().for_each(|entry| assert_eq!(entry.value().len(), 6));
+    }
+
+    #[test]
+    fn test_greedy_meshing_honors_border_flag() {
+        let mesh = Mesher::greedy_meshing(slab(|_| Color::new([255, 0, 0])), ValidSide::all() - ValidSide::BORDER);
+
+        assert!(mesh.faces.is_empty());
+    }
+
+    #[test]
+    fn test_smooth_meshing_is_closed() {
+        let mesh = SmoothMesher::meshing(slab(|_| Color::new([255, 0, 0])));
+
+        assert_eq!(mesh.resolution, 1. / SmoothMesher::SUBDIVISION as f64);
+
+        // 閉じた多様体であれば、すべての有向辺はちょうど1回ずつ、逆向きの辺と対になって現れる
+        let mut edges = fxhash::FxHashMap::<(usize, usize), usize>::default();
+        mesh.faces.iter().for_each(|entry| {
+            entry.value().chunks(3).for_each(|t| {
+                [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])].into_iter().for_each(|edge| {
+                    *edges.entry(edge).or_default() += 1;
+                });
+            });
+        });
+
+        assert!(!edges.is_empty());
+        assert!(edges.iter().all(|(&(a, b), &count)| count == 1 && edges.get(&(b, a)) == Some(&1)));
+    }
+
+    #[test]
+    fn test_smooth_meshing_faces_point_outward() {
+        let mesh = SmoothMesher::meshing(slab(|_| Color::new([255, 0, 0])));
+
+        let points = mesh.points.iter().map(|point| point.as_::<f64>()).collect::<Vec<_>>();
+        let center = points.iter().fold(Point3D::<f64>::default(), |acc, &p| acc + p) / points.len() as f64;
+
+        // 凸な立体なので、各三角形の法線は中心から三角形の重心への向きと同じ側を向く
+        mesh.faces.iter().for_each(|entry| {
+            entry.value().chunks(3).for_each(|t| {
+                let [a, b, c] = [points[t[0]], points[t[1]], points[t[2]]];
+                let (e1, e2) = (b - a, c - a);
+                let normal = [
+                    e1[1] * e2[2] - e1[2] * e2[1],
+                    e1[2] * e2[0] - e1[0] * e2[2],
+                    e1[0] * e2[1] - e1[1] * e2[0],
+                ];
+                let outward = (a + b + c) / 3. - center;
+
+                assert!((0..3).map(|i| normal[i] * outward[i]).sum::<f64>() > 0.);
+            });
+        });
+    }
+
+    #[test]
+    fn test_smooth_meshing_interpolates_colors() {
+        let mesh = SmoothMesher::meshing(slab(|x| Color::new([x as u8 * 100, 0, 0])));
+
+        // 面はボクセルの色ごとにまとめられる
+        let mut keys = mesh.faces.iter().map(|entry| entry.key()[0]).collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(keys, vec![0, 100, 200]);
+
+        // 頂点色は周囲のボクセルの色から補間される
+        assert_eq!(mesh.vertex_colors.len(), mesh.points.len());
+
+        let reds = mesh.vertex_colors.iter().map(|color| color[0]).collect::<Vec<_>>();
+
+        assert!(reds.iter().any(|&r| r != 0 && r != 100 && r != 200));
+        assert!(reds.iter().all(|&r| r <= 200));
+    }
 }
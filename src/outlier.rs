@@ -0,0 +1,284 @@
+use anyhow::anyhow;
+use fxhash::FxHashMap;
+use num::cast::AsPrimitive;
+
+use crate::collection::PointCloud;
+use crate::element::{Number, UInt};
+
+/// 点を一辺`cell_size`の格子に分割して管理する空間インデックスです。
+/// 近傍点の検索は、問い合わせた点の周囲の格子に含まれる点のみを走査して行います。
+#[derive(Clone, Debug)]
+pub struct PointIndex {
+    cell_size: f64,
+    points: Vec<[f64; 3]>,
+    cells: FxHashMap<[i64; 3], Vec<usize>>,
+    // 点が存在する格子の範囲
+    cell_bounds: ([i64; 3], [i64; 3]),
+}
+
+impl PointIndex {
+    /// 点の配列から空間インデックスを構築します。
+    /// `cell_size`には、検索半径や点の平均的な間隔と同程度の値を指定してください。
+    ///
+    /// # Errors
+    ///
+    /// + `cell_size`が正の有限な値でない場合、エラーを返します。
+    pub fn new(points: Vec<[f64; 3]>, cell_size: f64) -> Result<Self, anyhow::Error> {
+        if !(cell_size.is_finite() && cell_size > 0.) {
+            return Err(anyhow!("Cell size must be a positive finite number"));
+        }
+
+        let mut cells = FxHashMap::<[i64; 3], Vec<usize>>::default();
+        let mut cell_bounds = ([i64::MAX; 3], [i64::MIN; 3]);
+
+        points.iter().enumerate().for_each(|(i, point)| {
+            let cell = Self::cell_of(point, cell_size);
+
+            (0..3).for_each(|axis| {
+                cell_bounds.0[axis] = cell_bounds.0[axis].min(cell[axis]);
+                cell_bounds.1[axis] = cell_bounds.1[axis].max(cell[axis]);
+            });
+
+            cells.entry(cell).or_default().push(i);
+        });
+
+        Ok(Self { cell_size, points, cells, cell_bounds })
+    }
+
+    fn cell_of(point: &[f64; 3], cell_size: f64) -> [i64; 3] {
+        point.map(|a| (a / cell_size).floor() as i64)
+    }
+
+    fn distance(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+        ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+    }
+
+    // 中心の格子からチェビシェフ距離が`ring`の格子に含まれる点のインデックスを返す
+    fn ring(&self, center: [i64; 3], ring: i64) -> impl Iterator<Item=usize> + '_ {
+        (-ring..=ring).flat_map(move |dx| (-ring..=ring).flat_map(move |dy| (-ring..=ring).map(move |dz| [dx, dy, dz])))
+            .filter(move |delta| delta.iter().any(|a| a.abs() == ring))
+            .filter_map(move |delta| self.cells.get(&[center[0] + delta[0], center[1] + delta[1], center[2] + delta[2]]))
+            .flatten()
+            .copied()
+    }
+
+    // 中心の格子からチェビシェフ距離が`ring`以上の格子に含まれる点のインデックスを返す
+    // 登録されている格子をすべて走査するため、探索範囲の格子が登録されている格子より多い場合に用いる
+    fn rings_from(&self, center: [i64; 3], ring: i64) -> impl Iterator<Item=usize> + '_ {
+        self.cells.iter()
+            .filter(move |(cell, _)| (0..3).map(|axis| (cell[axis] - center[axis]).abs()).max().unwrap() >= ring)
+            .flat_map(|(_, indices)| indices)
+            .copied()
+    }
+
+    // チェビシェフ距離が`ring`以下の格子の数が、登録されている格子の数を超えるかどうか
+    fn exceeds_cells(&self, ring: i64) -> bool {
+        ring.checked_mul(2).and_then(|a| a.checked_add(1)).and_then(|a| a.checked_pow(3))
+            .is_none_or(|count| count as usize > self.cells.len())
+    }
+
+    /// 登録されている点の数を返します。
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    /// 点が1つも登録されていない場合に`true`を返します。
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// `point`からの距離が`radius`以下の点のインデックスを返します。
+    pub fn within_radius(&self, point: [f64; 3], radius: f64) -> Vec<usize> {
+        let center = Self::cell_of(&point, self.cell_size);
+        let rings = (radius / self.cell_size).ceil() as i64;
+
+        let candidates: Box<dyn Iterator<Item=usize>> = if self.exceeds_cells(rings) {
+            Box::new(self.rings_from(center, 0))
+        } else {
+            Box::new((0..=rings).flat_map(|ring| self.ring(center, ring)))
+        };
+
+        candidates.filter(|&i| Self::distance(&self.points[i], &point) <= radius).collect()
+    }
+
+    /// `point`に近い順に最大`k`個の点のインデックスと距離を返します。
+    pub fn nearest(&self, point: [f64; 3], k: usize) -> Vec<(usize, f64)> {
+        if k == 0 || self.points.is_empty() {
+            return Vec::new();
+        }
+
+        let center = Self::cell_of(&point, self.cell_size);
+
+        // すべての点を含む格子まで探索すれば十分
+        let max_ring = (0..3).map(|axis| {
+            (center[axis] - self.cell_bounds.0[axis]).abs().max((self.cell_bounds.1[axis] - center[axis]).abs())
+        }).max().unwrap();
+
+        let mut nearest = Vec::<(usize, f64)>::with_capacity(k + 1);
+        let push = |nearest: &mut Vec<(usize, f64)>, i: usize| {
+            let distance = Self::distance(&self.points[i], &point);
+
+            if nearest.len() < k || distance < nearest[k - 1].1 {
+                let position = nearest.partition_point(|&(_, d)| d <= distance);
+                nearest.insert(position, (i, distance));
+                nearest.truncate(k);
+            }
+        };
+
+        for ring in 0..=max_ring {
+            // 孤立した点の周囲は空の格子ばかりになるため、残りの格子をまとめて走査する
+            if self.exceeds_cells(ring) {
+                self.rings_from(center, ring).for_each(|i| push(&mut nearest, i));
+                break;
+            }
+
+            self.ring(center, ring).for_each(|i| push(&mut nearest, i));
+
+            // 次の格子以降に含まれる点は、少なくとも`ring * cell_size`だけ離れている
+            if nearest.len() == k && nearest[k - 1].1 <= ring as f64 * self.cell_size {
+                break;
+            }
+        }
+
+        nearest
+    }
+}
+
+impl<P, W, C> PointCloud<P, W, C>
+where
+    P: Number + AsPrimitive<f64>,
+    W: UInt,
+    C: UInt,
+{
+    // 点の座標と、点の平均的な間隔の推定値を返す
+    fn index_points(&self) -> (Vec<[f64; 3]>, f64) {
+        let points = self.field.iter().map(|(point, _)| point.data.map(|a| a.as_())).collect::<Vec<[f64; 3]>>();
+
+        // 遠く離れた少数の外れ値で大きくならないように、各軸の5から95パーセンタイルの範囲を用いる
+        let extent = (0..3).map(|axis| {
+            let mut values = points.iter().map(|point| point[axis]).filter(|a| a.is_finite()).collect::<Vec<_>>();
+
+            if values.is_empty() {
+                return 0.;
+            }
+
+            let last = values.len() - 1;
+            let (low, high) = (last * 5 / 100, last * 95 / 100);
+
+            let min = *values.select_nth_unstable_by(low, f64::total_cmp).1;
+            let max = *values.select_nth_unstable_by(high, f64::total_cmp).1;
+            max - min
+        }).fold(0., f64::max);
+
+        // 点が平面状に分布していても小さくなりすぎないように、最大の辺の長さから推定する
+        let spacing = extent / (points.len() as f64 * 0.9).cbrt();
+
+        (points, if spacing > 0. { spacing } else { 1. })
+    }
+
+    // 残す点を`true`とした配列に従って、点を削除する
+    fn retain_by_mask(&mut self, keep: Vec<bool>) {
+        let mut keep = keep.into_iter();
+        self.field.retain(|_| keep.next().unwrap());
+        self.bounds = None;
+    }
+
+    /// 統計的外れ値除去を行います。
+    /// 各点について最も近い`k`個の点までの距離の平均を求め、その値がすべての点における平均から標準偏差の`std_ratio`倍を超えて離れている点を削除します。
+    /// ボクセル化の前に、`Voxelizer::add`に渡す点群に対して使用することを想定しています。
+    pub fn remove_statistical_outliers(&mut self, k: usize, std_ratio: f64) {
+        if k == 0 || self.field.len() <= 1 {
+            return;
+        }
+
+        let (points, spacing) = self.index_points();
+        let index = PointIndex::new(points, spacing).unwrap();
+
+        let mean_distances = (0..index.len()).map(|i| {
+            // 自分自身を除いた近傍点
+            let neighbors = index.nearest(index.points[i], k + 1).into_iter()
+                .filter(|&(j, _)| j != i)
+                .take(k)
+                .collect::<Vec<_>>();
+
+            neighbors.iter().map(|(_, distance)| distance).sum::<f64>() / neighbors.len() as f64
+        }).collect::<Vec<_>>();
+
+        let n = mean_distances.len() as f64;
+        let mean = mean_distances.iter().sum::<f64>() / n;
+        let std = (mean_distances.iter().map(|d| (d - mean).powi(2)).sum::<f64>() / n).sqrt();
+        let threshold = mean + std_ratio * std;
+
+        self.retain_by_mask(mean_distances.into_iter().map(|d| d <= threshold).collect());
+    }
+
+    /// 半径外れ値除去を行います。
+    /// 距離が`radius`以下の点(自分自身を除く)が`min_neighbors`個未満の点を削除します。
+    /// ボクセル化の前に、`Voxelizer::add`に渡す点群に対して使用することを想定しています。
+    pub fn remove_radius_outliers(&mut self, radius: f64, min_neighbors: usize) {
+        let (points, spacing) = self.index_points();
+        let cell_size = if radius.is_finite() && radius > 0. { radius } else { spacing };
+        let index = PointIndex::new(points, cell_size).unwrap();
+
+        // 検索結果には自分自身が含まれる
+        let keep = (0..index.len()).map(|i| {
+            index.within_radius(index.points[i], radius).len() > min_neighbors
+        }).collect();
+
+        self.retain_by_mask(keep);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ordered_float::OrderedFloat;
+
+    use crate::collection::{PointCloud, VoxelCollection};
+    use crate::element::{Color, Point3D};
+    use crate::outlier::PointIndex;
+
+    fn noisy_cloud() -> PointCloud<OrderedFloat<f64>, u8, u8> {
+        // 0.1間隔の格子状の点と、遠く離れた1点
+        let mut points = (0..1000).map(|i| {
+            let point = [i / 100, i / 10 % 10, i % 10].map(|a| OrderedFloat(a as f64 * 0.1));
+            (Point3D::new(point), Color::new([10, 10, 10]))
+        }).collect::<Vec<_>>();
+
+        // 外れ値によって格子の大きさが決まらないように、十分に遠くに配置する
+        points.push((Point3D::new([1e4, 1e4, 1e4].map(OrderedFloat)), Color::new([255, 0, 0])));
+
+        PointCloud::builder().points(points).build()
+    }
+
+    #[test]
+    fn test_point_index() {
+        let points = vec![[0., 0., 0.], [1., 0., 0.], [0., 2., 0.], [10., 10., 10.]];
+        let index = PointIndex::new(points.clone(), 0.5).unwrap();
+
+        let mut within = index.within_radius([0., 0., 0.], 1.5);
+        within.sort();
+        assert_eq!(within, vec![0, 1]);
+
+        let nearest = index.nearest([0.1, 0., 0.], 3);
+        assert_eq!(nearest.iter().map(|(i, _)| *i).collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert_eq!(index.nearest([0., 0., 0.], 10).len(), 4);
+        assert_eq!(index.nearest([1e6, 0., 0.], 1)[0].0, 3);
+        assert_eq!(index.within_radius([0., 0., 0.], f64::INFINITY).len(), 4);
+
+        assert!(PointIndex::new(points.clone(), 0.).is_err());
+        assert!(PointIndex::new(points, f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_remove_outliers() {
+        let mut pc = noisy_cloud();
+        pc.remove_statistical_outliers(8, 2.);
+        assert_eq!(pc.field.len(), 1000);
+        assert!(pc.iter().all(|(_, voxel)| voxel.color == Color::new([10, 10, 10])));
+
+        let mut pc = noisy_cloud();
+        pc.remove_radius_outliers(0.15, 3);
+        assert_eq!(pc.field.len(), 1000);
+        assert!(!pc.has(&Point3D::new([1e4, 1e4, 1e4].map(OrderedFloat))));
+    }
+}
@@ -1,5 +1,7 @@
-use std::io::{BufReader, Read};
+use std::collections::BTreeMap;
+use std::io::{BufReader, BufWriter, Read, Write};
 
+use anyhow::anyhow;
 use fxhash::FxBuildHasher;
 use indexmap::IndexSet;
 use num::cast::AsPrimitive;
@@ -10,7 +12,7 @@ use ply_rs::ply::Property::{Float, ListUInt, UChar};
 use ply_rs::writer::Writer;
 
 use crate::collection::{PointCloud, VoxelCollection};
-use crate::element::{Color, Int, Point3D, UInt};
+use crate::element::{Color, Int, Number, Point3D, UInt};
 use crate::mesh::VoxelMesh;
 
 /// Plyファイルにおける1つの頂点を表す構造体
@@ -68,26 +70,220 @@ where
     u8: AsPrimitive<W>,
 {
     /// plyファイルから点群を読み込みます。
+    /// 読み込みに失敗した場合はパニックします。エラーを扱いたい場合は[`PlyPoints::from_reader`]を使用してください。
     /// 使用するには`ply`featureを有効にしてください。
     pub fn from_ply<T: Read>(file: T) -> Self {
-        let mut buf_reader = BufReader::new(file);
+        PlyPoints::from_reader(file, PlyReadOption::default())
+            .expect("Failed to read ply")
+            .into_point_cloud()
+    }
+}
+
+/// plyファイルから点群を読み込む際のオプションです。
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PlyReadOption {
+    /// 法線(`nx`, `ny`, `nz`)を読み込むかどうかを指定します。
+    pub normals: bool,
+    /// 座標・色・法線以外のスカラー値のプロパティを読み込むかどうかを指定します。
+    pub scalars: bool,
+}
 
-        let vertex_parser = Parser::<Vertex>::new();
+/// plyファイルから読み込んだ頂点の情報を持つ構造体
+/// 使用するには`ply`featureを有効にしてください。
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlyPoints {
+    /// 頂点の座標です。
+    pub points: Vec<Point3D<OrderedFloat<f64>>>,
+    /// 頂点の色です。色を持たないファイルの場合は`None`になります。
+    pub colors: Option<Vec<Color<u8>>>,
+    /// 頂点の法線です。[`PlyReadOption::normals`]が無効な場合や、法線を持たないファイルの場合は`None`になります。
+    pub normals: Option<Vec<Point3D<OrderedFloat<f32>>>>,
+    /// プロパティ名ごとのスカラー値です。[`PlyReadOption::scalars`]が無効な場合は空になります。
+    pub scalars: BTreeMap<String, Vec<f64>>,
+}
 
-        let header = vertex_parser.read_header(&mut buf_reader).unwrap();
+impl PlyPoints {
+    /// plyファイルの`vertex`要素を読み込みます。
+    ///
+    /// 座標は`float`・`double`を含むすべての数値型に対応します。
+    /// 色は`red`/`green`/`blue`、`r`/`g`/`b`、`diffuse_red`/`diffuse_green`/`diffuse_blue`のいずれかの名前で読み込み、8bitに変換します。
+    /// 整数型の色は型の最大値を、浮動小数点数型の色は1.0を最大の明るさとして扱います。
+    ///
+    /// # Errors
+    ///
+    /// + ファイルの形式が正しくない場合、エラーを返します。
+    /// + `vertex`要素、または`x`/`y`/`z`プロパティが存在しない場合、エラーを返します。
+    /// + 座標・色・法線のプロパティがリスト型の場合、エラーを返します。
+    pub fn from_reader<T: Read>(reader: T, option: PlyReadOption) -> Result<Self, anyhow::Error> {
+        let mut buf_reader = BufReader::new(reader);
+
+        let parser = Parser::<DefaultElement>::new();
+        let header = parser.read_header(&mut buf_reader)?;
+
+        let mut vertices = None;
+
+        // `vertex`要素より前の要素は読み飛ばす
+        for (name, element) in header.elements.iter() {
+            let payload = parser.read_payload_for_element(&mut buf_reader, element, &header)?;
+
+            if name == "vertex" {
+                vertices = Some((element, payload));
+                break;
+            }
+        }
 
+        let (element, vertices) = vertices.ok_or_else(|| anyhow!("Element vertex not found"))?;
 
-        let points = header.elements.iter()
-            .filter(|(_, element)| element.name == "vertex")
-            .flat_map(|(_, element)| {
-                vertex_parser.read_payload_for_element(&mut buf_reader, element, &header).unwrap()
-            }).map(|Vertex { x, y, z, r, g, b }| {
-            let point = Point3D::new([x, y, z]);
-            let color = Color::new([r, g, b]);
-            (point, color)
-        }).collect::<Vec<_>>();
+        let find = |names: &[&'static str]| names.iter().copied().find(|name| element.properties.contains_key(*name));
+
+        let coordinate_keys = ["x", "y", "z"].map(|name| find(&[name]));
+        let [Some(x_key), Some(y_key), Some(z_key)] = coordinate_keys else {
+            return Err(anyhow!("Property x, y or z not found in element vertex"));
+        };
+
+        let color_keys = [
+            ["red", "green", "blue"],
+            ["r", "g", "b"],
+            ["diffuse_red", "diffuse_green", "diffuse_blue"],
+        ].into_iter().find(|keys| keys.iter().all(|key| element.properties.contains_key(*key)));
+
+        let normal_keys = [
+            ["nx", "ny", "nz"],
+            ["normal_x", "normal_y", "normal_z"],
+        ].into_iter().find(|keys| keys.iter().all(|key| element.properties.contains_key(*key)))
+            .filter(|_| option.normals);
+
+        let scalar_keys = if option.scalars {
+            let used = [x_key, y_key, z_key].into_iter()
+                .chain(color_keys.into_iter().flatten())
+                .chain(normal_keys.into_iter().flatten())
+                .collect::<Vec<_>>();
+
+            element.properties.iter()
+                .filter(|(key, property)| !used.contains(&key.as_str()) && matches!(property.data_type, PropertyType::Scalar(_)))
+                .map(|(key, _)| key.clone())
+                .collect::<Vec<_>>()
+        } else { Vec::new() };
+
+        let mut points = Vec::with_capacity(vertices.len());
+        let mut colors = color_keys.map(|_| Vec::with_capacity(vertices.len()));
+        let mut normals = normal_keys.map(|_| Vec::with_capacity(vertices.len()));
+        let mut scalars = scalar_keys.iter().map(|key| (key.clone(), Vec::with_capacity(vertices.len()))).collect::<BTreeMap<_, _>>();
+
+        for vertex in vertices.iter() {
+            let point = [x_key, y_key, z_key].map(|key| scalar_value(vertex, key));
+            let [Ok(x), Ok(y), Ok(z)] = point else {
+                return Err(point.into_iter().find_map(|a| a.err()).unwrap());
+            };
+            points.push(Point3D::new([x, y, z].map(OrderedFloat::from)));
+
+            if let (Some(keys), Some(colors)) = (color_keys, colors.as_mut()) {
+                let color = keys.iter().map(|key| color_value(vertex, key)).collect::<Result<Vec<_>, _>>()?;
+                colors.push(Color::new([color[0], color[1], color[2]]));
+            }
+
+            if let (Some(keys), Some(normals)) = (normal_keys, normals.as_mut()) {
+                let normal = keys.iter().map(|key| scalar_value(vertex, key)).collect::<Result<Vec<_>, _>>()?;
+                normals.push(Point3D::new([normal[0], normal[1], normal[2]].map(|a| OrderedFloat::from(a as f32))));
+            }
+
+            for (key, values) in scalars.iter_mut() {
+                values.push(scalar_value(vertex, key)?);
+            }
+        }
+
+        Ok(Self {
+            points,
+            colors,
+            normals,
+            scalars,
+        })
+    }
+
+    /// 点群に変換します。
+    /// 色を持たない場合、すべての頂点の色は黒になります。
+    pub fn into_point_cloud<W>(self) -> PointCloud<OrderedFloat<f32>, W, u8>
+    where
+        W: UInt + AsPrimitive<u8>,
+        u8: AsPrimitive<W>,
+    {
+        let points = self.points.into_iter().map(|point| point.batch(|a| OrderedFloat::from(a.into_inner() as f32))).collect();
+
+        PointCloud::builder().points(Self::zip_colors(points, self.colors)).build()
+    }
+
+    /// 座標値を倍精度浮動小数点数のまま点群に変換します。
+    /// 平面直角座標系のように、大きな座標値を持つ点群を扱う場合に使用してください。
+    /// 色を持たない場合、すべての頂点の色は黒になります。
+    pub fn into_point_cloud_f64<W>(self) -> PointCloud<OrderedFloat<f64>, W, u8>
+    where
+        W: UInt + AsPrimitive<u8>,
+        u8: AsPrimitive<W>,
+    {
+        PointCloud::builder().points(Self::zip_colors(self.points, self.colors)).build()
+    }
+
+    fn zip_colors<P: Number>(points: Vec<Point3D<P>>, colors: Option<Vec<Color<u8>>>) -> Vec<(Point3D<P>, Color<u8>)> {
+        let colors = colors.unwrap_or_else(|| vec![Color::new([0, 0, 0]); points.len()]);
+
+        points.into_iter().zip(colors).collect()
+    }
+}
+
+// スカラー値のプロパティをf64として取得する
+fn scalar_value(vertex: &DefaultElement, key: &str) -> Result<f64, anyhow::Error> {
+    let value = match vertex.get(key) {
+        Some(Property::Char(v)) => *v as f64,
+        Some(Property::UChar(v)) => *v as f64,
+        Some(Property::Short(v)) => *v as f64,
+        Some(Property::UShort(v)) => *v as f64,
+        Some(Property::Int(v)) => *v as f64,
+        Some(Property::UInt(v)) => *v as f64,
+        Some(Property::Float(v)) => *v as f64,
+        Some(Property::Double(v)) => *v,
+        Some(_) => return Err(anyhow!("Property {} must be a scalar", key)),
+        None => return Err(anyhow!("Property {} not found", key)),
+    };
+
+    Ok(value)
+}
+
+// 色のプロパティを8bitの値として取得する
+fn color_value(vertex: &DefaultElement, key: &str) -> Result<u8, anyhow::Error> {
+    let normalized = match vertex.get(key) {
+        Some(Property::UChar(v)) => return Ok(*v),
+        Some(Property::Char(v)) => *v as f64 / i8::MAX as f64,
+        Some(Property::Short(v)) => *v as f64 / i16::MAX as f64,
+        Some(Property::UShort(v)) => *v as f64 / u16::MAX as f64,
+        Some(Property::Int(v)) => *v as f64 / i32::MAX as f64,
+        Some(Property::UInt(v)) => *v as f64 / u32::MAX as f64,
+        Some(Property::Float(v)) => *v as f64,
+        Some(Property::Double(v)) => *v,
+        Some(_) => return Err(anyhow!("Property {} must be a scalar", key)),
+        None => return Err(anyhow!("Property {} not found", key)),
+    };
+
+    Ok((normalized.clamp(0., 1.) * u8::MAX as f64).round() as u8)
+}
+
+/// plyファイルの形式を表します。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PlyFormat {
+    /// ASCII形式です。
+    Ascii,
+    /// リトルエンディアンのバイナリ形式です。
+    BinaryLittleEndian,
+    /// ビッグエンディアンのバイナリ形式です。
+    BinaryBigEndian,
+}
 
-        Self::builder().points(points).build()
+impl PlyFormat {
+    fn name(&self) -> &'static str {
+        match self {
+            PlyFormat::Ascii => "ascii",
+            PlyFormat::BinaryLittleEndian => "binary_little_endian",
+            PlyFormat::BinaryBigEndian => "binary_big_endian",
+        }
     }
 }
 
@@ -205,4 +401,190 @@ impl PlyStructs {
 
         buf
     }
+
+    /// バイナリ形式のplyファイルのバッファを返します。
+    /// `format`に[`PlyFormat::Ascii`]を指定した場合はASCII形式で出力します。
+    pub fn into_binary_buf(self, format: PlyFormat) -> Vec<u8> {
+        let mut buf = Vec::<u8>::new();
+        // Vec<u8>への書き込みは失敗しない
+        self.to_writer(&mut buf, format).unwrap();
+
+        buf
+    }
+
+    /// 指定した形式でplyファイルを書き込みます。
+    /// ファイル全体のバッファをメモリ上に構築せず、頂点と面を順に書き込みます。
+    ///
+    /// # Errors
+    ///
+    /// + 書き込みに失敗した場合、エラーを返します。
+    pub fn to_writer<T: Write>(&self, writer: T, format: PlyFormat) -> Result<(), anyhow::Error> {
+        let mut writer = BufWriter::new(writer);
+
+        writeln!(writer, "ply\nformat {} 1.0", format.name())?;
+        writeln!(writer, "element vertex {}", self.vertices.len())?;
+        ["x", "y", "z"].iter().try_for_each(|name| writeln!(writer, "property float {}", name))?;
+        ["red", "green", "blue"].iter().try_for_each(|name| writeln!(writer, "property uchar {}", name))?;
+        writeln!(writer, "element face {}", self.faces.len())?;
+        writeln!(writer, "property list uchar uint vertex_indices\nend_header")?;
+
+        match format {
+            PlyFormat::Ascii => {
+                self.vertices.iter().try_for_each(|Vertex { x, y, z, r, g, b }| {
+                    writeln!(writer, "{} {} {} {} {} {}", x, y, z, r, g, b)
+                })?;
+
+                self.faces.iter().try_for_each(|Face { vertex_indices }| {
+                    write!(writer, "{}", vertex_indices.len())?;
+                    vertex_indices.iter().try_for_each(|i| write!(writer, " {}", i))?;
+                    writeln!(writer)
+                })?;
+            }
+            PlyFormat::BinaryLittleEndian => Self::write_binary_payload(&mut writer, &self.vertices, &self.faces, f32::to_le_bytes, u32::to_le_bytes)?,
+            PlyFormat::BinaryBigEndian => Self::write_binary_payload(&mut writer, &self.vertices, &self.faces, f32::to_be_bytes, u32::to_be_bytes)?,
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    fn write_binary_payload<T: Write>(
+        writer: &mut T,
+        vertices: &[Vertex],
+        faces: &[Face],
+        f32_to_bytes: fn(f32) -> [u8; 4],
+        u32_to_bytes: fn(u32) -> [u8; 4],
+    ) -> Result<(), anyhow::Error> {
+        vertices.iter().try_for_each(|Vertex { x, y, z, r, g, b }| {
+            [x, y, z].iter().try_for_each(|a| writer.write_all(&f32_to_bytes(a.into_inner())))?;
+            writer.write_all(&[*r, *g, *b])
+        })?;
+
+        faces.iter().try_for_each(|Face { vertex_indices }| {
+            writer.write_all(&[vertex_indices.len() as u8])?;
+            vertex_indices.iter().try_for_each(|i| writer.write_all(&u32_to_bytes(*i)))
+        })?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ordered_float::OrderedFloat;
+    use ply_rs::parser::Parser;
+
+    use crate::collection::VoxelCollection;
+    use crate::element::{Color, Point3D};
+    use crate::ply::{Face, PlyFormat, PlyPoints, PlyReadOption, PlyStructs, Vertex};
+
+    fn triangle() -> PlyStructs {
+        let vertices = [(0., 0., 0.), (1.5, 0., 0.), (0., -2., 0.25)].into_iter().enumerate().map(|(i, (x, y, z))| {
+            Vertex {
+                x: OrderedFloat(x),
+                y: OrderedFloat(y),
+                z: OrderedFloat(z),
+                r: i as u8 * 100,
+                g: 10,
+                b: 255,
+            }
+        }).collect();
+
+        PlyStructs::new(vertices, vec![Face { vertex_indices: vec![0, 1, 2] }])
+    }
+
+    #[test]
+    fn test_into_binary_buf() {
+        [PlyFormat::BinaryLittleEndian, PlyFormat::BinaryBigEndian, PlyFormat::Ascii].into_iter().for_each(|format| {
+            let expected = triangle();
+            let buf = expected.clone().into_binary_buf(format);
+
+            let mut reader = buf.as_slice();
+            let vertex_parser = Parser::<Vertex>::new();
+            let face_parser = Parser::<Face>::new();
+
+            let header = vertex_parser.read_header(&mut reader).unwrap();
+            let vertices = vertex_parser.read_payload_for_element(&mut reader, &header.elements["vertex"], &header).unwrap();
+            let faces = face_parser.read_payload_for_element(&mut reader, &header.elements["face"], &header).unwrap();
+
+            assert_eq!(vertices, expected.vertices);
+            assert_eq!(faces, expected.faces);
+        });
+    }
+
+    #[test]
+    fn test_read_various_layouts() {
+        let ply = "ply
+format ascii 1.0
+element vertex 2
+property double x
+property double y
+property double z
+property float nx
+property float ny
+property float nz
+property ushort diffuse_red
+property ushort diffuse_green
+property ushort diffuse_blue
+property float intensity
+end_header
+-50000.25 12000.5 3.0 0 0 1 65535 0 32768 0.5
+1 2 3 1 0 0 0 65535 0 1.5
+";
+        let option = PlyReadOption { normals: true, scalars: true };
+        let points = PlyPoints::from_reader(ply.as_bytes(), option).unwrap();
+
+        assert_eq!(points.points[0], Point3D::new([-50000.25, 12000.5, 3.0].map(OrderedFloat::from)));
+        assert_eq!(points.colors, Some(vec![Color::new([255, 0, 128]), Color::new([0, 255, 0])]));
+        assert_eq!(points.normals.unwrap()[1], Point3D::new([1., 0., 0.].map(OrderedFloat::from)));
+        assert_eq!(points.scalars.keys().collect::<Vec<_>>(), vec!["intensity"]);
+        assert_eq!(points.scalars["intensity"], vec![0.5, 1.5]);
+
+        let points = PlyPoints::from_reader(ply.as_bytes(), PlyReadOption::default()).unwrap();
+        assert!(points.normals.is_none());
+        assert!(points.scalars.is_empty());
+    }
+
+    #[test]
+    fn test_read_without_color() {
+        let ply = "ply
+format ascii 1.0
+element vertex 1
+property float x
+property float y
+property float z
+end_header
+1 2 3
+";
+        let points = PlyPoints::from_reader(ply.as_bytes(), PlyReadOption::default()).unwrap();
+        assert!(points.colors.is_none());
+
+        let point_cloud = points.into_point_cloud::<u8>();
+        assert_eq!(point_cloud.into_points(), vec![(Point3D::new([1., 2., 3.].map(OrderedFloat::from)), Color::new([0, 0, 0]))]);
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let missing_z = "ply
+format ascii 1.0
+element vertex 1
+property float x
+property float y
+end_header
+1 2
+";
+        assert!(PlyPoints::from_reader(missing_z.as_bytes(), PlyReadOption::default()).is_err());
+
+        let malformed = "ply
+format ascii 1.0
+element vertex 2
+property float x
+property float y
+property float z
+end_header
+1 2 3
+";
+        assert!(PlyPoints::from_reader(malformed.as_bytes(), PlyReadOption::default()).is_err());
+    }
 }
@@ -0,0 +1,313 @@
+use std::fs::{create_dir_all, File};
+use std::io::Write;
+use std::path::Path;
+
+use coordinate_transformer::{llz2xyz, pixel2ll, ZoomLv};
+use gltf::json::serialize::to_string_pretty;
+use gltf::json::Value;
+use num::cast::AsPrimitive;
+
+use crate::collection::VoxelCollection;
+use crate::element::{Int, Point2D, Point3D, UInt};
+use crate::glb::{ColorMode, ColorOutput, Glb, GlbGen};
+use crate::mesh::VoxelMesh;
+
+/// タイルの境界ボリュームの表現方法を表す列挙型です。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BoundingVolume {
+    /// 経緯度(ラジアン)と楕円体高の範囲で表現します。
+    Region,
+    /// タイルのローカル座標系における直方体で表現します。
+    /// ルートタイルはECEF座標系の軸に沿った直方体で表現します。
+    Box,
+}
+
+/// 3D Tilesのタイルセットを表す構造体です。
+pub struct Tileset<'a> {
+    /// `tileset.json`の内容です。
+    pub json: Value,
+    /// 各タイルのコンテンツです。
+    /// タプルの1要素目として`tileset.json`からの相対パス、2要素目としてglbが格納されます。
+    pub contents: Vec<(String, Glb<'a>)>,
+}
+
+impl Tileset<'_> {
+    /// 指定したディレクトリに`tileset.json`と各タイルのglbファイルを書き込みます。
+    /// ディレクトリが存在しない場合は作成します。
+    pub fn write<T: AsRef<Path>>(self, dir: T) -> Result<(), anyhow::Error> {
+        let dir = dir.as_ref();
+
+        self.contents.into_iter().try_for_each(|(uri, glb)| {
+            let path = dir.join(uri);
+
+            if let Some(parent) = path.parent() {
+                create_dir_all(parent)?;
+            }
+
+            glb.to_writer(File::create(path)?)?;
+
+            Ok::<(), anyhow::Error>(())
+        })?;
+
+        create_dir_all(dir)?;
+
+        let mut writer = File::create(dir.join("tileset.json"))?;
+        writer.write_all(to_string_pretty(&self.json)?.as_bytes())?;
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+/// [`MapTileVoxelizer`](crate::voxelizer::MapTileVoxelizer)が出力したタイルごとのボクセルから、3D Tilesのタイルセットを生成するための構造体です。
+pub struct TilesetGen;
+
+impl TilesetGen {
+    /// タイル座標とボクセルのペアのリストからタイルセットを生成します。
+    ///
+    /// 各タイルは`meshing`でメッシュに変換され、`color_mode`と`color_output`に従ってglbとして出力されます。
+    /// glbの頂点はタイルの北西端を原点とするローカル座標系で表現され、タイルの`transform`によってECEF座標系に配置されます。
+    /// コンテンツのパスは`{ズームレベル}/{x}/{y}.glb`です。
+    ///
+    /// ボクセルの座標値は[`MapTileVoxelizer`](crate::voxelizer::MapTileVoxelizer)と同様に、`zoom_lv`におけるピクセル座標である必要があります。
+    /// ボクセルのオフセットは無視されます。
+    ///
+    /// # Errors
+    ///
+    /// + glbの生成に失敗した場合、エラーを返します。
+    pub fn from_tiles<'a, P, W, C, VC, F>(
+        tiles: Vec<(Point2D<u32>, VC)>,
+        zoom_lv: ZoomLv,
+        bounding_volume: BoundingVolume,
+        color_mode: ColorMode,
+        color_output: ColorOutput,
+        meshing: F,
+    ) -> Result<Tileset<'a>, anyhow::Error>
+    where
+        P: Int + AsPrimitive<f32> + AsPrimitive<f64> + AsPrimitive<i64>,
+        W: UInt,
+        C: UInt + AsPrimitive<f32>,
+        VC: VoxelCollection<P, W, C>,
+        F: Fn(VC) -> VoxelMesh<P, C>,
+        f32: AsPrimitive<P> + AsPrimitive<C>,
+        i64: AsPrimitive<P>,
+    {
+        const TILE_SIZE: u32 = 256;
+
+        let mut regions = Vec::new();
+        let mut corners = Vec::new();
+        let mut geometric_error = 0_f64;
+
+        let mut children = Vec::new();
+        let mut contents = Vec::new();
+
+        for (tile, vc) in tiles {
+            let resolution = vc.get_resolution();
+            let mesh = meshing(vc);
+
+            if mesh.points.is_empty() {
+                continue;
+            }
+
+            // メッシュの座標値が1ピクセルを何分割したものか
+            let scale = (resolution / mesh.resolution).round() as i64;
+
+            let [tile_x, tile_y] = tile.data;
+            let min_pixel = (tile_x.saturating_mul(TILE_SIZE), tile_y.saturating_mul(TILE_SIZE));
+            let max_pixel = (min_pixel.0.saturating_add(TILE_SIZE), min_pixel.1.saturating_add(TILE_SIZE));
+
+            let origin = Point3D::new([
+                min_pixel.0 as i64 * scale,
+                min_pixel.1 as i64 * scale,
+                AsPrimitive::<i64>::as_(mesh.bounds.0[2]),
+            ]).as_();
+
+            let min_height = AsPrimitive::<f64>::as_(mesh.bounds.0[2]) * mesh.resolution;
+            let max_height = AsPrimitive::<f64>::as_(mesh.bounds.1[2]) * mesh.resolution;
+
+            let (west, north) = pixel2ll(min_pixel, zoom_lv);
+            let (east, south) = pixel2ll(max_pixel, zoom_lv);
+
+            let width = TILE_SIZE as f64 * resolution;
+            let height = max_height - min_height;
+            geometric_error = geometric_error.max(width);
+
+            // ローカル座標系の各軸は(東, 南, 上)を向く
+            let transform = Self::enu_transform((west, north), min_height);
+
+            let volume = match bounding_volume {
+                BoundingVolume::Region => {
+                    Self::region([west, south, east, north, min_height, max_height])
+                }
+                BoundingVolume::Box => {
+                    Self::bounding_box([width / 2., width / 2., height / 2.], [width / 2., width / 2., height / 2.])
+                }
+            };
+
+            regions.push([west, south, east, north, min_height, max_height]);
+            corners.extend(Self::box_corners(&transform, [width, width, height]));
+
+            let uri = format!("{}/{}/{}.glb", zoom_lv as u8, tile_x, tile_y);
+
+            children.push(Value::from_iter([
+                ("boundingVolume", volume),
+                ("geometricError", Value::from(0.)),
+                ("transform", Value::from(transform.to_vec())),
+                ("content", Value::from_iter([("uri", Value::from(uri.clone()))])),
+            ]));
+
+            let glb = Glb::from_voxel_mesh(mesh.shift_origin(origin), color_mode, color_output)?;
+            contents.push((uri, glb));
+        }
+
+        let root_volume = match bounding_volume {
+            BoundingVolume::Region => {
+                let region = regions.into_iter().reduce(|a, b| {
+                    [a[0].min(b[0]), a[1].min(b[1]), a[2].max(b[2]), a[3].max(b[3]), a[4].min(b[4]), a[5].max(b[5])]
+                }).unwrap_or_default();
+
+                Self::region(region)
+            }
+            BoundingVolume::Box => {
+                let (min, max) = corners.iter().fold(
+                    (Point3D::from(f64::MAX), Point3D::from(f64::MIN)),
+                    |(min, max), &corner| (min.batch_with(corner, f64::min), max.batch_with(corner, f64::max)),
+                );
+
+                Self::bounding_box(((min + max) / 2.).data, ((max - min) / 2.).data)
+            }
+        };
+
+        let root = Value::from_iter([
+            ("boundingVolume", root_volume),
+            ("geometricError", Value::from(geometric_error)),
+            ("refine", Value::from("ADD")),
+            ("children", Value::from(children)),
+        ]);
+
+        let json = Value::from_iter([
+            ("asset", Value::from_iter([("version", Value::from("1.1"))])),
+            ("geometricError", Value::from(geometric_error)),
+            ("root", root),
+        ]);
+
+        Ok(Tileset { json, contents })
+    }
+
+    // 指定した経緯度と楕円体高を原点とし、各軸が(東, 南, 上)を向くローカル座標系からECEF座標系への変換行列を列優先で返す
+    fn enu_transform((long, lat): (f64, f64), height: f64) -> [f64; 16] {
+        let (x, y, z) = llz2xyz((long, lat), height);
+
+        let east = [-long.sin(), long.cos(), 0.];
+        let south = [lat.sin() * long.cos(), lat.sin() * long.sin(), -lat.cos()];
+        let up = [lat.cos() * long.cos(), lat.cos() * long.sin(), lat.sin()];
+
+        [
+            east[0], east[1], east[2], 0.,
+            south[0], south[1], south[2], 0.,
+            up[0], up[1], up[2], 0.,
+            x, y, z, 1.,
+        ]
+    }
+
+    // ローカル座標系において原点と`size`を対角とする直方体の頂点を、ECEF座標系で返す
+    fn box_corners(transform: &[f64; 16], size: [f64; 3]) -> Vec<Point3D<f64>> {
+        (0..8).map(|i| {
+            let local = [
+                if i & 1 == 0 { 0. } else { size[0] },
+                if i & 2 == 0 { 0. } else { size[1] },
+                if i & 4 == 0 { 0. } else { size[2] },
+            ];
+
+            Point3D::new([0, 1, 2].map(|axis| {
+                transform[12 + axis] + (0..3).map(|j| transform[j * 4 + axis] * local[j]).sum::<f64>()
+            }))
+        }).collect()
+    }
+
+    fn region(region: [f64; 6]) -> Value {
+        Value::from_iter([("region", Value::from(region.to_vec()))])
+    }
+
+    fn bounding_box(center: [f64; 3], half: [f64; 3]) -> Value {
+        Value::from_iter([("box", Value::from(vec![
+            center[0], center[1], center[2],
+            half[0], 0., 0.,
+            0., half[1], 0.,
+            0., 0., half[2],
+        ]))])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use coordinate_transformer::{ll2pixel, llz2xyz, pixel2ll, ZoomLv};
+    use fxhash::FxBuildHasher;
+
+    use crate::collection::{HMap3DVoxelCollection, VoxelCollection};
+    use crate::element::{Color, Point2D, Point3D};
+    use crate::glb::{ColorMode, ColorOutput};
+    use crate::mesh::{Mesher, ValidSide};
+    use crate::tileset::{BoundingVolume, TilesetGen};
+
+    type TestVC = HMap3DVoxelCollection<i32, u8, u8, FxBuildHasher>;
+
+    fn tiles() -> Vec<(Point2D<u32>, TestVC)> {
+        let (x, y) = ll2pixel((139.7649308_f64.to_radians(), 35.6812405_f64.to_radians()), ZoomLv::Lv17);
+        let tile = Point2D::new([x / 256, y / 256]);
+
+        [tile, tile + Point2D::new([1_u32, 0])].into_iter().map(|tile| {
+            let pixel = (tile * 256_u32).as_::<i32>();
+            let vc = TestVC::builder()
+                .points(vec![(Point3D::new([pixel[0] + 10, pixel[1] + 20, 30]), Color::new([255, 255, 255]))])
+                .resolution(1.)
+                .build();
+
+            (tile, vc)
+        }).collect()
+    }
+
+    #[test]
+    fn test_from_tiles_with_region() {
+        let tileset = TilesetGen::from_tiles(tiles(), ZoomLv::Lv17, BoundingVolume::Region, ColorMode::Srgb, ColorOutput::VertexColor, |vc| {
+            Mesher::meshing(vc, ValidSide::all())
+        }).unwrap();
+
+        assert_eq!(tileset.contents.len(), 2);
+
+        let children = tileset.json["root"]["children"].as_array().unwrap();
+        assert_eq!(children.len(), 2);
+
+        children.iter().zip(tiles()).for_each(|(child, (tile, _))| {
+            let [tile_x, tile_y] = tile.data;
+            assert_eq!(child["content"]["uri"], format!("17/{}/{}.glb", tile_x, tile_y));
+
+            let region = child["boundingVolume"]["region"].as_array().unwrap().iter().map(|v| v.as_f64().unwrap()).collect::<Vec<_>>();
+            assert!(region[0] < region[2] && region[1] < region[3]);
+            assert_eq!((region[4], region[5]), (30., 31.));
+
+            // 変換行列の平行移動成分は、タイルの北西端かつボクセルの最低高さのECEF座標になる
+            let transform = child["transform"].as_array().unwrap().iter().map(|v| v.as_f64().unwrap()).collect::<Vec<_>>();
+            let (x, y, z) = llz2xyz(pixel2ll((tile_x * 256, tile_y * 256), ZoomLv::Lv17), 30.);
+            assert_eq!(transform[12..15], [x, y, z]);
+        });
+
+        let root = tileset.json["root"]["boundingVolume"]["region"].as_array().unwrap();
+        assert_eq!(root[0], children[0]["boundingVolume"]["region"][0]);
+        assert_eq!(root[2], children[1]["boundingVolume"]["region"][2]);
+    }
+
+    #[test]
+    fn test_from_tiles_with_box() {
+        let tileset = TilesetGen::from_tiles(tiles(), ZoomLv::Lv17, BoundingVolume::Box, ColorMode::Srgb, ColorOutput::VertexColor, |vc| {
+            Mesher::meshing(vc, ValidSide::all())
+        }).unwrap();
+
+        let child_box = tileset.json["root"]["children"][0]["boundingVolume"]["box"].as_array().unwrap();
+        assert_eq!(child_box[0], 128.);
+        assert_eq!(child_box[2], 0.5);
+
+        let root_box = tileset.json["root"]["boundingVolume"]["box"].as_array().unwrap();
+        assert_eq!(root_box.len(), 12);
+    }
+}
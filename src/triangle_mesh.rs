@@ -0,0 +1,629 @@
+use std::io::Read;
+
+use anyhow::{anyhow, ensure};
+use fxhash::{FxBuildHasher, FxHashMap};
+use num::cast::AsPrimitive;
+use num::FromPrimitive;
+
+use crate::collection::{HMap3DVoxelCollection, PointCloud, VoxelCollection};
+use crate::element::{Color, Number, Point3D, UInt};
+
+/// 頂点色や材質を持たない三角形に用いる色です。
+const DEFAULT_COLOR: [u8; 3] = [255, 255, 255];
+
+/// 1つの三角形を表す構造体
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Triangle {
+    /// 三角形の頂点の座標です。
+    pub vertices: [Point3D<f64>; 3],
+    /// 各頂点のsRGB色です。
+    pub colors: [Color<u8>; 3],
+}
+
+/// ボクセル化の対象となる三角形メッシュを表す構造体
+/// 座標系はボクセルと同じく、z軸を上方向とします。
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TriangleMesh {
+    triangles: Vec<Triangle>,
+}
+
+impl TriangleMesh {
+    /// 三角形のリストを指定してインスタンスを生成します。
+    pub fn new(triangles: Vec<Triangle>) -> Self {
+        Self {
+            triangles
+        }
+    }
+
+    /// 登録されている三角形を返します。
+    pub fn triangles(&self) -> &Vec<Triangle> {
+        &self.triangles
+    }
+
+    /// objファイルから三角形メッシュを読み込みます。
+    /// 頂点色(`v x y z r g b`)を持つ場合はその色を、持たない場合は白を使用します。
+    /// 多角形の面は扇状に三角形に分割します。
+    ///
+    /// # Errors
+    ///
+    /// + 数値や頂点のインデックスが正しくない場合、エラーを返します。
+    pub fn from_obj<T: Read>(reader: T) -> Result<Self, anyhow::Error> {
+        Self::parse_obj(reader, FxHashMap::default())
+    }
+
+    /// objファイルと、それが参照するmtlファイルから三角形メッシュを読み込みます。
+    /// 頂点色を持たない頂点には、`usemtl`で指定された材質の拡散反射色(`Kd`)を使用します。
+    ///
+    /// # Errors
+    ///
+    /// + 数値や頂点のインデックスが正しくない場合、エラーを返します。
+    pub fn from_obj_with_mtl<T: Read, U: Read>(reader: T, mut mtl: U) -> Result<Self, anyhow::Error> {
+        let mut buf = String::new();
+        mtl.read_to_string(&mut buf)?;
+
+        let mut materials = FxHashMap::default();
+        let mut current = None;
+
+        for line in buf.lines() {
+            let mut tokens = line.split_whitespace();
+
+            match tokens.next() {
+                Some("newmtl") => current = tokens.next().map(|name| name.to_string()),
+                Some("Kd") => {
+                    let name = current.clone().ok_or_else(|| anyhow!("Kd is specified before newmtl"))?;
+                    let color = parse_floats::<3>(tokens)?;
+                    materials.insert(name, unit_to_color(color));
+                }
+                _ => {}
+            }
+        }
+
+        Self::parse_obj(reader, materials)
+    }
+
+    fn parse_obj<T: Read>(mut reader: T, materials: FxHashMap<String, Color<u8>>) -> Result<Self, anyhow::Error> {
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf)?;
+
+        let mut vertices = Vec::new();
+        let mut triangles = Vec::new();
+        let mut material_color = None;
+
+        for line in buf.lines() {
+            let mut tokens = line.split_whitespace();
+
+            match tokens.next() {
+                Some("v") => {
+                    let values = tokens.map(|a| a.parse::<f64>()).collect::<Result<Vec<_>, _>>()?;
+                    ensure!(values.len() >= 3, "Vertex must have 3 coordinates");
+
+                    let point = Point3D::new([values[0], values[1], values[2]]);
+                    let color = (values.len() >= 6).then(|| unit_to_color([values[3], values[4], values[5]]));
+
+                    vertices.push((point, color));
+                }
+                Some("usemtl") => {
+                    material_color = tokens.next().and_then(|name| materials.get(name)).copied();
+                }
+                Some("f") => {
+                    let indices = tokens.map(|token| {
+                        let index = token.split('/').next().unwrap().parse::<i64>()?;
+                        // 負のインデックスは、それまでに定義された頂点の末尾から数える
+                        let index = if index < 0 { vertices.len() as i64 + index } else { index - 1 };
+                        ensure!(0 <= index && index < vertices.len() as i64, "Vertex index {} is out of range", token);
+                        Ok(index as usize)
+                    }).collect::<Result<Vec<_>, anyhow::Error>>()?;
+
+                    let default_color = material_color.unwrap_or(Color::new(DEFAULT_COLOR));
+
+                    (1..indices.len().saturating_sub(1)).for_each(|i| {
+                        let [a, b, c] = [indices[0], indices[i], indices[i + 1]].map(|i| vertices[i]);
+
+                        triangles.push(Triangle {
+                            vertices: [a.0, b.0, c.0],
+                            colors: [a.1, b.1, c.1].map(|color| color.unwrap_or(default_color)),
+                        });
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Self::new(triangles))
+    }
+
+    /// stlファイル(ASCII形式またはバイナリ形式)から三角形メッシュを読み込みます。
+    /// stlファイルは色を持たないため、すべての頂点の色は白になります。
+    ///
+    /// # Errors
+    ///
+    /// + ファイルの形式が正しくない場合、エラーを返します。
+    pub fn from_stl<T: Read>(mut reader: T) -> Result<Self, anyhow::Error> {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+
+        let color = Color::new(DEFAULT_COLOR);
+
+        // バイナリ形式は、ヘッダ(80バイト)・三角形の数(4バイト)・三角形ごとに50バイトで構成される
+        let is_binary = buf.len() >= 84 && {
+            let count = u32::from_le_bytes([buf[80], buf[81], buf[82], buf[83]]) as usize;
+            buf.len() == 84 + count * 50
+        };
+
+        let triangles = if is_binary {
+            buf[84..].chunks_exact(50).map(|chunk| {
+                // 法線(12バイト)の後に3つの頂点が続く
+                let vertices = [0, 1, 2].map(|i| {
+                    Point3D::new([0, 1, 2].map(|axis| {
+                        let start = 12 + i * 12 + axis * 4;
+                        f32::from_le_bytes([chunk[start], chunk[start + 1], chunk[start + 2], chunk[start + 3]]) as f64
+                    }))
+                });
+
+                Triangle { vertices, colors: [color; 3] }
+            }).collect()
+        } else {
+            let text = String::from_utf8(buf)?;
+
+            let vertices = text.lines()
+                .map(|line| line.split_whitespace())
+                .filter_map(|mut tokens| (tokens.next() == Some("vertex")).then_some(tokens))
+                .map(|tokens| parse_floats::<3>(tokens).map(Point3D::new))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            ensure!(vertices.len() % 3 == 0, "The number of vertices is not a multiple of 3");
+
+            vertices.chunks_exact(3).map(|chunk| {
+                Triangle { vertices: [chunk[0], chunk[1], chunk[2]], colors: [color; 3] }
+            }).collect()
+        };
+
+        Ok(Self::new(triangles))
+    }
+
+    /// gltf/glbファイルから三角形メッシュを読み込みます。
+    /// 外部ファイルを参照するバッファには対応していません。
+    /// デフォルトのシーン(存在しない場合は最初のシーン)に含まれるノードの変換を適用し、y軸が上方向の座標系からz軸が上方向の座標系に変換します。
+    /// 頂点色(`COLOR_0`)を持つ場合はその色を、持たない場合は材質の基本色を使用します。
+    ///
+    /// # Errors
+    ///
+    /// + ファイルの形式が正しくない場合、エラーを返します。
+    pub fn from_gltf(slice: &[u8]) -> Result<Self, anyhow::Error> {
+        let (document, buffers, _images) = gltf::import_slice(slice)?;
+
+        let scene = document.default_scene().or_else(|| document.scenes().next())
+            .ok_or_else(|| anyhow!("Scene not found"))?;
+
+        let mut triangles = Vec::new();
+        let mut stack = scene.nodes().map(|node| (node, IDENTITY)).collect::<Vec<_>>();
+
+        while let Some((node, parent)) = stack.pop() {
+            let transform = multiply(&parent, &node.transform().matrix());
+
+            stack.extend(node.children().map(|child| (child, transform)));
+
+            let Some(mesh) = node.mesh() else { continue };
+
+            for primitive in mesh.primitives().filter(|primitive| primitive.mode() == gltf::mesh::Mode::Triangles) {
+                let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+
+                let positions = reader.read_positions().ok_or_else(|| anyhow!("POSITION not found"))?
+                    .map(|position| {
+                        let [x, y, z] = apply(&transform, position);
+                        // gltfの座標系(y軸が上方向)から変換する
+                        Point3D::new([x, -z, y])
+                    })
+                    .collect::<Vec<_>>();
+
+                let colors = match reader.read_colors(0) {
+                    Some(colors) => colors.into_rgb_f32().map(|color| linear_to_srgb(color.map(|a| a as f64))).collect(),
+                    None => {
+                        let [r, g, b, _a] = primitive.material().pbr_metallic_roughness().base_color_factor();
+                        vec![linear_to_srgb([r, g, b].map(|a| a as f64)); positions.len()]
+                    }
+                };
+
+                let indices = match reader.read_indices() {
+                    Some(indices) => indices.into_u32().map(|i| i as usize).collect(),
+                    None => (0..positions.len()).collect::<Vec<_>>(),
+                };
+
+                for chunk in indices.chunks_exact(3) {
+                    ensure!(chunk.iter().all(|&i| i < positions.len()), "Index is out of range");
+
+                    triangles.push(Triangle {
+                        vertices: [chunk[0], chunk[1], chunk[2]].map(|i| positions[i]),
+                        colors: [chunk[0], chunk[1], chunk[2]].map(|i| colors.get(i).copied().unwrap_or(Color::new(DEFAULT_COLOR))),
+                    });
+                }
+            }
+        }
+
+        Ok(Self::new(triangles))
+    }
+
+    /// 三角形メッシュを指定された分解能でボクセル化し、各ボクセルの中心に1点を持つ点群を返します。
+    /// 三角形と少しでも交わるボクセルをすべて含む、保守的な表面ボクセル化を行います。
+    /// `fill`に`true`を指定した場合、[`VoxelCollection::fill_solid`]を用いて閉じたメッシュの内部も埋めます。内部のボクセルの色は、その真下にある表面のボクセルの色になります。
+    ///
+    /// 返される点群は、同じ分解能の[`Resolution::Mater`](crate::voxelizer::Resolution::Mater)を指定した[`SimpleVoxelizer`](crate::voxelizer::SimpleVoxelizer)にそのまま追加できます。
+    /// ボクセルの色は、ボクセルの中心に最も近い三角形上の点における頂点色の補間値の平均です。
+    ///
+    /// # Errors
+    ///
+    /// + ボクセルの中心の座標値が`P`で表現できない場合(符号なし整数型で負の座標値を含む場合など)、エラーを返します。
+    /// + `fill`に`true`を指定し、表面のボクセルの1つの連結成分の外接直方体に含まれるボクセルの数が多すぎる場合、エラーを返します。
+    pub fn to_point_cloud<P, W, C>(&self, resolution: f64, fill: bool) -> Result<PointCloud<P, W, C>, anyhow::Error>
+    where
+        P: Number + FromPrimitive,
+        W: UInt + AsPrimitive<C>,
+        C: UInt + AsPrimitive<W> + AsPrimitive<f64> + FromPrimitive,
+    {
+        let mut field = FxHashMap::<Point3D<i64>, ([f64; 3], f64)>::default();
+
+        self.triangles.iter().for_each(|triangle| {
+            let vertices = triangle.vertices.map(|vertex| (vertex / resolution).data);
+
+            surface_voxels(&vertices).into_iter().for_each(|voxel| {
+                let center = voxel.as_::<f64>().data.map(|a| a + 0.5);
+                let weights = closest_barycentric(&vertices, center);
+
+                let color = [0, 1, 2].map(|c| {
+                    (0..3).map(|i| weights[i] * triangle.colors[i][c] as f64).sum::<f64>()
+                });
+
+                let entry = field.entry(voxel).or_insert(([0.; 3], 0.));
+                entry.0 = [0, 1, 2].map(|c| entry.0[c] + color[c]);
+                entry.1 += 1.;
+            });
+        });
+
+        let mut voxels = field.into_iter().map(|(voxel, (sum, count))| {
+            (voxel, sum.map(|a| a / count))
+        }).collect::<Vec<_>>();
+
+        if fill {
+            voxels = fill_solid(voxels)?;
+        }
+
+        let max = AsPrimitive::<f64>::as_(C::max_value());
+
+        let points = voxels.into_iter().map(|(voxel, color)| {
+            let point = voxel.as_::<f64>().data.map(|a| (a + 0.5) * resolution);
+            let point = point.map(P::from_f64);
+            let [Some(x), Some(y), Some(z)] = point else {
+                return Err(anyhow!("Voxel center cannot be represented by the point type"));
+            };
+
+            // 不正な頂点色や縮退した三角形によって色が範囲外やNaNになった場合も、表現できる値に収める
+            let color = color.map(|a| {
+                let a = if a.is_nan() { 0. } else { (a / u8::MAX as f64 * max).round().clamp(0., max) };
+                C::from_f64(a).unwrap_or_default()
+            });
+
+            Ok((Point3D::new([x, y, z]), Color::new(color)))
+        }).collect::<Result<Vec<_>, anyhow::Error>>()?;
+
+        Ok(PointCloud::builder().points(points).resolution(resolution).build())
+    }
+}
+
+// ボクセルの座標値と、0から255の範囲で表された色
+type VoxelColors = Vec<(Point3D<i64>, [f64; 3])>;
+
+// 表面のボクセルの内部を埋める
+// 色は`fill_solid`で扱えるように16ビットの値に変換し、埋めた後に元の範囲に戻す
+fn fill_solid(surface: VoxelColors) -> Result<VoxelColors, anyhow::Error> {
+    let scale = u16::MAX as f64 / u8::MAX as f64;
+
+    let points = surface.into_iter().map(|(voxel, color)| {
+        let color = color.map(|a| if a.is_nan() { 0 } else { (a * scale).round().clamp(0., u16::MAX as f64) as u16 });
+        (voxel, Color::new(color))
+    }).collect();
+
+    let solid = HMap3DVoxelCollection::<i64, u8, u16, FxBuildHasher>::builder()
+        .points(points)
+        .build()
+        .fill_solid()?;
+
+    Ok(solid.to_points().into_iter().map(|(voxel, color)| (voxel, color.data.map(|a| a as f64 / scale))).collect())
+}
+
+// 三角形と交わるボクセルを返す
+// 座標値はボクセルの大きさを1とした値で与える
+fn surface_voxels(vertices: &[[f64; 3]; 3]) -> Vec<Point3D<i64>> {
+    let min = [0, 1, 2].map(|axis| vertices.iter().map(|v| v[axis]).fold(f64::INFINITY, f64::min).floor() as i64);
+    let max = [0, 1, 2].map(|axis| vertices.iter().map(|v| v[axis]).fold(f64::NEG_INFINITY, f64::max).floor() as i64);
+
+    // 法線の成分が最も大きい軸`w`に投影し、投影面上の列ごとに平面が通過する範囲のみを判定する
+    let normal = cross(sub(vertices[1], vertices[0]), sub(vertices[2], vertices[0]));
+    let w = (0..3).max_by(|&a, &b| normal[a].abs().total_cmp(&normal[b].abs())).unwrap();
+    let (u, v) = ((w + 1) % 3, (w + 2) % 3);
+    let d = dot(normal, vertices[0]);
+
+    let mut voxels = Vec::new();
+
+    for a in min[u]..=max[u] {
+        for b in min[v]..=max[v] {
+            // 面積を持たない三角形は平面を決められないため、外接直方体の範囲をすべて判定する
+            let (low, high) = if normal[w] == 0. {
+                (min[w], max[w])
+            } else {
+                let depths = [(0., 0.), (1., 0.), (0., 1.), (1., 1.)].map(|(du, dv)| {
+                    (d - normal[u] * (a as f64 + du) - normal[v] * (b as f64 + dv)) / normal[w]
+                });
+                let low = depths.iter().copied().fold(f64::INFINITY, f64::min) - 1e-9;
+                let high = depths.iter().copied().fold(f64::NEG_INFINITY, f64::max) + 1e-9;
+
+                // 境界で接するボクセルも含める
+                ((low.ceil() as i64 - 1).max(min[w]), (high.floor() as i64).min(max[w]))
+            };
+
+            for c in low..=high {
+                let mut voxel = Point3D::new([0; 3]);
+                voxel[u] = a;
+                voxel[v] = b;
+                voxel[w] = c;
+
+                let center = voxel.as_::<f64>().data.map(|a| a + 0.5);
+
+                if triangle_box_overlap(vertices, center, 0.5) {
+                    voxels.push(voxel);
+                }
+            }
+        }
+    }
+
+    voxels
+}
+
+// 分離軸定理を用いて、三角形と軸に平行な立方体が交わるかどうかを判定する
+// 境界で接する場合も交わるものとして扱う
+fn triangle_box_overlap(vertices: &[[f64; 3]; 3], center: [f64; 3], half: f64) -> bool {
+    let v = vertices.map(|vertex| sub(vertex, center));
+    let edges = [sub(v[1], v[0]), sub(v[2], v[1]), sub(v[0], v[2])];
+
+    let separated = |axis: [f64; 3]| {
+        let p = v.map(|vertex| dot(vertex, axis));
+        let r = half * (axis[0].abs() + axis[1].abs() + axis[2].abs());
+        let min = p.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = p.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+
+        min > r || max < -r
+    };
+
+    let unit_axes = [[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]];
+
+    if unit_axes.iter().any(|&axis| separated(axis)) {
+        return false;
+    }
+
+    if separated(cross(edges[0], edges[1])) {
+        return false;
+    }
+
+    !edges.iter().any(|&edge| unit_axes.iter().any(|&axis| separated(cross(axis, edge))))
+}
+
+// 点に最も近い三角形上の点の重心座標を返す
+fn closest_barycentric(vertices: &[[f64; 3]; 3], point: [f64; 3]) -> [f64; 3] {
+    let e0 = sub(vertices[1], vertices[0]);
+    let e1 = sub(vertices[2], vertices[0]);
+    let p = sub(point, vertices[0]);
+
+    let d00 = dot(e0, e0);
+    let d01 = dot(e0, e1);
+    let d11 = dot(e1, e1);
+    let d20 = dot(p, e0);
+    let d21 = dot(p, e1);
+    let denominator = d00 * d11 - d01 * d01;
+
+    // 面積を持たない三角形は、各頂点を等しく扱う
+    if denominator.abs() < f64::EPSILON {
+        return [1. / 3.; 3];
+    }
+
+    let v = (d11 * d20 - d01 * d21) / denominator;
+    let w = (d00 * d21 - d01 * d20) / denominator;
+    let weights = [1. - v - w, v, w].map(|a| a.max(0.));
+    let sum = weights.iter().sum::<f64>();
+
+    weights.map(|a| a / sum)
+}
+
+fn parse_floats<'a, const N: usize>(tokens: impl Iterator<Item=&'a str>) -> Result<[f64; N], anyhow::Error> {
+    let values = tokens.take(N).map(|a| a.parse::<f64>()).collect::<Result<Vec<_>, _>>()?;
+    values.try_into().map_err(|_| anyhow!("Expected {} numbers", N))
+}
+
+fn unit_to_color(color: [f64; 3]) -> Color<u8> {
+    Color::new(color.map(|a| (a.clamp(0., 1.) * u8::MAX as f64).round() as u8))
+}
+
+// リニアRGBからsRGBに近似
+fn linear_to_srgb(color: [f64; 3]) -> Color<u8> {
+    unit_to_color(color.map(|a| a.max(0.).powf(1. / 2.2)))
+}
+
+const IDENTITY: [[f32; 4]; 4] = [[1., 0., 0., 0.], [0., 1., 0., 0.], [0., 0., 1., 0.], [0., 0., 0., 1.]];
+
+// 列優先の4x4行列の積を返す
+fn multiply(a: &[[f32; 4]; 4], b: &[[f32; 4]; 4]) -> [[f32; 4]; 4] {
+    let mut result = [[0.; 4]; 4];
+
+    for (column, b_column) in b.iter().enumerate() {
+        for row in 0..4 {
+            result[column][row] = (0..4).map(|k| a[k][row] * b_column[k]).sum();
+        }
+    }
+
+    result
+}
+
+// 列優先の4x4行列を点に適用する
+fn apply(matrix: &[[f32; 4]; 4], point: [f32; 3]) -> [f64; 3] {
+    [0, 1, 2].map(|row| {
+        (matrix[0][row] * point[0] + matrix[1][row] * point[1] + matrix[2][row] * point[2] + matrix[3][row]) as f64
+    })
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use gltf::Glb;
+    use ordered_float::OrderedFloat;
+
+    use crate::build_voxelizer::{BuildSimpleVoxelizerDefault, BuildVoxelizer};
+    use crate::collection::{HMap3DVoxelCollection, PointCloud, VoxelCollection};
+    use crate::element::{Color, Point3D};
+    use crate::glb::{ColorMode, ColorOutput, GlbGen};
+    use crate::mesh::{Mesher, ValidSide};
+    use crate::triangle_mesh::{surface_voxels, triangle_box_overlap, Triangle, TriangleMesh};
+    use crate::voxelizer::Resolution;
+
+    // 0.5から4.5までの立方体のstl
+    fn cube_stl() -> String {
+        let corners = |i: usize| [i & 1, (i >> 1) & 1, (i >> 2) & 1].map(|a| a as f64 * 4. + 0.5);
+        let quads = [[0, 2, 3, 1], [4, 5, 7, 6], [0, 1, 5, 4], [2, 6, 7, 3], [0, 4, 6, 2], [1, 3, 7, 5]];
+
+        let facets = quads.iter().flat_map(|quad| [[quad[0], quad[1], quad[2]], [quad[0], quad[2], quad[3]]])
+            .map(|triangle| {
+                let vertices = triangle.map(|i| {
+                    let [x, y, z] = corners(i);
+                    format!("vertex {} {} {}", x, y, z)
+                });
+                format!("facet normal 0 0 0\nouter loop\n{}\nendloop\nendfacet", vertices.join("\n"))
+            })
+            .collect::<Vec<_>>();
+
+        format!("solid cube\n{}\nendsolid cube\n", facets.join("\n"))
+    }
+
+    #[test]
+    fn test_surface_and_solid() {
+        let mesh = TriangleMesh::from_stl(cube_stl().as_bytes()).unwrap();
+        assert_eq!(mesh.triangles().len(), 12);
+
+        let surface = mesh.to_point_cloud::<OrderedFloat<f32>, u8, u8>(1., false).unwrap();
+        let surface = BuildSimpleVoxelizerDefault::voxelize_one(surface, Resolution::Mater(1.));
+        assert_eq!(surface.into_points().len(), 125 - 27);
+
+        let solid = mesh.to_point_cloud::<OrderedFloat<f32>, u8, u8>(1., true).unwrap();
+        let solid = BuildSimpleVoxelizerDefault::voxelize_one(solid, Resolution::Mater(1.));
+        let points = solid.into_points();
+        assert_eq!(points.len(), 125);
+        assert!(points.iter().all(|(point, color)| {
+            point.data.iter().all(|&a| (0..5).contains(&a)) && *color == Color::new([255, 255, 255])
+        }));
+    }
+
+    #[test]
+    fn test_surface_voxels_by_column() {
+        // 外接直方体に含まれるすべてのボクセルを判定した結果と一致する
+        let triangles = [
+            [[0.3, 0.2, 0.1], [17.6, 3.1, 9.4], [5.2, 14.8, 20.3]],
+            [[2., 2., 2.], [12., 2., 2.], [2., 12., 2.]],
+            [[1.5, 1.5, 1.5], [4.5, 4.5, 4.5], [7.5, 7.5, 7.5]],
+        ];
+
+        triangles.iter().for_each(|vertices| {
+            let mut voxels = surface_voxels(vertices);
+            voxels.sort_by_key(|voxel| voxel.data);
+
+            let min = [0, 1, 2].map(|axis| vertices.iter().map(|v| v[axis]).fold(f64::INFINITY, f64::min).floor() as i64);
+            let max = [0, 1, 2].map(|axis| vertices.iter().map(|v| v[axis]).fold(f64::NEG_INFINITY, f64::max).floor() as i64);
+
+            let mut expected = Vec::new();
+            for x in min[0]..=max[0] {
+                for y in min[1]..=max[1] {
+                    for z in min[2]..=max[2] {
+                        if triangle_box_overlap(vertices, [x as f64 + 0.5, y as f64 + 0.5, z as f64 + 0.5], 0.5) {
+                            expected.push(Point3D::new([x, y, z]));
+                        }
+                    }
+                }
+            }
+            expected.sort_by_key(|voxel| voxel.data);
+
+            assert_eq!(voxels, expected);
+        });
+    }
+
+    #[test]
+    fn test_point_cloud_errors() {
+        let triangle = |offset: f64| Triangle {
+            vertices: [[0., 0., 0.], [1., 0., 0.], [0., 1., 0.]].map(|v| Point3D::new(v.map(|a| a + offset))),
+            colors: [Color::new([255, 0, 0]); 3],
+        };
+
+        // 符号なし整数型では負の座標値を表現できない
+        let mesh = TriangleMesh::new(vec![triangle(-5.)]);
+        assert!(mesh.to_point_cloud::<u32, u8, u8>(1., false).is_err());
+        assert!(mesh.to_point_cloud::<i32, u8, u8>(1., false).is_ok());
+
+        // 大きく離れた2つの三角形は、連結成分ごとに内部を埋める
+        let mesh = TriangleMesh::new(vec![triangle(0.), triangle(1e6)]);
+        let surface = mesh.to_point_cloud::<i32, u8, u8>(1., false).unwrap().into_points();
+        let solid = mesh.to_point_cloud::<i32, u8, u8>(1., true).unwrap().into_points();
+        assert_eq!(solid.len(), surface.len());
+        assert!(solid.iter().all(|(_, color)| *color == Color::new([255, 0, 0])));
+    }
+
+    #[test]
+    fn test_obj_with_material() {
+        let obj = "v 0.1 0.1 0.5\nv 2.9 0.1 0.5\nv 0.1 2.9 0.5\nv 0.1 0.1 1.5 0 0 1\nusemtl red\nf 1 2 3\nf 1/1/1 2 -1\n";
+        let mtl = "newmtl red\nKd 1 0 0\n";
+
+        let mesh = TriangleMesh::from_obj_with_mtl(obj.as_bytes(), mtl.as_bytes()).unwrap();
+        assert_eq!(mesh.triangles().len(), 2);
+        assert_eq!(mesh.triangles()[1].colors[2], Color::new([0, 0, 255]));
+
+        let points = TriangleMesh::new(vec![mesh.triangles()[0]])
+            .to_point_cloud::<OrderedFloat<f32>, u8, u8>(1., false).unwrap()
+            .into_points();
+
+        let voxels = points.iter().map(|(point, _)| point.as_::<f32>().batch(|a| a.floor() as i32)).collect::<Vec<_>>();
+        assert!(voxels.contains(&Point3D::new([2, 0, 0])));
+        assert!(voxels.contains(&Point3D::new([0, 2, 0])));
+        assert!(!voxels.contains(&Point3D::new([2, 2, 0])));
+        assert!(points.iter().all(|(point, color)| point[2] == OrderedFloat(0.5) && *color == Color::new([255, 0, 0])));
+    }
+
+    #[test]
+    fn test_gltf() {
+        let vc = HMap3DVoxelCollection::<i32, u8, u8, fxhash::FxBuildHasher>::builder()
+            .points(vec![(Point3D::new([0, 0, 0]), Color::new([0, 255, 0])), (Point3D::new([1, 0, 2]), Color::new([0, 255, 0]))])
+            .build();
+
+        let glb = Glb::from_voxel_mesh(Mesher::meshing(vc, ValidSide::all()), ColorMode::Srgb, ColorOutput::Material).unwrap();
+        let mesh = TriangleMesh::from_gltf(&glb.to_vec().unwrap()).unwrap();
+
+        assert_eq!(mesh.triangles().len(), 24);
+
+        let points: PointCloud<OrderedFloat<f32>, u8, u8> = mesh.to_point_cloud(0.5, false).unwrap();
+        let vc = BuildSimpleVoxelizerDefault::voxelize_one(points, Resolution::Mater(0.5));
+        let mut voxels = vc.into_points();
+        voxels.sort_by_key(|(point, _)| point.data);
+
+        // 表面が格子と一致するため、面の両側のボクセルが含まれる
+        assert!(voxels.iter().any(|(point, _)| *point == Point3D::new([0, 0, 0])));
+        assert!(voxels.iter().any(|(point, _)| *point == Point3D::new([1, 0, 2])));
+        assert!(voxels.iter().all(|(_, color)| color[1] > 200 && color[0] < 50));
+    }
+}
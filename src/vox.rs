@@ -0,0 +1,625 @@
+use std::hash::BuildHasher;
+use std::io::{Read, Write};
+
+use anyhow::{anyhow, ensure};
+use fxhash::FxHashMap;
+use num::cast::AsPrimitive;
+
+use crate::collection::{HMap3DVoxelCollection, VoxelCollection};
+use crate::element::{Color, Int, Point3D, UInt};
+
+/// 1つのモデルの各軸の最大の大きさです。
+const MAX_MODEL_SIZE: i64 = 256;
+
+/// パレットに登録できる色の数です。
+/// インデックス0は空のボクセルを表すため、256色のうち255色のみを使用できます。
+const MAX_PALETTE_SIZE: usize = 255;
+
+const VERSION: i32 = 150;
+
+/// MagicaVoxelの.voxファイルにおける1つのモデルを表す構造体
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VoxModel {
+    /// モデルの原点(ローカル座標(0, 0, 0)のボクセル)のワールド座標です。
+    pub origin: Point3D<i32>,
+    /// モデルの各軸の大きさです。
+    pub size: Point3D<u32>,
+    /// モデル内のローカル座標と、パレットのインデックス(1~255)のペアです。
+    pub voxels: Vec<(Point3D<u8>, u8)>,
+}
+
+/// MagicaVoxelの.voxファイルを生成・読み込みするために必要な情報を持つ構造体
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Vox {
+    models: Vec<VoxModel>,
+    palette: Vec<Color<u8>>,
+}
+
+impl Vox {
+    /// モデルとパレットを指定してインスタンスを生成します。
+    /// パレットの`i`番目の色は、インデックス`i + 1`に対応します。
+    pub fn new(models: Vec<VoxModel>, palette: Vec<Color<u8>>) -> Self {
+        Self {
+            models,
+            palette,
+        }
+    }
+
+    /// 登録されているモデルを返します。
+    pub fn models(&self) -> &Vec<VoxModel> {
+        &self.models
+    }
+
+    /// 登録されているパレットを返します。
+    pub fn palette(&self) -> &Vec<Color<u8>> {
+        &self.palette
+    }
+
+    /// [`VoxelCollection`]からインスタンスを生成します。
+    /// ボクセルの色は、メディアンカット法で255色に減色したパレットに登録されます。
+    /// 各軸が256ボクセルを超える場合、256x256x256ボクセルごとの複数のモデルに分割し、それぞれをトランスフォームノードで配置します。
+    /// ボクセルの座標は、すべてのボクセルの最小値が原点になるように平行移動されます。
+    pub fn from_voxel_collection<P, W, C, VC>(vc: VC) -> Self
+    where
+        P: Int + AsPrimitive<i64>,
+        W: UInt + AsPrimitive<C>,
+        C: UInt + AsPrimitive<W> + AsPrimitive<f32>,
+        VC: VoxelCollection<P, W, C>,
+    {
+        let max: f32 = C::max_value().as_();
+
+        let points = vc.into_iter_points().map(|(point, color)| {
+            let point = Point3D::new(point.data.map(|a| a.as_()));
+            let color = Color::new(color.data.map(|a| (AsPrimitive::<f32>::as_(a) / max * u8::MAX as f32) as u8));
+            (point, color)
+        }).collect::<Vec<(Point3D<i64>, Color<u8>)>>();
+
+        if points.is_empty() {
+            return Self::new(Vec::new(), Vec::new());
+        }
+
+        let min = points.iter().map(|(point, _)| *point).reduce(|a, b| a.batch_with(b, |a, b| a.min(b))).unwrap();
+        let max = points.iter().map(|(point, _)| *point).reduce(|a, b| a.batch_with(b, |a, b| a.max(b))).unwrap();
+        let extent = max - min + Point3D::from(1);
+
+        let (palette, indices) = {
+            let mut counts = FxHashMap::<Color<u8>, u32>::default();
+            points.iter().for_each(|(_, color)| *counts.entry(*color).or_default() += 1);
+            quantize(counts)
+        };
+
+        let mut models = FxHashMap::<Point3D<i64>, Vec<(Point3D<u8>, u8)>>::default();
+        points.into_iter().for_each(|(point, color)| {
+            let local = point - min;
+            let chunk = local.batch(|a| a / MAX_MODEL_SIZE);
+            let local = (local - chunk * MAX_MODEL_SIZE).as_::<u8>();
+
+            models.entry(chunk).or_default().push((local, indices[&color]));
+        });
+
+        let mut models = models.into_iter().map(|(chunk, voxels)| {
+            let chunk_origin = chunk * MAX_MODEL_SIZE;
+            let size = (extent - chunk_origin).batch(|a| a.min(MAX_MODEL_SIZE));
+
+            VoxModel {
+                origin: chunk_origin.as_(),
+                size: size.as_(),
+                voxels,
+            }
+        }).collect::<Vec<_>>();
+
+        // 出力を決定的にするため、モデルを位置順に並べる
+        models.sort_by_key(|model| [model.origin[2], model.origin[1], model.origin[0]]);
+
+        Self::new(models, palette)
+    }
+
+    /// .vox形式のバイナリのバッファを返します。
+    pub fn into_buf(self) -> Vec<u8> {
+        let mut children = Vec::new();
+
+        self.models.iter().for_each(|model| {
+            let mut size = Vec::new();
+            model.size.data.iter().for_each(|&a| write_i32(&mut size, a as i32));
+            write_chunk(&mut children, b"SIZE", &size);
+
+            let mut xyzi = Vec::new();
+            write_i32(&mut xyzi, model.voxels.len() as i32);
+            model.voxels.iter().for_each(|(point, index)| {
+                xyzi.extend_from_slice(&point.data);
+                xyzi.push(*index);
+            });
+            write_chunk(&mut children, b"XYZI", &xyzi);
+        });
+
+        // シーングラフ: ルートのトランスフォームノード(0) -> グループノード(1) -> 各モデルのトランスフォームノード -> シェイプノード
+        let model_count = self.models.len() as i32;
+
+        write_chunk(&mut children, b"nTRN", &transform_node(0, 1, -1, Point3D::default()));
+
+        let mut group = Vec::new();
+        write_i32(&mut group, 1);
+        write_dict(&mut group, &[]);
+        write_i32(&mut group, model_count);
+        (0..model_count).for_each(|i| write_i32(&mut group, 2 + i * 2));
+        write_chunk(&mut children, b"nGRP", &group);
+
+        self.models.iter().enumerate().for_each(|(i, model)| {
+            let i = i as i32;
+
+            // MagicaVoxelはモデルの中心(各軸の大きさの半分)を基準に配置する
+            let translation = model.origin + (model.size / 2_u32).as_::<i32>();
+            write_chunk(&mut children, b"nTRN", &transform_node(2 + i * 2, 3 + i * 2, 0, translation));
+
+            let mut shape = Vec::new();
+            write_i32(&mut shape, 3 + i * 2);
+            write_dict(&mut shape, &[]);
+            write_i32(&mut shape, 1);
+            write_i32(&mut shape, i);
+            write_dict(&mut shape, &[]);
+            write_chunk(&mut children, b"nSHP", &shape);
+        });
+
+        let mut rgba = Vec::with_capacity(256 * 4);
+        (0..256).for_each(|i| {
+            let [r, g, b] = self.palette.get(i).map(|color| color.data).unwrap_or_default();
+            rgba.extend_from_slice(&[r, g, b, 255]);
+        });
+        write_chunk(&mut children, b"RGBA", &rgba);
+
+        let mut buf = Vec::with_capacity(children.len() + 20);
+        buf.extend_from_slice(b"VOX ");
+        write_i32(&mut buf, VERSION);
+        buf.extend_from_slice(b"MAIN");
+        write_i32(&mut buf, 0);
+        write_i32(&mut buf, children.len() as i32);
+        buf.extend_from_slice(&children);
+
+        buf
+    }
+
+    /// .vox形式のバイナリを書き込みます。
+    pub fn to_writer<T: Write>(self, mut writer: T) -> Result<(), anyhow::Error> {
+        writer.write_all(&self.into_buf())?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// .vox形式のバイナリを読み込みます。
+    ///
+    /// # Errors
+    ///
+    /// + ファイルの形式が正しくない場合、エラーを返します。
+    /// + モデルが回転されている場合、エラーを返します。
+    ///
+    /// パレット(RGBAチャンク)が含まれない場合は、MagicaVoxelの標準のパレットを使用します。
+    pub fn from_reader<T: Read>(mut reader: T) -> Result<Self, anyhow::Error> {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+
+        let mut cursor = ByteCursor { buf: &buf, position: 0 };
+
+        ensure!(cursor.bytes(4)? == b"VOX ", "Not a vox file");
+        cursor.i32()?;
+
+        let (id, content_size, _children_size) = cursor.chunk_header()?;
+        ensure!(id == b"MAIN", "MAIN chunk not found");
+        cursor.bytes(content_size)?;
+
+        let mut sizes = Vec::new();
+        let mut models = Vec::new();
+        let mut palette = None;
+        let mut nodes = FxHashMap::<i32, Node>::default();
+
+        while cursor.position < buf.len() {
+            let (id, content_size, children_size) = cursor.chunk_header()?;
+            let mut content = ByteCursor { buf: cursor.bytes(content_size)?, position: 0 };
+            cursor.bytes(children_size)?;
+
+            match id {
+                b"SIZE" => {
+                    sizes.push(Point3D::new([content.i32()? as u32, content.i32()? as u32, content.i32()? as u32]));
+                }
+                b"XYZI" => {
+                    let count = content.i32()? as usize;
+                    let voxels = (0..count).map(|_| {
+                        let [x, y, z, i] = content.bytes(4)? else { unreachable!() };
+                        Ok((Point3D::new([*x, *y, *z]), *i))
+                    }).collect::<Result<Vec<_>, anyhow::Error>>()?;
+                    models.push(voxels);
+                }
+                b"RGBA" => {
+                    let colors = (0..256).map(|_| {
+                        let [r, g, b, _a] = content.bytes(4)? else { unreachable!() };
+                        Ok(Color::new([*r, *g, *b]))
+                    }).collect::<Result<Vec<_>, anyhow::Error>>()?;
+                    palette = Some(colors);
+                }
+                b"nTRN" => {
+                    let node_id = content.i32()?;
+                    content.dict()?;
+                    let child = content.i32()?;
+                    content.i32()?;
+                    content.i32()?;
+                    let frame_count = content.i32()?;
+
+                    let frames = (0..frame_count).map(|_| content.dict()).collect::<Result<Vec<_>, anyhow::Error>>()?;
+                    let frame = frames.into_iter().next().unwrap_or_default();
+
+                    if let Some(rotation) = frame.get("_r") {
+                        // 4は回転しない場合の値
+                        ensure!(rotation == "4", "Rotated models are not supported");
+                    }
+
+                    let translation = match frame.get("_t") {
+                        Some(t) => {
+                            let t = t.split_whitespace().map(|a| a.parse::<i32>()).collect::<Result<Vec<_>, _>>()?;
+                            ensure!(t.len() == 3, "Invalid translation");
+                            Point3D::new([t[0], t[1], t[2]])
+                        }
+                        None => Point3D::default(),
+                    };
+
+                    nodes.insert(node_id, Node::Transform { child, translation });
+                }
+                b"nGRP" => {
+                    let node_id = content.i32()?;
+                    content.dict()?;
+                    let count = content.i32()?;
+                    let children = (0..count).map(|_| content.i32()).collect::<Result<Vec<_>, anyhow::Error>>()?;
+
+                    nodes.insert(node_id, Node::Group { children });
+                }
+                b"nSHP" => {
+                    let node_id = content.i32()?;
+                    content.dict()?;
+                    let count = content.i32()?;
+                    let shape_models = (0..count).map(|_| {
+                        let model = content.i32()?;
+                        content.dict()?;
+                        Ok(model)
+                    }).collect::<Result<Vec<_>, anyhow::Error>>()?;
+
+                    nodes.insert(node_id, Node::Shape { models: shape_models });
+                }
+                _ => {}
+            }
+        }
+
+        ensure!(sizes.len() == models.len(), "The number of SIZE and XYZI chunks do not match");
+
+        let palette = palette.unwrap_or_else(default_palette);
+
+        // シーングラフがない場合、すべてのモデルは原点を中心に配置される
+        let placements = if nodes.is_empty() {
+            (0..models.len() as i32).map(|i| (i, Point3D::default())).collect()
+        } else {
+            let mut placements = Vec::new();
+            collect_placements(&nodes, 0, Point3D::default(), &mut placements, 0)?;
+            placements
+        };
+
+        let models = placements.into_iter().map(|(model, translation)| {
+            let voxels = models.get(model as usize).ok_or_else(|| anyhow!("Model {} not found", model))?.clone();
+            let size = sizes[model as usize];
+
+            Ok(VoxModel {
+                origin: translation - (size / 2_u32).as_::<i32>(),
+                size,
+                voxels,
+            })
+        }).collect::<Result<Vec<_>, anyhow::Error>>()?;
+
+        Ok(Self::new(models, palette.into_iter().take(MAX_PALETTE_SIZE).collect()))
+    }
+}
+
+impl<P, W, C, BH> HMap3DVoxelCollection<P, W, C, BH>
+where
+    P: Int,
+    W: UInt,
+    C: UInt,
+    BH: BuildHasher + Default + Clone,
+    Self: VoxelCollection<P, W, C>,
+{
+    /// MagicaVoxelの.voxファイルからボクセルを読み込みます。
+    /// 座標値はすべてのモデルを配置した後のワールド座標になり、色は`C`の最大値に合わせて拡大されます。
+    ///
+    /// # Errors
+    ///
+    /// + [`Vox::from_reader`]がエラーを返した場合、エラーを返します。
+    /// + 空のボクセルを表すインデックス0の色が使用されている場合、エラーを返します。
+    pub fn from_vox<T: Read>(reader: T, resolution: f64) -> Result<Self, anyhow::Error>
+    where
+        P: 'static,
+        C: AsPrimitive<f32> + 'static,
+        i32: AsPrimitive<P>,
+        f32: AsPrimitive<C>,
+    {
+        let vox = Vox::from_reader(reader)?;
+        let max: f32 = C::max_value().as_();
+
+        let points = vox.models.iter().flat_map(|model| {
+            model.voxels.iter().map(|(point, index)| {
+                let point = Point3D::new((model.origin + point.as_::<i32>()).data.map(|a| a.as_()));
+                let index = index.checked_sub(1).ok_or_else(|| anyhow!("Color index 0 is not allowed"))?;
+                let color = vox.palette.get(index as usize).copied().unwrap_or_default();
+                let color = Color::new(color.data.map(|a| (a as f32 / u8::MAX as f32 * max).as_()));
+
+                Ok((point, color))
+            })
+        }).collect::<Result<Vec<_>, anyhow::Error>>()?;
+
+        Ok(Self::builder().points(points).resolution(resolution).build())
+    }
+}
+
+// RGBAチャンクを持たないファイルで使用される、MagicaVoxelの標準のパレット
+// 6段階のRGBの組み合わせ(黒を除く)と、赤・緑・青・灰色の10段階のグラデーションからなる
+fn default_palette() -> Vec<Color<u8>> {
+    let steps = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+    let ramp = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+
+    let cube = steps.into_iter().flat_map(|r| {
+        steps.into_iter().flat_map(move |g| steps.into_iter().map(move |b| Color::new([r, g, b])))
+    }).filter(|color| color.data != [0, 0, 0]);
+
+    let ramps = [[1, 0, 0], [0, 1, 0], [0, 0, 1], [1, 1, 1]].into_iter().flat_map(|mask: [u8; 3]| {
+        ramp.into_iter().map(move |a| Color::new(mask.map(|m| m * a)))
+    });
+
+    cube.chain(ramps).collect()
+}
+
+enum Node {
+    Transform { child: i32, translation: Point3D<i32> },
+    Group { children: Vec<i32> },
+    Shape { models: Vec<i32> },
+}
+
+// ノードをたどり、モデルのIDとワールド座標での平行移動量を集める
+fn collect_placements(nodes: &FxHashMap<i32, Node>, node_id: i32, translation: Point3D<i32>, placements: &mut Vec<(i32, Point3D<i32>)>, depth: usize) -> Result<(), anyhow::Error> {
+    ensure!(depth <= nodes.len(), "Scene graph has a cycle");
+
+    match nodes.get(&node_id).ok_or_else(|| anyhow!("Node {} not found", node_id))? {
+        Node::Transform { child, translation: t } => {
+            collect_placements(nodes, *child, translation + *t, placements, depth + 1)?;
+        }
+        Node::Group { children } => {
+            for child in children {
+                collect_placements(nodes, *child, translation, placements, depth + 1)?;
+            }
+        }
+        Node::Shape { models } => {
+            placements.extend(models.iter().map(|&model| (model, translation)));
+        }
+    }
+
+    Ok(())
+}
+
+// メディアンカット法で色を減色し、パレットと元の色からパレットのインデックスへの対応を返す
+fn quantize(counts: FxHashMap<Color<u8>, u32>) -> (Vec<Color<u8>>, FxHashMap<Color<u8>, u8>) {
+    let mut boxes = vec![counts.into_iter().collect::<Vec<_>>()];
+
+    while boxes.len() < MAX_PALETTE_SIZE {
+        // 色の範囲が最も広い箱を分割する
+        let target = boxes.iter().enumerate()
+            .map(|(i, colors)| {
+                let (channel, range) = widest_channel(colors);
+                (i, channel, range)
+            })
+            .filter(|(_, _, range)| *range > 0)
+            .max_by_key(|(i, _, range)| (*range, std::cmp::Reverse(*i)));
+
+        let Some((i, channel, _)) = target else { break };
+
+        let mut colors = boxes.swap_remove(i);
+        colors.sort_by_key(|(color, _)| color[channel]);
+
+        let total = colors.iter().map(|(_, count)| *count as u64).sum::<u64>();
+        let mut cumulative = 0;
+        let median = colors.iter().position(|(_, count)| {
+            cumulative += *count as u64;
+            cumulative * 2 >= total
+        }).unwrap();
+
+        let split = (median + 1).clamp(1, colors.len() - 1);
+        let upper = colors.split_off(split);
+
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+
+    let mut indices = FxHashMap::default();
+
+    let palette = boxes.into_iter().enumerate().map(|(i, colors)| {
+        let total = colors.iter().map(|(_, count)| *count as u64).sum::<u64>();
+        let sum = colors.iter().fold([0_u64; 3], |sum, (color, count)| {
+            [0, 1, 2].map(|c| sum[c] + color[c] as u64 * *count as u64)
+        });
+
+        colors.iter().for_each(|(color, _)| {
+            indices.insert(*color, i as u8 + 1);
+        });
+
+        Color::new(sum.map(|a| ((a + total / 2) / total) as u8))
+    }).collect();
+
+    (palette, indices)
+}
+
+// 色の範囲が最も広いチャンネルと、その範囲を返す
+fn widest_channel(colors: &[(Color<u8>, u32)]) -> (usize, u8) {
+    (0..3).map(|c| {
+        let min = colors.iter().map(|(color, _)| color[c]).min().unwrap_or(0);
+        let max = colors.iter().map(|(color, _)| color[c]).max().unwrap_or(0);
+        (c, max - min)
+    }).max_by_key(|(c, range)| (*range, std::cmp::Reverse(*c))).unwrap()
+}
+
+fn transform_node(node_id: i32, child: i32, layer_id: i32, translation: Point3D<i32>) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_i32(&mut buf, node_id);
+    write_dict(&mut buf, &[]);
+    write_i32(&mut buf, child);
+    write_i32(&mut buf, -1);
+    write_i32(&mut buf, layer_id);
+    write_i32(&mut buf, 1);
+
+    let [x, y, z] = translation.data;
+    write_dict(&mut buf, &[("_t", &format!("{} {} {}", x, y, z))]);
+
+    buf
+}
+
+fn write_i32(buf: &mut Vec<u8>, value: i32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_dict(buf: &mut Vec<u8>, dict: &[(&str, &str)]) {
+    write_i32(buf, dict.len() as i32);
+    dict.iter().for_each(|(key, value)| {
+        [key, value].iter().for_each(|s| {
+            write_i32(buf, s.len() as i32);
+            buf.extend_from_slice(s.as_bytes());
+        });
+    });
+}
+
+fn write_chunk(buf: &mut Vec<u8>, id: &[u8; 4], content: &[u8]) {
+    buf.extend_from_slice(id);
+    write_i32(buf, content.len() as i32);
+    write_i32(buf, 0);
+    buf.extend_from_slice(content);
+}
+
+struct ByteCursor<'a> {
+    buf: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteCursor<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], anyhow::Error> {
+        let bytes = self.buf.get(self.position..self.position + len).ok_or_else(|| anyhow!("Unexpected end of file"))?;
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn i32(&mut self) -> Result<i32, anyhow::Error> {
+        let bytes = self.bytes(4)?;
+        Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn size(&mut self) -> Result<usize, anyhow::Error> {
+        let size = self.i32()?;
+        ensure!(size >= 0, "Invalid size");
+        Ok(size as usize)
+    }
+
+    fn string(&mut self) -> Result<String, anyhow::Error> {
+        let len = self.size()?;
+        Ok(String::from_utf8_lossy(self.bytes(len)?).into_owned())
+    }
+
+    fn dict(&mut self) -> Result<FxHashMap<String, String>, anyhow::Error> {
+        let len = self.size()?;
+        (0..len).map(|_| Ok((self.string()?, self.string()?))).collect()
+    }
+
+    fn chunk_header(&mut self) -> Result<(&'a [u8], usize, usize), anyhow::Error> {
+        let id = self.bytes(4)?;
+        let content_size = self.size()?;
+        let children_size = self.size()?;
+        Ok((id, content_size, children_size))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fxhash::FxBuildHasher;
+
+    use crate::collection::{HMap3DVoxelCollection, VoxelCollection};
+    use crate::element::{Color, Point3D};
+    use crate::vox::Vox;
+
+    type VC = HMap3DVoxelCollection<i32, u8, u8, FxBuildHasher>;
+
+    #[test]
+    fn test_round_trip_split_models() {
+        let points = vec![
+            (Point3D::new([-10, 0, 0]), Color::new([255, 0, 0])),
+            (Point3D::new([300, 5, 2]), Color::new([0, 255, 0])),
+            (Point3D::new([0, 600, 1]), Color::new([0, 0, 255])),
+        ];
+
+        let vc = VC::builder().points(points.clone()).build();
+        let vox = Vox::from_voxel_collection(vc);
+
+        assert_eq!(vox.models().len(), 3);
+        assert!(vox.models().iter().all(|model| model.size.data.iter().all(|&a| a <= 256)));
+
+        let vc = VC::from_vox(vox.into_buf().as_slice(), 0.5).unwrap();
+        assert_eq!(vc.get_resolution(), 0.5);
+
+        let mut actual = vc.into_points();
+        actual.sort_by_key(|(point, _)| point.data);
+
+        // 最小値が原点になるように平行移動される
+        let mut expected = points.into_iter().map(|(point, color)| (point - Point3D::new([-10, 0, 0]), color)).collect::<Vec<_>>();
+        expected.sort_by_key(|(point, _)| point.data);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_palette_quantization() {
+        let points = (0..1000).map(|i| {
+            let color = Color::new([(i % 256) as u8, (i / 4 % 256) as u8, 128]);
+            (Point3D::new([i % 10, i / 10 % 10, i / 100]), color)
+        }).collect::<Vec<_>>();
+
+        let vox = Vox::from_voxel_collection(VC::builder().points(points).build());
+
+        assert_eq!(vox.palette().len(), 255);
+        assert_eq!(vox.models().len(), 1);
+        assert_eq!(vox.models()[0].voxels.len(), 1000);
+        assert!(vox.models()[0].voxels.iter().all(|(_, index)| *index >= 1));
+    }
+
+    // RGBAチャンクを持たない、1つのボクセルのみのファイル
+    fn vox_without_palette(index: u8) -> Vec<u8> {
+        let chunk = |id: &[u8], content: Vec<i32>| {
+            let mut buf = id.to_vec();
+            buf.extend_from_slice(&(content.len() as i32 * 4).to_le_bytes());
+            buf.extend_from_slice(&0_i32.to_le_bytes());
+            content.into_iter().for_each(|a| buf.extend_from_slice(&a.to_le_bytes()));
+            buf
+        };
+
+        let mut children = chunk(b"SIZE", vec![1, 1, 1]);
+        children.extend(chunk(b"XYZI", vec![1, i32::from_le_bytes([0, 0, 0, index])]));
+
+        let mut buf = b"VOX ".to_vec();
+        buf.extend_from_slice(&150_i32.to_le_bytes());
+        buf.extend_from_slice(b"MAIN");
+        buf.extend_from_slice(&0_i32.to_le_bytes());
+        buf.extend_from_slice(&(children.len() as i32).to_le_bytes());
+        buf.extend(children);
+        buf
+    }
+
+    #[test]
+    fn test_default_palette() {
+        let vox = Vox::from_reader(vox_without_palette(1).as_slice()).unwrap();
+
+        assert_eq!(vox.palette().len(), 255);
+        assert_eq!(vox.palette()[0], Color::new([255, 255, 255]));
+        assert_eq!(vox.palette()[254], Color::new([17, 17, 17]));
+
+        let vc = VC::from_vox(vox_without_palette(1).as_slice(), 1.).unwrap();
+        assert_eq!(vc.into_points(), vec![(Point3D::new([0, 0, 0]), Color::new([255, 255, 255]))]);
+
+        assert!(VC::from_vox(vox_without_palette(0).as_slice(), 1.).is_err());
+    }
+}
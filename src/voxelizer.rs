@@ -1,11 +1,12 @@
 use coordinate_transformer::{ll2pixel, pixel_resolution, ZoomLv};
 use dashmap::DashMap;
-use fxhash::FxBuildHasher;
+use fxhash::{FxBuildHasher, FxHashMap};
+use num::One;
 use num::traits::AsPrimitive;
 
 use crate::build_voxelizer::VoxelizerOption;
-use crate::collection::{PointCloud, VoxelCollection};
-use crate::element::{Point2D, Point3D, Voxel};
+use crate::collection::VoxelCollection;
+use crate::element::{Point2D, Point3D, UInt, Voxel};
 use crate::voxelizer::private::PrivateVoxelizerMethod;
 
 mod private {
@@ -33,6 +34,9 @@ mod private {
     }
 }
 
+/// ボクセルの座標値と、そのボクセルの重み(ボクセル内に存在した点の数)の対応です。
+pub type WeightField<P, W> = FxHashMap<Point3D<P>, W>;
+
 pub trait Voxelizer<Option: VoxelizerOption>: PrivateVoxelizerMethod<Option>
 {
     ///　分解能を指定して新しいインスタンスを生成します。
@@ -42,9 +46,40 @@ pub trait Voxelizer<Option: VoxelizerOption>: PrivateVoxelizerMethod<Option>
     /// この関数が呼ばれた時点で座標計算を行います。
     fn add<T: VoxelCollection<Option::InPoint, Option::Weight, Option::Color>>(&mut self, pc: T);
 
+    /// 点が含まれるボクセルの座標値を返します。
+    /// `add`で追加された点は、オフセットを適用した座標値に対してこの関数で求めたボクセルに集約されます。
+    fn voxel_point(&self, point: Point3D<Option::InPoint>) -> Point3D<Option::OutPoint>;
+
+    /// `voxel_point`で求めたボクセルの座標値を、現在の状態で`finish`を呼んだ場合に出力されるボクセルデータの`to_vec`で得られる座標値に変換します。
+    /// [`MapTileVoxelizer::fit_offset_to_min_tile`]のように、点の追加後にオフセットが変更された場合の対応付けに使用します。
+    fn output_point(&self, voxel_point: Point3D<Option::OutPoint>) -> Point3D<Option::OutPoint>;
+
+    /// 出力するボクセルの重み(ボクセル内に存在した点の数)の最小値を設定します。
+    /// `finish`の前に、重みがこの値未満のボクセルを取り除くため、孤立した1点からボクセルが生成されることを防げます。
+    /// 初期値は1で、すべてのボクセルを出力します。
+    fn set_min_weight(&mut self, min_weight: Option::Weight);
+
     /// 最終的に指定された形式でボクセルデータを返します。
     /// 出力されるボクセルは、座標値を整数値で表された原点から数えたボクセルの位置とし、ボクセルのサイズは分解能として保持します。
     fn finish(self) -> Option::OutVC;
+
+    /// `finish`と同様にボクセルデータを返し、併せて各ボクセルの重みを返します。
+    /// 重みの座標値は、出力されたボクセルデータの`to_vec`で得られる座標値と対応します。
+    /// 点の密度(点/m³)は、重みをボクセルの体積(分解能の3乗)で割ることで求められます。
+    fn finish_with_weight(self) -> (Option::OutVC, WeightField<Option::OutPoint, Option::Weight>);
+}
+
+// 重みが`min_weight`未満のボクセルを取り除く
+fn retain_min_weight<P, W, C, VC>(vc: &mut VC, min_weight: W)
+where
+    P: crate::element::Number,
+    W: UInt,
+    C: UInt,
+    VC: VoxelCollection<P, W, C>,
+{
+    if min_weight > W::one() {
+        vc.retain(|_, voxel| voxel.weight >= min_weight);
+    }
 }
 
 /// 与えられた点群を指定された分解能でボクセル化するための最も単純な構造体です。
@@ -53,6 +88,7 @@ pub struct SimpleVoxelizer<Option: VoxelizerOption>
 {
     field: Option::CalcVC,
     resolution: f64,
+    min_weight: Option::Weight,
 }
 
 impl<Option: VoxelizerOption> PrivateVoxelizerMethod<Option> for SimpleVoxelizer<Option>
@@ -75,6 +111,7 @@ where
         Self {
             field: Option::CalcVC::default(),
             resolution: 1.,
+            min_weight: Option::Weight::one(),
         }
     }
 }
@@ -94,67 +131,188 @@ where
                 SimpleVoxelizer {
                     field: Option::CalcVC::default(),
                     resolution,
+                    min_weight: Option::Weight::one(),
                 },
             _ => panic!("Resolution is not mater"),
         }
     }
 
     fn add<T: VoxelCollection<Option::InPoint, Option::Weight, Option::Color>>(&mut self, pc: T) {
-        let voxels = pc.into_vec_with_offset().into_iter().map(|(point, voxel)| {
-            let point = (point.as_::<f64>() / self.resolution).batch(|a| a.floor()).as_::<Option::OutPoint>();
+        // 蓄積済みのボクセルを作り直さないように、既存のボクセルへ直接加算する
+        pc.into_iter_with_offset().for_each(|(point, voxel)| {
+            let point = self.voxel_point(point);
 
             let color = voxel.color.as_::<Option::ColorPool>();
-            let voxel = Voxel::new(color);
-            (point, voxel)
-        }).collect::<Vec<_>>();
+            self.field.insert_one(point, Voxel::new(color));
+        });
+    }
+
+    fn voxel_point(&self, point: Point3D<Option::InPoint>) -> Point3D<Option::OutPoint> {
+        mater_voxel_point(point.as_(), self.resolution).as_()
+    }
 
-        let pc = PointCloud::<Option::OutPoint, Option::Weight, Option::ColorPool>::builder().voxels(voxels).build();
+    // 出力のオフセットはボクセルの座標値に適用されないため、そのまま返す
+    fn output_point(&self, voxel_point: Point3D<Option::OutPoint>) -> Point3D<Option::OutPoint> {
+        voxel_point
+    }
 
-        self.field = self.field.clone().merge(pc).unwrap();
+    fn set_min_weight(&mut self, min_weight: Option::Weight) {
+        self.min_weight = min_weight;
     }
+
     fn finish(mut self) -> Option::OutVC
     {
+        retain_min_weight(&mut self.field, self.min_weight);
+
         let current_bounds = if self.field.has_bounds() {
             Some(self.field.get_bounds())
         } else { None };
 
         let offset = self.field.get_offset();
 
-        let points = self.field.into_vec().into_iter().map(Self::average_color).collect();
+        let points = self.field.into_iter().map(Self::average_color).collect();
 
 
         Option::OutVC::new(points, current_bounds, offset, self.resolution)
     }
+
+    fn finish_with_weight(mut self) -> (Option::OutVC, WeightField<Option::OutPoint, Option::Weight>) {
+        retain_min_weight(&mut self.field, self.min_weight);
+
+        let weights = self.field.iter().map(|(point, voxel)| (point, voxel.weight)).collect();
+
+        (self.finish(), weights)
+    }
 }
 
+// タイル座標ごとのボクセル
+type TileField<Option> = DashMap<Point2D<u32>, <Option as VoxelizerOption>::CalcVC, FxBuildHasher>;
+
 /// 与えられた点群をタイル座標を基準にボクセル化するための構造体です。
 /// 指定される分解能は[`Resolution::Tile`]である必要があります。
 pub struct MapTileVoxelizer<Option: VoxelizerOption>
 {
     // value: (Resolution, VoxelsCollection)
-    field: DashMap<Point2D<u32>, Option::CalcVC, FxBuildHasher>,
+    field: TileField<Option>,
     zoom_lv: ZoomLv,
+    min_weight: Option::Weight,
 }
 
 impl<Option: VoxelizerOption> MapTileVoxelizer<Option> {
+    // 重みが最小値未満のボクセルを取り除き、空になったタイルを削除する
+    fn retain_min_weight(&mut self) {
+        let min_weight = self.min_weight;
+
+        self.field.retain(|_tile, pc| {
+            retain_min_weight(pc, min_weight);
+            pc.iter().next().is_some()
+        });
+    }
+
     ///　出力をタイルごとに分割して返します。
     /// タプルの1要素目としてタイル座標(x, y)、2要素目としてボクセルデータが格納されます。
-    pub fn finish_tiles(self) -> Vec<(Point2D<u32>, Option::OutVC)>
+    pub fn finish_tiles(mut self) -> Vec<(Point2D<u32>, Option::OutVC)>
+    where
+        Option::Weight: AsPrimitive<Option::ColorPool>,
+        Option::ColorPool: AsPrimitive<Option::Weight>,
+    {
+        self.retain_min_weight();
+
+        self.field.into_iter().map(|(tile, pc)| {
+            (tile, Self::finish_tile(pc))
+        }).collect::<Vec<_>>()
+    }
+
+    /// `min_zoom_lv`から現在のズームレベルまでのすべてのズームレベルについて、出力をタイルごとに分割して返します。
+    /// 点群のボクセル化は現在のズームレベルでのみ行い、それより小さいズームレベルのボクセルは2x2x2個の子ボクセルを集約して生成します。
+    /// 集約されたボクセルの色は子ボクセルの重み付き平均、重みは子ボクセルの重みの和になります。
+    /// タプルの1要素目としてズームレベル、2要素目としてタイル座標(x, y)、3要素目としてボクセルデータが格納されます。
+    pub fn finish_pyramid(mut self, min_zoom_lv: ZoomLv) -> Vec<(ZoomLv, Point2D<u32>, Option::OutVC)>
     where
         Option::Weight: AsPrimitive<Option::ColorPool>,
         Option::ColorPool: AsPrimitive<Option::Weight>,
+        Option::OutPoint: AsPrimitive<u32> + AsPrimitive<f64>,
+        u32: AsPrimitive<Option::OutPoint>,
+        f64: AsPrimitive<Option::OutPoint>,
     {
-        self.field.into_iter().map(|(tile, mut pc)| {
-            let bounds = pc.get_bounds();
-            let offset = pc.get_offset();
-            let resolution = pc.get_resolution();
+        // 重みの閾値は現在のズームレベルのボクセルにのみ適用し、集約されたボクセルには適用しない
+        self.retain_min_weight();
 
-            let voxels = pc.into_vec().into_iter().map(Self::average_color).collect();
+        let mut levels = vec![(self.zoom_lv, self.field)];
 
-            (tile, Option::OutVC::new(voxels, Some(bounds), offset, resolution))
+        while let Some(parent_zoom_lv) = (levels.last().unwrap().0 as u8).checked_sub(1)
+            .filter(|&zoom_lv| zoom_lv >= min_zoom_lv as u8)
+            .and_then(|zoom_lv| ZoomLv::parse(zoom_lv).ok())
+        {
+            let parent = Self::aggregate_parent(&levels.last().unwrap().1);
+            levels.push((parent_zoom_lv, parent));
+        }
+
+        levels.into_iter().flat_map(|(zoom_lv, field)| {
+            field.into_iter().map(move |(tile, pc)| {
+                (zoom_lv, tile, Self::finish_tile(pc))
+            })
         }).collect::<Vec<_>>()
     }
 
+    // 1つ小さいズームレベルのタイルを、2x2x2個の子ボクセルを集約して生成する
+    fn aggregate_parent(field: &TileField<Option>) -> TileField<Option>
+    where
+        Option::OutPoint: AsPrimitive<u32> + AsPrimitive<f64>,
+        u32: AsPrimitive<Option::OutPoint>,
+        f64: AsPrimitive<Option::OutPoint>,
+    {
+        let parent = TileField::<Option>::with_hasher(FxBuildHasher::default());
+
+        field.iter().for_each(|entry| {
+            let pc = entry.value();
+
+            let resolution = pc.get_resolution() * 2.;
+            let offset = Self::parent_point(pc.get_offset());
+
+            pc.iter().for_each(|(point, voxel)| {
+                let point = Self::parent_point(point);
+                let tile = point.fit::<2>().as_::<u32>() / 256_u32;
+
+                parent.entry(tile).and_modify(|field| {
+                    field.insert_one(point, voxel);
+                }).or_insert_with(|| {
+                    Option::CalcVC::builder()
+                        .voxels(vec![(point, voxel)])
+                        .offset(offset)
+                        .resolution(resolution)
+                        .build()
+                });
+            });
+        });
+
+        parent
+    }
+
+    // 1つ小さいズームレベルにおける座標を返す
+    // 負の値についても切り捨てになるように、浮動小数点数で計算する
+    fn parent_point(point: Point3D<Option::OutPoint>) -> Point3D<Option::OutPoint>
+    where
+        Option::OutPoint: AsPrimitive<f64>,
+        f64: AsPrimitive<Option::OutPoint>,
+    {
+        (point.as_::<f64>() / 2.).batch(|a| a.floor()).as_()
+    }
+
+    fn finish_tile(mut pc: Option::CalcVC) -> Option::OutVC
+    where
+        Option::Weight: AsPrimitive<Option::ColorPool>,
+        Option::ColorPool: AsPrimitive<Option::Weight>,
+    {
+        let bounds = pc.get_bounds();
+        let offset = pc.get_offset();
+        let resolution = pc.get_resolution();
+
+        let voxels = pc.into_iter().map(Self::average_color).collect();
+
+        Option::OutVC::new(voxels, Some(bounds), offset, resolution)
+    }
+
     /// ボクセルを内包する最小のタイルに合わせてオフセットを調整します。
     pub fn fit_offset_to_min_tile(&mut self)
     where
@@ -198,6 +356,7 @@ where
                 MapTileVoxelizer {
                     field: DashMap::with_hasher(FxBuildHasher::default()),
                     zoom_lv,
+                    min_weight: Option::Weight::one(),
                 },
             _ => panic!("Resolution is not tile"),
         }
@@ -205,18 +364,10 @@ where
 
     fn add<T: VoxelCollection<Option::InPoint, Option::Weight, Option::Color>>(&mut self, pc: T)
     {
-        pc.into_vec().into_iter().for_each(|(point, voxel)| {
-            let long = point[0].as_();
-            let lat = point[1].as_();
-
-            let (pixel_x, pixel_y) = ll2pixel((long, lat), self.zoom_lv);
-            let tile = Point2D::new([pixel_x / 256, pixel_y / 256]);
-
-            let resolution = pixel_resolution(lat, self.zoom_lv);
-
-            let pixel_z = (point[2].as_() / resolution).floor() as u32;
+        pc.into_iter_with_offset().for_each(|(point, voxel)| {
+            let (tile, point, resolution) = tile_voxel_point(Point3D::new(point.data.map(|a| a.as_())), self.zoom_lv);
 
-            let point = Point3D::new([pixel_x, pixel_y, pixel_z]).as_();
+            let point = point.as_();
             let voxel = Voxel::new(voxel.color.as_::<Option::ColorPool>());
 
             self.field.entry(tile).and_modify(|field| {
@@ -231,21 +382,74 @@ where
     }
 
 
-    fn finish(self) -> Option::OutVC
+    fn voxel_point(&self, point: Point3D<Option::InPoint>) -> Point3D<Option::OutPoint> {
+        tile_voxel_point(Point3D::new(point.data.map(|a| a.as_())), self.zoom_lv).1.as_()
+    }
+
+    // `finish`はタイルごとのオフセットを適用した座標値を出力するため、ボクセルを含むタイルのオフセットを減算する
+    fn output_point(&self, voxel_point: Point3D<Option::OutPoint>) -> Point3D<Option::OutPoint> {
+        let tile = Point2D::new([voxel_point[0].as_() / 256, voxel_point[1].as_() / 256]);
+
+        match self.field.get(&tile) {
+            Some(field) => voxel_point - field.get_offset(),
+            None => voxel_point,
+        }
+    }
+
+    fn set_min_weight(&mut self, min_weight: Option::Weight) {
+        self.min_weight = min_weight;
+    }
+
+    fn finish(mut self) -> Option::OutVC
     {
+        self.retain_min_weight();
+
+        if self.field.is_empty() {
+            return Option::OutVC::default();
+        }
+
         let (_tile, vcf_list): (Vec<_>, Vec<_>) = self.field.into_iter().unzip();
 
         let min_resolution = vcf_list.iter().map(|vcf| vcf.get_resolution()).reduce(|a, b| a.min(b)).unwrap();
         let max_resolution = vcf_list.iter().map(|vcf| vcf.get_resolution()).reduce(|a, b| a.max(b)).unwrap();
         let average_resolution = (min_resolution + max_resolution) / 2.;
 
-        let voxels = vcf_list.into_iter().flat_map(|v| { v.into_vec_with_offset() }).map(Self::average_color).collect();
+        let voxels = vcf_list.into_iter().flat_map(|v| v.into_iter_with_offset()).map(Self::average_color).collect();
 
         Option::OutVC::builder()
             .voxels(voxels)
             .resolution(average_resolution)
             .build()
     }
+
+    fn finish_with_weight(mut self) -> (Option::OutVC, WeightField<Option::OutPoint, Option::Weight>) {
+        self.retain_min_weight();
+
+        let weights = self.field.iter().flat_map(|tile| {
+            tile.value().iter_with_offset().map(|(point, voxel)| (point, voxel.weight)).collect::<Vec<_>>()
+        }).collect();
+
+        (self.finish(), weights)
+    }
+}
+
+// メートル単位の分解能で、点が含まれるボクセルの座標を返す
+pub(crate) fn mater_voxel_point(point: Point3D<f64>, resolution: f64) -> Point3D<f64> {
+    (point / resolution).batch(|a| a.floor())
+}
+
+// 経緯度で表された点が含まれるタイルの座標、ボクセルのピクセル座標、その地点での分解能を返す
+pub(crate) fn tile_voxel_point(point: Point3D<f64>, zoom_lv: ZoomLv) -> (Point2D<u32>, Point3D<u32>, f64) {
+    let [long, lat, z] = point.data;
+
+    let (pixel_x, pixel_y) = ll2pixel((long, lat), zoom_lv);
+    let tile = Point2D::new([pixel_x / 256, pixel_y / 256]);
+
+    let resolution = pixel_resolution(lat, zoom_lv);
+
+    let pixel_z = (z / resolution).floor() as u32;
+
+    (tile, Point3D::new([pixel_x, pixel_y, pixel_z]), resolution)
 }
 
 /// ボクセライザーの分解能を表します。
@@ -261,3 +465,84 @@ pub enum Resolution {
         zoom_lv: ZoomLv,
     },
 }
+
+#[cfg(test)]
+mod test {
+    use coordinate_transformer::{ll2pixel, pixel2ll, pixel_resolution, ZoomLv};
+    use ordered_float::OrderedFloat;
+
+    use crate::build_voxelizer::{BuildMapTileVoxelizerDefault, BuildSimpleVoxelizerDefault, BuildVoxelizer};
+    use crate::collection::{PointCloud, VoxelCollection};
+    use crate::element::{Color, Point3D};
+    use crate::voxelizer::{Resolution, Voxelizer};
+
+    #[test]
+    fn test_finish_pyramid() {
+        // ズームレベル17で隣接する2つのピクセルの中心
+        let (x, y) = ll2pixel((139.7649308_f64.to_radians(), 35.6812405_f64.to_radians()), ZoomLv::Lv17);
+        let x = x & !1;
+
+        let points = [(x, 100_u16), (x + 1, 200_u16)].into_iter().map(|(x, red)| {
+            let (long, lat) = pixel2ll((x, y), ZoomLv::Lv17);
+            let (next_long, next_lat) = pixel2ll((x + 1, y + 1), ZoomLv::Lv17);
+
+            let point = Point3D::new([(long + next_long) / 2., (lat + next_lat) / 2., 0.1].map(OrderedFloat::from));
+            (point, Color::new([red, 0, 0]))
+        }).collect::<Vec<_>>();
+
+        let mut voxelizer = BuildMapTileVoxelizerDefault::build_voxelizer(Resolution::Tile { zoom_lv: ZoomLv::Lv17 });
+        voxelizer.add(PointCloud::builder().points(points).build());
+
+        let pyramid = voxelizer.finish_pyramid(ZoomLv::Lv15);
+
+        let mut zoom_levels = pyramid.iter().map(|(zoom_lv, _, _)| *zoom_lv).collect::<Vec<_>>();
+        zoom_levels.dedup();
+        assert_eq!(zoom_levels, vec![ZoomLv::Lv17, ZoomLv::Lv16, ZoomLv::Lv15]);
+
+        pyramid.into_iter().for_each(|(zoom_lv, tile, vc)| {
+            let shift = ZoomLv::Lv17 as i32 - zoom_lv as i32;
+            let resolution = pixel_resolution(35.6812405_f64.to_radians(), ZoomLv::Lv17) * 2_f64.powi(shift);
+            assert!((vc.get_resolution() - resolution).abs() < 1e-3);
+
+            let points = vc.into_points();
+
+            if zoom_lv == ZoomLv::Lv17 {
+                assert_eq!(points.len(), 2);
+                return;
+            }
+
+            assert_eq!(points.len(), 1);
+
+            let (point, color) = points[0];
+            assert_eq!(point.fit::<2>().as_::<u32>() / 256_u32, tile);
+            assert_eq!(color, Color::new([150, 0, 0]));
+            assert_eq!(point[0], (x >> shift) as i32);
+        });
+    }
+
+    #[test]
+    fn test_min_weight() {
+        // 同じボクセルに入る3点と、孤立した1点
+        let points = [[0.1, 0.1, 0.1], [0.2, 0.2, 0.2], [0.3, 0.3, 0.3], [5.1, 5.1, 5.1]].into_iter().map(|p| {
+            (Point3D::new(p.map(OrderedFloat::from)), Color::new([10_u8, 20, 30]))
+        }).collect::<Vec<_>>();
+
+        let mut voxelizer = BuildSimpleVoxelizerDefault::build_voxelizer(Resolution::Mater(1.));
+        voxelizer.add(PointCloud::builder().points(points.clone()).build());
+        assert_eq!(voxelizer.finish().to_vec().len(), 2);
+
+        let mut voxelizer = BuildSimpleVoxelizerDefault::build_voxelizer(Resolution::Mater(1.));
+        voxelizer.add(PointCloud::builder().points(points).build());
+        voxelizer.set_min_weight(2);
+
+        let (vc, weights) = voxelizer.finish_with_weight();
+        let voxels = vc.to_vec();
+
+        assert_eq!(voxels.len(), 1);
+        assert_eq!(weights.len(), 1);
+
+        let (point, voxel) = voxels[0];
+        assert_eq!(voxel.color, Color::new([10, 20, 30]));
+        assert_eq!(weights[&point], 3);
+    }
+}